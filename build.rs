@@ -0,0 +1,34 @@
+//! Passes the versions of atspi and zbus that Cargo.lock resolved to the build, for
+//! `--version` and the metadata of snapshots.
+
+use std::path::Path;
+
+/// The crates whose resolved versions are passed, and the variables they are passed in.
+const CRATES: &[(&str, &str)] = &[
+    ("atspi", "A11Y_APP_ATSPI_VERSION"),
+    ("zbus", "A11Y_APP_ZBUS_VERSION"),
+];
+
+fn main() {
+    let lock = Path::new(&std::env::var("CARGO_MANIFEST_DIR").unwrap()).join("Cargo.lock");
+    println!("cargo:rerun-if-changed={}", lock.display());
+    // Built as a dependency, the lock file of the build is not at hand.
+    let lock = std::fs::read_to_string(&lock).unwrap_or_default();
+
+    for (name, variable) in CRATES {
+        let version = resolved_version(&lock, name).unwrap_or("unknown");
+        println!("cargo:rustc-env={variable}={version}");
+    }
+}
+
+/// The version of the package `name` in a Cargo.lock, the first if there are several. The
+/// `version` of a package follows its `name`.
+fn resolved_version<'a>(lock: &'a str, name: &str) -> Option<&'a str> {
+    let name_line = format!("name = \"{name}\"");
+    let mut lines = lock.lines();
+    lines.find(|line| *line == name_line)?;
+    lines
+        .next()?
+        .strip_prefix("version = \"")?
+        .strip_suffix('"')
+}
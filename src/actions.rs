@@ -0,0 +1,276 @@
+//! Discovering and invoking the actions a node offers through the Action interface.
+
+use crate::{
+    target::{self, Selected},
+    Result,
+};
+use argh::{ArgsInfo, FromArgs};
+use atspi::{proxy::proxy_ext::ProxyExt, AccessibilityConnection, Role, State};
+use std::time::{Duration, Instant};
+
+/// The actions that flip a toggle, in order of preference.
+const TOGGLE_ACTIONS: &[&str] = &["toggle", "click", "press", "activate"];
+
+/// Roles whose on/off state is `checked`; toggle buttons may use `pressed` instead.
+const CHECKABLE_ROLES: &[Role] = &[
+    Role::CheckBox,
+    Role::CheckMenuItem,
+    Role::RadioButton,
+    Role::RadioMenuItem,
+];
+
+/// How long to wait for the toolkit to update the state after toggling.
+const TOGGLE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// List the actions of a node: their names, descriptions and key bindings
+#[derive(FromArgs, ArgsInfo)]
+#[argh(subcommand, name = "actions")]
+pub struct ActionsArgs {
+    /// the application, by bus name or application name
+    #[argh(positional)]
+    app: String,
+
+    /// the node, by the child indices leading to it from the application's root, e.g. `0/2/1`
+    #[argh(option)]
+    path: Option<String>,
+
+    /// the first node with this role and/or name, e.g. `push button "Save"`
+    #[argh(option)]
+    query: Option<String>,
+}
+
+pub async fn actions(a11y: &AccessibilityConnection, args: ActionsArgs) -> Result<()> {
+    let conn = a11y.connection();
+    let node = target::select(conn, &args.app, args.path.as_deref(), args.query.as_deref()).await?;
+    banner!("Node: {} at {}", node.description, node.location());
+
+    let proxies = node.proxy.proxies().await?;
+    let Ok(action) = proxies.action().await else {
+        println!("The node does not implement the Action interface.");
+        return Ok(());
+    };
+
+    let actions = action.get_actions().await?;
+    if actions.is_empty() {
+        println!("The node has no actions.");
+        return Ok(());
+    }
+
+    let mut rows = Vec::with_capacity(actions.len());
+    for (index, a) in actions.into_iter().enumerate() {
+        let localized = action
+            .get_localized_name(index as i32)
+            .await
+            .unwrap_or_default();
+        rows.push(vec![
+            index.to_string(),
+            a.name,
+            localized,
+            a.description,
+            a.keybinding,
+        ]);
+    }
+    crate::print_table(
+        &[
+            "Index",
+            "Name",
+            "Localized name",
+            "Description",
+            "Key binding",
+        ],
+        &rows,
+    );
+
+    Ok(())
+}
+
+/// Perform an action of a node, by name (e.g. `click`, `press`, `toggle`) or by index
+#[derive(FromArgs, ArgsInfo)]
+#[argh(subcommand, name = "do-action")]
+pub struct DoActionArgs {
+    /// the application, by bus name or application name
+    #[argh(positional)]
+    app: String,
+
+    /// the name or index of the action, as listed by `actions`
+    #[argh(positional)]
+    action: String,
+
+    /// the node, by the child indices leading to it from the application's root, e.g. `0/2/1`
+    #[argh(option)]
+    path: Option<String>,
+
+    /// the first node with this role and/or name, e.g. `push button "Save"`
+    #[argh(option)]
+    query: Option<String>,
+}
+
+pub async fn do_action(a11y: &AccessibilityConnection, args: DoActionArgs) -> Result<()> {
+    let conn = a11y.connection();
+    let node = target::select(conn, &args.app, args.path.as_deref(), args.query.as_deref()).await?;
+    let name = perform(&node, &args.action).await?;
+    if crate::dry_run() {
+        return Ok(());
+    }
+    println!(
+        "Performed {name:?} on {} at {}",
+        node.description,
+        node.location()
+    );
+    Ok(())
+}
+
+/// Perform the node's action given by name or index, returns the action's name.
+pub(crate) async fn perform(node: &Selected, action_name: &str) -> Result<String> {
+    let proxies = node.proxy.proxies().await?;
+    let Ok(action) = proxies.action().await else {
+        return Err(format!(
+            "{} at {} does not implement the Action interface",
+            node.description,
+            node.location()
+        )
+        .into());
+    };
+
+    let actions = action.get_actions().await?;
+    let index = match action_name.parse::<usize>() {
+        Ok(index) if index < actions.len() => index,
+        _ => actions
+            .iter()
+            .position(|a| a.name.eq_ignore_ascii_case(action_name))
+            .ok_or_else(|| {
+                let names: Vec<&str> = actions.iter().map(|a| a.name.as_str()).collect();
+                format!(
+                    "{} has no action {:?}, its actions are: {}",
+                    node.description,
+                    action_name,
+                    names.join(", ")
+                )
+            })?,
+    };
+
+    let name = actions[index].name.clone();
+    if crate::dry_run() {
+        println!(
+            "Would perform {name:?} on {} at {}",
+            node.description,
+            node.location()
+        );
+        return Ok(name);
+    }
+    let performed = action.do_action(index as i32).await?;
+    if performed {
+        Ok(name)
+    } else {
+        Err(format!(
+            "{} at {} reported that {name:?} could not be performed",
+            node.description,
+            node.location()
+        )
+        .into())
+    }
+}
+
+/// Flip a check box, toggle button or similar, and verify its state flipped
+#[derive(FromArgs, ArgsInfo)]
+#[argh(subcommand, name = "toggle")]
+pub struct ToggleArgs {
+    /// the application, by bus name or application name
+    #[argh(positional)]
+    app: String,
+
+    /// the node, by the child indices leading to it from the application's root, e.g. `0/2/1`
+    #[argh(option)]
+    path: Option<String>,
+
+    /// the first node with this role and/or name, e.g. `check box "Wrap lines"`
+    #[argh(option)]
+    query: Option<String>,
+}
+
+pub async fn toggle(a11y: &AccessibilityConnection, args: ToggleArgs) -> Result<()> {
+    let conn = a11y.connection();
+    let node = target::select(conn, &args.app, args.path.as_deref(), args.query.as_deref()).await?;
+
+    let role = node.proxy.get_role().await?;
+    let states = node.proxy.get_state().await?;
+    let state = if states.contains(State::Checked)
+        || states.contains(State::Checkable)
+        || CHECKABLE_ROLES.contains(&role)
+    {
+        State::Checked
+    } else if states.contains(State::Pressed) || role == Role::ToggleButton {
+        State::Pressed
+    } else {
+        return Err(format!(
+            "{} at {} is neither checkable nor a toggle button",
+            node.description,
+            node.location()
+        )
+        .into());
+    };
+    let before = states.contains(state);
+
+    let proxies = node.proxy.proxies().await?;
+    let Ok(action) = proxies.action().await else {
+        return Err(format!(
+            "{} at {} does not implement the Action interface",
+            node.description,
+            node.location()
+        )
+        .into());
+    };
+    let actions = action.get_actions().await?;
+    let Some((index, name)) = TOGGLE_ACTIONS.iter().find_map(|name| {
+        actions
+            .iter()
+            .position(|a| a.name.eq_ignore_ascii_case(name))
+            .map(|index| (index, *name))
+    }) else {
+        let names: Vec<&str> = actions.iter().map(|a| a.name.as_str()).collect();
+        return Err(format!(
+            "{} has none of the actions {}, its actions are: {}",
+            node.description,
+            TOGGLE_ACTIONS.join(", "),
+            names.join(", ")
+        )
+        .into());
+    };
+
+    if crate::dry_run() {
+        println!(
+            "Would toggle {} at {} with {name:?}: {} is {before}",
+            node.description,
+            node.location(),
+            state.to_static_str()
+        );
+        return Ok(());
+    }
+    if !action.do_action(index as i32).await? {
+        return Err(format!("{} could not perform {name:?}", node.description).into());
+    }
+
+    // The toolkit updates the state after handling the action, so give it a moment.
+    let start = Instant::now();
+    loop {
+        let after = node.proxy.get_state().await?.contains(state);
+        if after != before {
+            println!(
+                "Toggled {} at {} with {name:?}: {} {before} -> {after}",
+                node.description,
+                node.location(),
+                state.to_static_str()
+            );
+            return Ok(());
+        }
+        if start.elapsed() > TOGGLE_TIMEOUT {
+            return Err(format!(
+                "Performed {name:?} on {}, but its {} state stayed {before}",
+                node.description,
+                state.to_static_str()
+            )
+            .into());
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+}
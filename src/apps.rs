@@ -0,0 +1,77 @@
+//! Listing the applications registered on the accessibility bus.
+
+use crate::{watch, Result};
+use argh::{ArgsInfo, FromArgs};
+use atspi::AccessibilityConnection;
+use zbus::{fdo::DBusProxy, names::BusName};
+
+/// List the applications on the accessibility bus: their names, bus names, toolkits,
+/// process ids and child counts
+#[derive(FromArgs, ArgsInfo)]
+#[argh(subcommand, name = "apps")]
+pub struct AppsArgs {}
+
+pub async fn apps(a11y: &AccessibilityConnection, _args: AppsArgs) -> Result<()> {
+    let conn = a11y.connection();
+    let registry = crate::get_registry_accessible(conn).await?;
+    let dbus = DBusProxy::new(conn).await?;
+    let unknown = || "--- No value ---".to_string();
+
+    let children = registry.get_children().await?;
+    let mut rows = Vec::with_capacity(children.len());
+    for child in children {
+        let bus_name = child.name.to_string();
+        let pid = match dbus
+            .get_connection_unix_process_id(BusName::from(child.name.clone()))
+            .await
+        {
+            Ok(pid) => pid.to_string(),
+            Err(_) => unknown(),
+        };
+        let (name, child_count) = match watch::accessible_from_object_ref(conn, child.clone()).await
+        {
+            Ok(proxy) => (
+                proxy.name().await.ok().filter(|name| !name.is_empty()),
+                proxy.child_count().await.ok(),
+            ),
+            Err(_) => (None, None),
+        };
+        let (toolkit, version) = match watch::application_from_object_ref(conn, child).await {
+            Ok(application) => (
+                application.toolkit_name().await.ok(),
+                application.version().await.ok(),
+            ),
+            Err(_) => (None, None),
+        };
+
+        rows.push(vec![
+            name.unwrap_or_else(unknown),
+            bus_name,
+            toolkit
+                .filter(|toolkit| !toolkit.is_empty())
+                .unwrap_or_else(unknown),
+            version
+                .filter(|version| !version.is_empty())
+                .unwrap_or_else(unknown),
+            pid,
+            child_count.map_or_else(unknown, |count| count.to_string()),
+        ]);
+    }
+
+    if rows.is_empty() {
+        println!("No applications are registered on the accessibility bus.");
+        return Ok(());
+    }
+    crate::print_table(
+        &[
+            "Name",
+            "Bus name",
+            "Toolkit",
+            "Version",
+            "PID",
+            "Child count",
+        ],
+        &rows,
+    );
+    Ok(())
+}
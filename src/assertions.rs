@@ -0,0 +1,305 @@
+//! Checks on an application's tree for CI: each fails with `ASSERTION_FAILED` and a
+//! diff-style message when it does not hold.
+//!
+//! ```text
+//! Error: assertion failed: state focused
+//!   node: text "Search" at path 0/1/3
+//! - expected: focused
+//! + actual:   editable, enabled, focusable, sensitive, showing, visible
+//! ```
+
+use crate::{
+    exit,
+    target::{self, Selected},
+    Result,
+};
+use argh::{ArgsInfo, FromArgs};
+use atspi::{AccessibilityConnection, State};
+use zbus::Connection;
+
+/// How many similar nodes a failed `exists` lists.
+const MAX_CANDIDATES: usize = 10;
+
+/// Check a condition on an application's tree, exiting non-zero if it does not hold
+#[derive(FromArgs, ArgsInfo)]
+#[argh(subcommand, name = "assert")]
+pub struct AssertArgs {
+    #[argh(subcommand)]
+    assertion: Assertion,
+}
+
+#[derive(FromArgs, ArgsInfo)]
+#[argh(subcommand)]
+enum Assertion {
+    Exists(ExistsArgs),
+    Absent(AbsentArgs),
+    State(StateArgs),
+    Name(NameArgs),
+}
+
+/// Check that a node with the given role and/or name exists
+#[derive(FromArgs, ArgsInfo)]
+#[argh(subcommand, name = "exists")]
+struct ExistsArgs {
+    /// the application, by bus name or application name
+    #[argh(positional)]
+    app: String,
+
+    /// the role of the node, e.g. `alert`
+    #[argh(option)]
+    role: Option<String>,
+
+    /// the name of the node, e.g. `Saved`
+    #[argh(option)]
+    name: Option<String>,
+
+    /// the role and/or name of the node, e.g. `alert "Saved"`
+    #[argh(option)]
+    query: Option<String>,
+}
+
+/// Check that no node with the given role and/or name exists
+#[derive(FromArgs, ArgsInfo)]
+#[argh(subcommand, name = "absent")]
+struct AbsentArgs {
+    /// the application, by bus name or application name
+    #[argh(positional)]
+    app: String,
+
+    /// the role of the node, e.g. `alert`
+    #[argh(option)]
+    role: Option<String>,
+
+    /// the name of the node, e.g. `Error`
+    #[argh(option)]
+    name: Option<String>,
+
+    /// the role and/or name of the node, e.g. `alert "Error"`
+    #[argh(option)]
+    query: Option<String>,
+}
+
+/// Check that a node has (or with `--not`, does not have) a state
+#[derive(FromArgs, ArgsInfo)]
+#[argh(subcommand, name = "state")]
+struct StateArgs {
+    /// the application, by bus name or application name
+    #[argh(positional)]
+    app: String,
+
+    /// the state, e.g. `focused` or `checked`
+    #[argh(positional)]
+    state: String,
+
+    /// check that the node does not have the state
+    #[argh(switch)]
+    not: bool,
+
+    /// the node, by the child indices leading to it from the application's root, e.g. `0/2/1`
+    #[argh(option)]
+    path: Option<String>,
+
+    /// the first node with this role and/or name, e.g. `text "Search"`
+    #[argh(option)]
+    query: Option<String>,
+
+    /// the first node with this role
+    #[argh(option)]
+    role: Option<String>,
+
+    /// the first node with this name
+    #[argh(option)]
+    name: Option<String>,
+}
+
+/// Check the accessible name of a node
+#[derive(FromArgs, ArgsInfo)]
+#[argh(subcommand, name = "name")]
+struct NameArgs {
+    /// the application, by bus name or application name
+    #[argh(positional)]
+    app: String,
+
+    /// the expected name
+    #[argh(positional)]
+    expected: String,
+
+    /// the node, by the child indices leading to it from the application's root, e.g. `0/2/1`
+    #[argh(option)]
+    path: Option<String>,
+
+    /// the first node with this role and/or name, e.g. `push button`
+    #[argh(option)]
+    query: Option<String>,
+
+    /// the first node with this role
+    #[argh(option)]
+    role: Option<String>,
+}
+
+/// A query from `--role` and `--name`, or `--query`.
+fn query(role: Option<&str>, name: Option<&str>, query: Option<&str>) -> Result<Option<String>> {
+    match (role, name, query) {
+        (None, None, query) => Ok(query.map(str::to_string)),
+        (role, name, None) => {
+            let mut parts = Vec::new();
+            parts.extend(role.map(str::to_string));
+            parts.extend(name.map(|name| format!("\"{name}\"")));
+            Ok(Some(parts.join(" ")))
+        }
+        _ => Err("Select a node by --role and --name or by --query, not both".into()),
+    }
+}
+
+/// The failed assertion, as a diff of what was expected and what was found.
+fn fail(assertion: &str, context: &[String], expected: &str, actual: &str) -> Result<()> {
+    let mut message = format!("assertion failed: {assertion}\n");
+    for line in context {
+        message.push_str(&format!("  {line}\n"));
+    }
+    message.push_str(&format!("- expected: {expected}\n"));
+    message.push_str(&format!("+ actual:   {actual}"));
+    Err(exit::Failure::boxed(exit::ASSERTION_FAILED, message))
+}
+
+fn pass(assertion: &str, node: &Selected) {
+    println!(
+        "ok: {assertion} ({} at {})",
+        node.description,
+        node.location()
+    );
+}
+
+pub async fn assert(a11y: &AccessibilityConnection, args: AssertArgs) -> Result<()> {
+    let conn = a11y.connection();
+    match args.assertion {
+        Assertion::Exists(args) => {
+            let query = query(
+                args.role.as_deref(),
+                args.name.as_deref(),
+                args.query.as_deref(),
+            )?
+            .ok_or("Give the node to look for with --role, --name or --query")?;
+            exists(conn, &args.app, &query, args.role.as_deref()).await
+        }
+        Assertion::Absent(args) => {
+            let query = query(
+                args.role.as_deref(),
+                args.name.as_deref(),
+                args.query.as_deref(),
+            )?
+            .ok_or("Give the node to look for with --role, --name or --query")?;
+            absent(conn, &args.app, &query).await
+        }
+        Assertion::State(args) => {
+            let query = query(
+                args.role.as_deref(),
+                args.name.as_deref(),
+                args.query.as_deref(),
+            )?;
+            let node =
+                target::select(conn, &args.app, args.path.as_deref(), query.as_deref()).await?;
+            state(&node, &args.state, !args.not).await
+        }
+        Assertion::Name(args) => {
+            let query = query(args.role.as_deref(), None, args.query.as_deref())?;
+            let node =
+                target::select(conn, &args.app, args.path.as_deref(), query.as_deref()).await?;
+            name(&node, &args.expected).await
+        }
+    }
+}
+
+async fn exists(conn: &Connection, app: &str, query: &str, role: Option<&str>) -> Result<()> {
+    let assertion = format!("exists {query}");
+    let root = target::select(conn, app, None, None).await?;
+    if let Some(node) = target::search(conn, &root, query, 1).await?.pop() {
+        pass(&assertion, &node);
+        return Ok(());
+    }
+
+    // Nodes of the same role help to spot a misspelled or changed name.
+    let candidates = match role {
+        Some(role) => target::search(conn, &root, role, MAX_CANDIDATES).await?,
+        None => Vec::new(),
+    };
+    let context: Vec<String> = candidates
+        .iter()
+        .map(|node| format!("similar: {} at {}", node.description, node.location()))
+        .collect();
+    fail(
+        &assertion,
+        &context,
+        &format!("a node of {app} matching {query}"),
+        "no such node",
+    )
+}
+
+async fn absent(conn: &Connection, app: &str, query: &str) -> Result<()> {
+    let assertion = format!("absent {query}");
+    let root = target::select(conn, app, None, None).await?;
+    let found = target::search(conn, &root, query, MAX_CANDIDATES).await?;
+    if found.is_empty() {
+        println!("ok: {assertion}");
+        return Ok(());
+    }
+
+    let actual: Vec<String> = found
+        .iter()
+        .map(|node| format!("{} at {}", node.description, node.location()))
+        .collect();
+    fail(
+        &assertion,
+        &[],
+        &format!("no node of {app} matching {query}"),
+        &actual.join(", "),
+    )
+}
+
+async fn state(node: &Selected, name: &str, expected: bool) -> Result<()> {
+    let state = State::from(name);
+    if state == State::Invalid {
+        return Err(format!("Unknown state: {name}").into());
+    }
+
+    let assertion = if expected {
+        format!("state {name}")
+    } else {
+        format!("state not {name}")
+    };
+    let states = node.proxy.get_state().await?;
+    if states.contains(state) == expected {
+        pass(&assertion, node);
+        return Ok(());
+    }
+
+    let mut actual: Vec<&str> = states.iter().map(|state| state.to_static_str()).collect();
+    actual.sort_unstable();
+    let expected = if expected {
+        name.to_string()
+    } else {
+        format!("not {name}")
+    };
+    fail(
+        &assertion,
+        &[format!("node: {} at {}", node.description, node.location())],
+        &expected,
+        &actual.join(", "),
+    )
+}
+
+async fn name(node: &Selected, expected: &str) -> Result<()> {
+    let assertion = format!("name {expected:?}");
+    let actual = node.proxy.name().await?;
+    if actual == expected {
+        pass(&assertion, node);
+        return Ok(());
+    }
+
+    fail(
+        &assertion,
+        &[format!("node: {} at {}", node.description, node.location())],
+        &format!("{expected:?}"),
+        &format!("{actual:?}"),
+    )
+}
@@ -0,0 +1,2246 @@
+//! Auditing an application's tree for common accessibility defects.
+//!
+//! The tree is collected once, then each rule looks for defects in the collected nodes.
+//! Besides the built-in rules in `RULES`, rule plugins can be loaded from Rhai scripts
+//! and shared libraries, see `audit_plugins`.
+
+use crate::{
+    audit_plugins, config, exit, interrupt,
+    snapshot::{self, Detail, Facet},
+    tab_order,
+    target::{self, Selected},
+    tree, watch, ArgResult, Result,
+};
+use argh::{ArgsInfo, FromArgs};
+use atspi::{
+    proxy::{accessible::AccessibleProxy, proxy_ext::ProxyExt},
+    AccessibilityConnection, CoordType, ObjectRef, RelationType, Role, State, StateSet,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tracing::warn;
+use zbus::{names::UniqueName, zvariant::ObjectPath, Connection};
+
+/// Roles of the widgets users click or activate, which need a name to be announced.
+const INTERACTIVE_ROLES: &[Role] = &[
+    Role::Button,
+    Role::ToggleButton,
+    Role::Link,
+    Role::MenuItem,
+    Role::CheckMenuItem,
+    Role::RadioMenuItem,
+    Role::PageTab,
+];
+
+/// Roles of form fields, which need a label to say what to enter. `text` only counts when editable.
+const FORM_FIELD_ROLES: &[Role] = &[
+    Role::Entry,
+    Role::PasswordText,
+    Role::Text,
+    Role::ComboBox,
+    Role::SpinButton,
+    Role::CheckBox,
+];
+
+/// Roles that can be checked without having the checkable state.
+const CHECKABLE_ROLES: &[Role] = &[
+    Role::CheckBox,
+    Role::CheckMenuItem,
+    Role::RadioButton,
+    Role::RadioMenuItem,
+    Role::ToggleButton,
+];
+
+/// State pairs where the first only makes sense with the second, e.g. focused without focusable.
+const IMPLIED_STATES: &[(State, State)] = &[
+    (State::Focused, State::Focusable),
+    (State::Selected, State::Selectable),
+    (State::Expanded, State::Expandable),
+    (State::Collapsed, State::Expandable),
+    (State::Showing, State::Visible),
+];
+
+/// Roles that are expected to be reached by pressing Tab. `text` only counts when editable.
+const TAB_STOP_ROLES: &[Role] = &[
+    Role::Button,
+    Role::ToggleButton,
+    Role::Link,
+    Role::CheckBox,
+    Role::Entry,
+    Role::PasswordText,
+    Role::Text,
+    Role::ComboBox,
+    Role::SpinButton,
+    Role::Slider,
+];
+
+/// The most times Tab is pressed for `--tab-order`, however large the tree.
+const MAX_TAB_PRESSES: usize = 500;
+
+/// How long to wait before pressing Tab, to switch to the application's window.
+const TAB_ORDER_DELAY: Duration = Duration::from_secs(3);
+
+/// Prefixes of message ids, e.g. `IDS_SAVE` in Windows resources or `gtk-ok` stock ids.
+const MESSAGE_ID_PREFIXES: &[&str] = &["IDS_", "IDC_", "IDM_", "STR_", "MSG_", "gtk-", "gtk_"];
+
+/// How much a finding of each severity takes off the score, relative to one node.
+const ERROR_PENALTY: usize = 10;
+const WARNING_PENALTY: usize = 3;
+const INFO_PENALTY: usize = 1;
+
+/// How many of the worst subtrees the summary lists.
+const WORST_SUBTREES: usize = 5;
+
+/// Relations that should be mirrored by the node they point at, with their names.
+const RECIPROCAL_RELATIONS: &[(RelationType, &str, RelationType, &str)] = &[
+    (
+        RelationType::LabelledBy,
+        "labelled-by",
+        RelationType::LabelFor,
+        "label-for",
+    ),
+    (
+        RelationType::LabelFor,
+        "label-for",
+        RelationType::LabelledBy,
+        "labelled-by",
+    ),
+    (
+        RelationType::ControllerFor,
+        "controller-for",
+        RelationType::ControlledBy,
+        "controlled-by",
+    ),
+    (
+        RelationType::ControlledBy,
+        "controlled-by",
+        RelationType::ControllerFor,
+        "controller-for",
+    ),
+    (
+        RelationType::DescribedBy,
+        "described-by",
+        RelationType::DescriptionFor,
+        "description-for",
+    ),
+    (
+        RelationType::DescriptionFor,
+        "description-for",
+        RelationType::DescribedBy,
+        "described-by",
+    ),
+    (
+        RelationType::FlowsTo,
+        "flows-to",
+        RelationType::FlowsFrom,
+        "flows-from",
+    ),
+    (
+        RelationType::FlowsFrom,
+        "flows-from",
+        RelationType::FlowsTo,
+        "flows-to",
+    ),
+    (
+        RelationType::MemberOf,
+        "member-of",
+        RelationType::MemberOf,
+        "member-of",
+    ),
+];
+
+/// Roles of documents, whose headings should form an outline.
+const DOCUMENT_ROLES: &[Role] = &[
+    Role::DocumentFrame,
+    Role::DocumentWeb,
+    Role::DocumentText,
+    Role::DocumentEmail,
+    Role::DocumentPresentation,
+];
+
+/// The relations that can be read back by name, e.g. from snapshots.
+const RELATION_TYPES: &[RelationType] = &[
+    RelationType::LabelFor,
+    RelationType::LabelledBy,
+    RelationType::ControllerFor,
+    RelationType::ControlledBy,
+    RelationType::MemberOf,
+    RelationType::TooltipFor,
+    RelationType::NodeChildOf,
+    RelationType::NodeParentOf,
+    RelationType::Extended,
+    RelationType::FlowsTo,
+    RelationType::FlowsFrom,
+    RelationType::SubwindowOf,
+    RelationType::Embeds,
+    RelationType::EmbeddedBy,
+    RelationType::PopupFor,
+    RelationType::ParentWindowOf,
+    RelationType::DescriptionFor,
+    RelationType::DescribedBy,
+    RelationType::Details,
+    RelationType::DetailsFor,
+    RelationType::ErrorMessage,
+    RelationType::ErrorFor,
+];
+
+/// The most rows and columns whose headers are checked, so huge tables do not take forever.
+const MAX_TABLE_HEADERS: i32 = 100;
+
+/// The rules configuration used when there is no `--config`, if it exists.
+const DEFAULT_CONFIG: &str = "a11y-audit.toml";
+
+/// Attributes that tell apart nodes with the same name, besides the description and id.
+const DISAMBIGUATING_ATTRIBUTES: &[&str] = &["tooltip", "placeholder-text", "id"];
+
+/// Check an application's tree for common accessibility defects
+#[derive(FromArgs, ArgsInfo)]
+#[argh(subcommand, name = "audit")]
+pub struct AuditArgs {
+    /// the application, by bus name or application name, or a snapshot file saved with `snapshot`
+    #[argh(positional)]
+    app: String,
+
+    /// only audit below the node with these child indices from the application's root, e.g. `0/2`
+    #[argh(option)]
+    path: Option<String>,
+
+    /// only audit below the first node with this role and/or name, e.g. `dialog "Preferences"`
+    #[argh(option)]
+    query: Option<String>,
+
+    /// also press Tab through the application to find focusable nodes that cannot be
+    /// reached by keyboard and keyboard traps; its window must be active
+    #[argh(switch)]
+    tab_order: bool,
+
+    /// how many times to press Tab at most for `--tab-order`
+    /// (default: twice the number of focusable nodes)
+    #[argh(option)]
+    presses: Option<usize>,
+
+    /// the output format: text, or json for dashboards and scripts (default: text)
+    #[argh(option, from_str_fn(parse_format))]
+    format: Option<Format>,
+
+    /// exit with 6 if there are findings of this severity or worse: error, warning or any;
+    /// with `--compare`, only new findings count
+    #[argh(option, from_str_fn(parse_fail_on))]
+    fail_on: Option<Severity>,
+
+    /// compare the findings to those of an earlier report written with `--format json`,
+    /// and say which are new, fixed and persisting
+    #[argh(option)]
+    compare: Option<PathBuf>,
+
+    /// a rule plugin, a Rhai script ending in `.rhai` or a shared library, can be given
+    /// more than once
+    #[argh(option)]
+    plugin: Vec<PathBuf>,
+
+    /// the rules configuration, a TOML file that enables and disables rules, sets their
+    /// severity and the limits (default: `a11y-audit.toml`, if it exists)
+    #[argh(option)]
+    config: Option<PathBuf>,
+
+    /// the most nodes the tree should have (default: 5000)
+    #[argh(option)]
+    max_nodes: Option<usize>,
+
+    /// the most levels the tree should have below the audited node (default: 50)
+    #[argh(option)]
+    max_depth: Option<usize>,
+
+    /// the most children a node should have (default: 500)
+    #[argh(option)]
+    max_children: Option<usize>,
+
+    /// also look for names that look like untranslated message ids, e.g. `IDS_SAVE` or `gtk-ok`
+    #[argh(switch)]
+    untranslated: bool,
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Format {
+    Text,
+    Json,
+}
+
+pub(crate) fn parse_format(value: &str) -> ArgResult<Format> {
+    match value {
+        "text" => Ok(Format::Text),
+        "json" => Ok(Format::Json),
+        _ => Err(format!("Unknown format: {value}, expected text or json")),
+    }
+}
+
+/// The least severity to fail on: `any` fails on infos as well.
+fn parse_fail_on(value: &str) -> ArgResult<Severity> {
+    match value {
+        "error" => Ok(Severity::Error),
+        "warning" => Ok(Severity::Warning),
+        "any" => Ok(Severity::Info),
+        _ => Err(format!(
+            "Unknown severity: {value}, expected error, warning or any"
+        )),
+    }
+}
+
+/// Limits and opt-in checks for the rules, and which rules to run at what severity.
+#[derive(Clone, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub(crate) struct AuditConfig {
+    /// Screen readers walk large parts of the tree, so bloated trees make them slow.
+    pub(crate) max_nodes: usize,
+    pub(crate) max_depth: usize,
+    pub(crate) max_children: usize,
+    /// Look for names that look like untranslated message ids.
+    pub(crate) untranslated: bool,
+    /// Rules by id, turned off or with another severity than their own.
+    pub(crate) rules: HashMap<String, RuleSetting>,
+    /// Rule plugins to load, relative to the configuration file.
+    pub(crate) plugins: Vec<PathBuf>,
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        AuditConfig {
+            max_nodes: 5000,
+            max_depth: 50,
+            max_children: 500,
+            untranslated: false,
+            rules: HashMap::new(),
+            plugins: Vec::new(),
+        }
+    }
+}
+
+impl AuditConfig {
+    /// Read a rules configuration, e.g.
+    ///
+    /// ```toml
+    /// max-nodes = 10000
+    ///
+    /// plugins = ["rules/toolbar-tooltips.rhai"]
+    ///
+    /// [rules]
+    /// duplicate-sibling-names = "off"
+    /// unlabeled-form-field = "warning"
+    /// ```
+    pub(crate) fn load(path: &Path) -> Result<AuditConfig> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Could not read {}: {e}", path.display()))?;
+        let mut config: AuditConfig = toml::from_str(&contents)
+            .map_err(|e| format!("Invalid rules configuration {}: {e}", path.display()))?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+        config.plugins = config
+            .plugins
+            .iter()
+            .map(|plugin| dir.join(plugin))
+            .collect();
+        Ok(config)
+    }
+
+    /// Check that the configured rules are among `rules`, to catch typos.
+    pub(crate) fn check_rules(&self, rules: &[&dyn AuditRule]) -> Result<()> {
+        match self
+            .rules
+            .keys()
+            .find(|id| !rules.iter().any(|rule| rule.id() == id.as_str()))
+        {
+            Some(id) => Err(format!("Unknown rule {id:?} in the rules configuration").into()),
+            None => Ok(()),
+        }
+    }
+
+    /// The severity to report the rule's findings at, or `None` if it is turned off.
+    pub(crate) fn severity(&self, rule: &dyn AuditRule) -> Option<Severity> {
+        match self.rules.get(rule.id()) {
+            None => Some(rule.severity()),
+            Some(RuleSetting::Off) => None,
+            Some(RuleSetting::Error) => Some(Severity::Error),
+            Some(RuleSetting::Warning) => Some(Severity::Warning),
+            Some(RuleSetting::Info) => Some(Severity::Info),
+        }
+    }
+}
+
+/// How a rule is configured: turned off, or at a severity.
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum RuleSetting {
+    Off,
+    Error,
+    Warning,
+    Info,
+}
+
+/// What we need to know about a node to audit it.
+pub(crate) struct AuditNode {
+    pub(crate) object: ObjectRef,
+    /// The index path of the node, e.g. `0/2/1`, empty for the root.
+    pub(crate) path: String,
+    pub(crate) parent: Option<usize>,
+    pub(crate) children: Vec<usize>,
+    pub(crate) role: Role,
+    pub(crate) name: String,
+    /// The Description property, not to be confused with `description()`.
+    pub(crate) accessible_description: String,
+    pub(crate) accessible_id: String,
+    pub(crate) attributes: HashMap<String, String>,
+    pub(crate) states: StateSet,
+    pub(crate) relations: Vec<(RelationType, Vec<ObjectRef>)>,
+    /// The locale of the node's text, empty if it does not say.
+    pub(crate) locale: String,
+    /// What the Table interface says, for nodes that implement it.
+    pub(crate) table: Option<TableInfo>,
+    /// The node's Parent property, `None` for no parent.
+    pub(crate) reported_parent: Option<ObjectRef>,
+    /// What the node says its index among its parent's children is, -1 if it does not know.
+    pub(crate) index_in_parent: i32,
+    /// Where the node is on the screen, if collected and it implements Component.
+    pub(crate) extents: Option<Extents>,
+    /// The node's text, if collected and it implements Text.
+    pub(crate) text: Option<String>,
+}
+
+/// A rectangle on the screen.
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Extents {
+    pub(crate) x: i32,
+    pub(crate) y: i32,
+    pub(crate) width: i32,
+    pub(crate) height: i32,
+}
+
+/// The headers, caption and summary of a table.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct TableInfo {
+    pub(crate) rows: i32,
+    pub(crate) columns: i32,
+    /// How many of the checked columns have a header or a description.
+    pub(crate) column_headers: i32,
+    /// How many of the checked rows have a header or a description.
+    pub(crate) row_headers: i32,
+    /// How many columns and rows were checked, at most `MAX_TABLE_HEADERS` each.
+    pub(crate) checked_columns: i32,
+    pub(crate) checked_rows: i32,
+    pub(crate) has_caption: bool,
+    pub(crate) has_summary: bool,
+}
+
+impl TableInfo {
+    /// Tables of one row or column are lists, which do not need headers.
+    pub(crate) fn is_data_table(&self) -> bool {
+        self.rows > 1 && self.columns > 1
+    }
+}
+
+impl AuditNode {
+    /// The level of a heading, from its `level` attribute.
+    pub(crate) fn heading_level(&self) -> Option<u32> {
+        if self.role != Role::Heading {
+            return None;
+        }
+        self.attributes.get("level")?.trim().parse().ok()
+    }
+
+    /// Role and name, as given by `watch::describe_node`, e.g. `push button "Save"`.
+    pub(crate) fn description(&self) -> String {
+        if self.name.is_empty() {
+            self.role.name().to_string()
+        } else {
+            format!("{} {:?}", self.role.name(), self.name)
+        }
+    }
+
+    pub(crate) fn location(&self) -> String {
+        location(&self.path)
+    }
+
+    /// The targets of the node's relations of a type.
+    pub(crate) fn relation_targets(
+        &self,
+        relation: RelationType,
+    ) -> impl Iterator<Item = &ObjectRef> {
+        self.relations
+            .iter()
+            .filter(move |(r, _)| *r == relation)
+            .flat_map(|(_, targets)| targets)
+    }
+
+    pub(crate) fn has_relation(&self, relation: RelationType) -> bool {
+        self.relations
+            .iter()
+            .any(|(r, targets)| *r == relation && !targets.is_empty())
+    }
+}
+
+/// The nodes of the audited tree, the first being the audited node itself.
+pub(crate) struct AuditTree {
+    pub(crate) nodes: Vec<AuditNode>,
+    /// The locale of the application, empty if it does not say.
+    pub(crate) app_locale: String,
+    /// The keyboard traversal, if it was followed with `--tab-order`.
+    pub(crate) tab_order: Option<TabOrder>,
+}
+
+impl AuditTree {
+    /// The index of the node for `object`, if it is in the tree.
+    pub(crate) fn index_of(&self, object: &ObjectRef) -> Option<usize> {
+        self.nodes.iter().position(|node| node.object == *object)
+    }
+
+    /// The number of levels each node is below the audited node.
+    pub(crate) fn depths(&self) -> Vec<usize> {
+        let mut depths: Vec<usize> = Vec::with_capacity(self.nodes.len());
+        // Parents come before their children.
+        for node in &self.nodes {
+            depths.push(node.parent.map_or(0, |parent| depths[parent] + 1));
+        }
+        depths
+    }
+}
+
+/// The nodes that got the focus by pressing Tab, as indices into the tree's nodes.
+/// Focus moving to nodes outside the audited tree is left out.
+pub(crate) struct TabOrder {
+    pub(crate) visited: Vec<usize>,
+    /// The nodes the focus cycled through without getting back to where it started.
+    pub(crate) trap: Option<Vec<usize>>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Severity {
+    Error,
+    Warning,
+    Info,
+}
+
+impl Severity {
+    pub(crate) fn parse(name: &str) -> Option<Severity> {
+        match name {
+            "error" => Some(Severity::Error),
+            "warning" => Some(Severity::Warning),
+            "info" => Some(Severity::Info),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Info => "info",
+        }
+    }
+
+    fn penalty(self) -> usize {
+        match self {
+            Severity::Error => ERROR_PENALTY,
+            Severity::Warning => WARNING_PENALTY,
+            Severity::Info => INFO_PENALTY,
+        }
+    }
+}
+
+/// A defect found by a rule.
+pub(crate) struct Finding {
+    pub(crate) rule: String,
+    pub(crate) severity: Severity,
+    /// The node the defect is about.
+    pub(crate) node: usize,
+    pub(crate) message: String,
+}
+
+/// A check on the audited tree, looking at one node at a time or at the tree as a whole.
+pub(crate) trait AuditRule {
+    fn id(&self) -> &str;
+
+    /// The severity of the findings, unless the rules configuration says otherwise.
+    fn severity(&self) -> Severity;
+
+    /// The problem with a node, if it has one.
+    fn visit(
+        &self,
+        _tree: &AuditTree,
+        _node: usize,
+        _config: &AuditConfig,
+    ) -> Result<Option<String>> {
+        Ok(None)
+    }
+
+    /// The problems found in the tree, by node. Visits every node, unless the rule
+    /// needs to look at more than one node at a time.
+    fn check(&self, tree: &AuditTree, config: &AuditConfig) -> Result<Vec<(usize, String)>> {
+        let mut problems = Vec::new();
+        for node in 0..tree.nodes.len() {
+            if let Some(message) = self.visit(tree, node, config)? {
+                problems.push((node, message));
+            }
+        }
+        Ok(problems)
+    }
+}
+
+/// A built-in rule.
+pub(crate) struct Rule {
+    pub(crate) id: &'static str,
+    pub(crate) severity: Severity,
+    pub(crate) check: fn(&AuditTree, &AuditConfig) -> Vec<(usize, String)>,
+}
+
+impl AuditRule for Rule {
+    fn id(&self) -> &str {
+        self.id
+    }
+
+    fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    fn check(&self, tree: &AuditTree, config: &AuditConfig) -> Result<Vec<(usize, String)>> {
+        Ok((self.check)(tree, config))
+    }
+}
+
+pub(crate) const RULES: &[Rule] = &[
+    Rule {
+        id: "unnamed-interactive",
+        severity: Severity::Error,
+        check: unnamed_interactive,
+    },
+    Rule {
+        id: "unlabeled-form-field",
+        severity: Severity::Error,
+        check: unlabeled_form_field,
+    },
+    Rule {
+        id: "duplicate-sibling-names",
+        severity: Severity::Warning,
+        check: duplicate_sibling_names,
+    },
+    Rule {
+        id: "unreachable-focusable",
+        severity: Severity::Warning,
+        check: unreachable_focusable,
+    },
+    Rule {
+        id: "keyboard-trap",
+        severity: Severity::Error,
+        check: keyboard_trap,
+    },
+    Rule {
+        id: "inconsistent-states",
+        severity: Severity::Warning,
+        check: inconsistent_states,
+    },
+    Rule {
+        id: "table-without-headers",
+        severity: Severity::Error,
+        check: table_without_headers,
+    },
+    Rule {
+        id: "table-missing-headers",
+        severity: Severity::Warning,
+        check: table_missing_headers,
+    },
+    Rule {
+        id: "table-without-caption",
+        severity: Severity::Info,
+        check: table_without_caption,
+    },
+    Rule {
+        id: "heading-level-skipped",
+        severity: Severity::Warning,
+        check: heading_level_skipped,
+    },
+    Rule {
+        id: "missing-top-heading",
+        severity: Severity::Warning,
+        check: missing_top_heading,
+    },
+    Rule {
+        id: "one-sided-relation",
+        severity: Severity::Warning,
+        check: one_sided_relation,
+    },
+    Rule {
+        id: "broken-parent-link",
+        severity: Severity::Error,
+        check: broken_parent_link,
+    },
+    Rule {
+        id: "locale-mismatch",
+        severity: Severity::Info,
+        check: locale_mismatch,
+    },
+    Rule {
+        id: "untranslated-name",
+        severity: Severity::Warning,
+        check: untranslated_name,
+    },
+    Rule {
+        id: "tree-too-large",
+        severity: Severity::Warning,
+        check: tree_too_large,
+    },
+    Rule {
+        id: "tree-too-deep",
+        severity: Severity::Warning,
+        check: tree_too_deep,
+    },
+    Rule {
+        id: "too-many-children",
+        severity: Severity::Warning,
+        check: too_many_children,
+    },
+];
+
+impl AuditArgs {
+    /// Whether the application is a snapshot file, to audit without the accessibility bus.
+    pub fn is_offline(&self) -> bool {
+        snapshot::is_snapshot(&self.app)
+    }
+}
+
+/// What an audit needs besides the tree: the configuration, the rules and an earlier report.
+struct Setup {
+    config: AuditConfig,
+    plugins: Vec<Box<dyn AuditRule>>,
+    previous: Option<Report>,
+}
+
+impl Setup {
+    fn new(args: &AuditArgs) -> Result<Setup> {
+        // Options given on the command line win over the configuration.
+        let mut config = match &args.config {
+            Some(path) => AuditConfig::load(path)?,
+            None if Path::new(DEFAULT_CONFIG).exists() => {
+                AuditConfig::load(Path::new(DEFAULT_CONFIG))?
+            }
+            None => config::get().audit.clone().unwrap_or_default(),
+        };
+        config.max_nodes = args.max_nodes.unwrap_or(config.max_nodes);
+        config.max_depth = args.max_depth.unwrap_or(config.max_depth);
+        config.max_children = args.max_children.unwrap_or(config.max_children);
+        config.untranslated |= args.untranslated;
+        config.plugins.extend(args.plugin.iter().cloned());
+
+        let plugins = config
+            .plugins
+            .iter()
+            .map(|path| audit_plugins::load(path))
+            .collect::<Result<Vec<_>>>()?;
+        let setup = Setup {
+            previous: args.compare.as_deref().map(load_report).transpose()?,
+            config,
+            plugins,
+        };
+        setup.config.check_rules(&setup.rules())?;
+        Ok(setup)
+    }
+
+    fn rules(&self) -> Vec<&dyn AuditRule> {
+        let mut rules: Vec<&dyn AuditRule> =
+            RULES.iter().map(|rule| rule as &dyn AuditRule).collect();
+        rules.extend(self.plugins.iter().map(|rule| rule.as_ref()));
+        rules
+    }
+}
+
+pub async fn audit(a11y: &AccessibilityConnection, args: AuditArgs) -> Result<()> {
+    let setup = Setup::new(&args)?;
+
+    let conn = a11y.connection();
+    let node = target::select(conn, &args.app, args.path.as_deref(), args.query.as_deref()).await?;
+    let root = format!("{} at {}", node.description, node.location());
+    eprintln!("Auditing {root}");
+
+    let mut tree = collect(conn, &node, &Detail::default()).await?;
+    if args.tab_order {
+        tree.tab_order = Some(follow_tab_order(a11y, &node, &tree, args.presses).await?);
+    }
+    finish(&args, &setup, root, &tree)
+}
+
+/// Audit the tree saved in a snapshot file, given instead of the application.
+pub fn audit_offline(args: AuditArgs) -> Result<()> {
+    if args.tab_order {
+        return Err("--tab-order needs the running application, not a snapshot".into());
+    }
+    let setup = Setup::new(&args)?;
+
+    let snapshot = snapshot::load(Path::new(&args.app))?;
+    let missing = snapshot.detail.missing(&Detail::default());
+    if !missing.is_empty() {
+        return Err(format!(
+            "{} has no {} of its nodes, take it with the default --detail to audit it",
+            args.app,
+            missing.join(", ")
+        )
+        .into());
+    }
+    let (node, path) = snapshot.select(args.path.as_deref(), args.query.as_deref())?;
+    let root = format!(
+        "{} at {} of {}",
+        node.description(),
+        location(&path),
+        args.app
+    );
+    eprintln!("Auditing {root}");
+
+    let tree = snapshot::to_tree(node, &path, &snapshot.app_locale)?;
+    finish(&args, &setup, root, &tree)
+}
+
+/// Run the rules on the tree, report the findings and exit according to `--fail-on`.
+fn finish(args: &AuditArgs, setup: &Setup, root: String, tree: &AuditTree) -> Result<()> {
+    let findings = run_rules(tree, &setup.config, &setup.rules())?;
+    let mut report = report(root, tree, &findings);
+    if let (Some(path), Some(previous)) = (&args.compare, &setup.previous) {
+        report.comparison = Some(compare(&report, previous, &path.display().to_string()));
+    }
+    match args.format.or(config::get().format).unwrap_or(Format::Text) {
+        Format::Text => print_report(&report),
+        Format::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+    }
+
+    if let Some(fail_on) = args.fail_on {
+        // Comparing, only regressions count.
+        let findings = match &report.comparison {
+            Some(comparison) => &comparison.new,
+            None => &report.findings,
+        };
+        let failing = findings
+            .iter()
+            .filter(|finding| finding.severity <= fail_on)
+            .count();
+        if failing > 0 {
+            let new = if report.comparison.is_some() {
+                "new "
+            } else {
+                ""
+            };
+            return Err(exit::Failure::boxed(
+                exit::FINDINGS,
+                format!(
+                    "audit failed: {failing} {new}findings of severity {} or worse",
+                    fail_on.name()
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn run_rules(
+    tree: &AuditTree,
+    config: &AuditConfig,
+    rules: &[&dyn AuditRule],
+) -> Result<Vec<Finding>> {
+    let mut findings = Vec::new();
+    for rule in rules {
+        let Some(severity) = config.severity(*rule) else {
+            continue;
+        };
+        findings.extend(
+            rule.check(tree, config)?
+                .into_iter()
+                .map(|(node, message)| Finding {
+                    rule: rule.id().to_string(),
+                    severity,
+                    node,
+                    message,
+                }),
+        );
+    }
+    findings.sort_by(|a, b| a.severity.cmp(&b.severity).then(a.node.cmp(&b.node)));
+    Ok(findings)
+}
+
+/// The outcome of an audit, as printed or written as JSON.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Report {
+    pub(crate) root: String,
+    pub(crate) nodes: usize,
+    /// 100 for no findings, lower the more and the more severe the findings
+    /// are relative to the size of the tree.
+    pub(crate) score: u32,
+    pub(crate) errors: usize,
+    pub(crate) warnings: usize,
+    pub(crate) infos: usize,
+    /// The rules that found anything, most findings first.
+    pub(crate) rules: Vec<RuleCount>,
+    pub(crate) worst_subtrees: Vec<Subtree>,
+    pub(crate) findings: Vec<ReportedFinding>,
+    /// How the findings compare to an earlier report, with `--compare`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) comparison: Option<Comparison>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct RuleCount {
+    pub(crate) rule: String,
+    pub(crate) findings: usize,
+}
+
+/// A subtree with many findings.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Subtree {
+    pub(crate) path: String,
+    pub(crate) node: String,
+    pub(crate) findings: usize,
+    pub(crate) penalty: usize,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct ReportedFinding {
+    pub(crate) rule: String,
+    pub(crate) severity: Severity,
+    pub(crate) path: String,
+    pub(crate) node: String,
+    pub(crate) message: String,
+}
+
+impl ReportedFinding {
+    /// Whether two findings are the same defect. Messages are not compared, as they
+    /// may contain counts that change without the defect being fixed.
+    fn same_as(&self, other: &ReportedFinding) -> bool {
+        self.rule == other.rule && self.path == other.path && self.node == other.node
+    }
+}
+
+/// The findings of a report compared to those of an earlier one.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Comparison {
+    /// The earlier report.
+    pub(crate) previous: String,
+    pub(crate) previous_score: u32,
+    /// Findings that are not in the earlier report.
+    pub(crate) new: Vec<ReportedFinding>,
+    /// Findings of the earlier report that are gone.
+    pub(crate) fixed: Vec<ReportedFinding>,
+    /// The number of findings in both reports.
+    pub(crate) persisting: usize,
+}
+
+/// Classify the findings of `report` as new, fixed or persisting compared to `previous`.
+pub(crate) fn compare(report: &Report, previous: &Report, previous_name: &str) -> Comparison {
+    let new: Vec<ReportedFinding> = report
+        .findings
+        .iter()
+        .filter(|finding| !previous.findings.iter().any(|old| old.same_as(finding)))
+        .cloned()
+        .collect();
+    let fixed: Vec<ReportedFinding> = previous
+        .findings
+        .iter()
+        .filter(|old| !report.findings.iter().any(|finding| finding.same_as(old)))
+        .cloned()
+        .collect();
+    Comparison {
+        previous: previous_name.to_string(),
+        previous_score: previous.score,
+        persisting: report.findings.len() - new.len(),
+        new,
+        fixed,
+    }
+}
+
+/// Read a report written with `--format json`.
+fn load_report(path: &Path) -> Result<Report> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("Could not read {}: {e}", path.display()))?;
+    let report = serde_json::from_str(&contents)
+        .map_err(|e| format!("Invalid audit report {}: {e}", path.display()))?;
+    Ok(report)
+}
+
+/// The report of the findings in `tree`, whose root is described by `root`.
+pub(crate) fn report(root: String, tree: &AuditTree, findings: &[Finding]) -> Report {
+    let count = |severity| {
+        findings
+            .iter()
+            .filter(|finding| finding.severity == severity)
+            .count()
+    };
+    let penalty: usize = findings
+        .iter()
+        .map(|finding| finding.severity.penalty())
+        .sum();
+    let nodes = tree.nodes.len().max(1);
+    let score = (100 * nodes / (nodes + penalty)) as u32;
+
+    let mut rules: Vec<RuleCount> = Vec::new();
+    for finding in findings {
+        match rules.iter_mut().find(|count| count.rule == finding.rule) {
+            Some(count) => count.findings += 1,
+            None => rules.push(RuleCount {
+                rule: finding.rule.clone(),
+                findings: 1,
+            }),
+        }
+    }
+    rules.sort_by(|a, b| b.findings.cmp(&a.findings).then(a.rule.cmp(&b.rule)));
+
+    Report {
+        root,
+        nodes: tree.nodes.len(),
+        score,
+        errors: count(Severity::Error),
+        warnings: count(Severity::Warning),
+        infos: count(Severity::Info),
+        rules,
+        worst_subtrees: worst_subtrees(tree, findings),
+        findings: findings
+            .iter()
+            .map(|finding| {
+                let node = &tree.nodes[finding.node];
+                ReportedFinding {
+                    rule: finding.rule.clone(),
+                    severity: finding.severity,
+                    path: node.path.clone(),
+                    node: node.description(),
+                    message: finding.message.clone(),
+                }
+            })
+            .collect(),
+        comparison: None,
+    }
+}
+
+/// The smallest subtrees below the root with the most severe findings: a node is left
+/// out when one of its children has all of its findings.
+fn worst_subtrees(tree: &AuditTree, findings: &[Finding]) -> Vec<Subtree> {
+    let mut counts = vec![(0usize, 0usize); tree.nodes.len()];
+    for finding in findings {
+        let mut node = Some(finding.node);
+        while let Some(index) = node {
+            counts[index].0 += 1;
+            counts[index].1 += finding.severity.penalty();
+            node = tree.nodes[index].parent;
+        }
+    }
+
+    let mut subtrees: Vec<usize> = (1..tree.nodes.len())
+        .filter(|index| counts[*index].0 > 0 && !tree.nodes[*index].children.is_empty())
+        .filter(|index| {
+            !tree.nodes[*index]
+                .children
+                .iter()
+                .any(|child| counts[*child] == counts[*index])
+        })
+        .collect();
+    subtrees.sort_by(|a, b| counts[*b].1.cmp(&counts[*a].1).then(a.cmp(b)));
+    subtrees
+        .into_iter()
+        .take(WORST_SUBTREES)
+        .map(|index| Subtree {
+            path: tree.nodes[index].path.clone(),
+            node: tree.nodes[index].description(),
+            findings: counts[index].0,
+            penalty: counts[index].1,
+        })
+        .collect()
+}
+
+fn print_report(report: &Report) {
+    println!(
+        "Audited {} nodes, found {} problems",
+        report.nodes,
+        report.findings.len()
+    );
+    if !report.findings.is_empty() {
+        let rows: Vec<Vec<String>> = report
+            .findings
+            .iter()
+            .map(|finding| {
+                vec![
+                    finding.severity.name().to_string(),
+                    finding.rule.clone(),
+                    location(&finding.path),
+                    finding.node.clone(),
+                    finding.message.clone(),
+                ]
+            })
+            .collect();
+        crate::print_table(&["Severity", "Rule", "Location", "Node", "Problem"], &rows);
+    }
+
+    println!();
+    println!(
+        "Score: {}/100 ({} errors, {} warnings, {} infos)",
+        report.score, report.errors, report.warnings, report.infos
+    );
+    if !report.rules.is_empty() {
+        let rows: Vec<Vec<String>> = report
+            .rules
+            .iter()
+            .map(|count| vec![count.rule.clone(), count.findings.to_string()])
+            .collect();
+        crate::print_table(&["Rule", "Findings"], &rows);
+    }
+    if !report.worst_subtrees.is_empty() {
+        println!("Worst subtrees:");
+        let rows: Vec<Vec<String>> = report
+            .worst_subtrees
+            .iter()
+            .map(|subtree| {
+                vec![
+                    location(&subtree.path),
+                    subtree.node.clone(),
+                    subtree.findings.to_string(),
+                    subtree.penalty.to_string(),
+                ]
+            })
+            .collect();
+        crate::print_table(&["Location", "Node", "Findings", "Penalty"], &rows);
+    }
+
+    if let Some(comparison) = &report.comparison {
+        println!();
+        println!(
+            "Compared to {} (score {}): {} new, {} fixed, {} persisting",
+            comparison.previous,
+            comparison.previous_score,
+            comparison.new.len(),
+            comparison.fixed.len(),
+            comparison.persisting
+        );
+        let rows: Vec<Vec<String>> = [("new", &comparison.new), ("fixed", &comparison.fixed)]
+            .into_iter()
+            .flat_map(|(change, findings)| {
+                findings.iter().map(move |finding| {
+                    vec![
+                        change.to_string(),
+                        finding.severity.name().to_string(),
+                        finding.rule.clone(),
+                        location(&finding.path),
+                        finding.node.clone(),
+                        finding.message.clone(),
+                    ]
+                })
+            })
+            .collect();
+        if !rows.is_empty() {
+            crate::print_table(
+                &["Change", "Severity", "Rule", "Location", "Node", "Problem"],
+                &rows,
+            );
+        }
+    }
+}
+
+/// The name of a relation, e.g. `labelled-by` for `RelationType::LabelledBy`.
+pub(crate) fn relation_name(relation: RelationType) -> String {
+    let mut name = String::new();
+    for (i, c) in format!("{relation:?}").chars().enumerate() {
+        if c.is_ascii_uppercase() && i > 0 {
+            name.push('-');
+        }
+        name.push(c.to_ascii_lowercase());
+    }
+    name
+}
+
+/// The relation with this name, as given by `relation_name`.
+pub(crate) fn relation_from_name(name: &str) -> Option<RelationType> {
+    RELATION_TYPES
+        .iter()
+        .copied()
+        .find(|relation| relation_name(*relation) == name)
+}
+
+/// Where a node is, by its index path.
+pub(crate) fn location(path: &str) -> String {
+    if path.is_empty() {
+        "the root".to_string()
+    } else {
+        format!("path {path}")
+    }
+}
+
+/// Collect `root` and the nodes below it, in depth-first order, with what `detail` asks for
+/// of each node. Audits need the default detail.
+pub(crate) async fn collect(
+    conn: &Connection,
+    root: &Selected,
+    detail: &Detail,
+) -> Result<AuditTree> {
+    // Depth, skipped roles, concurrency and timeout as for the printed trees.
+    let traversal = tree::configured();
+    let mut nodes: Vec<AuditNode> = Vec::new();
+    // The nodes yet to be collected, with their index path, depth below `root`, role if
+    // fetched already, and the index of their parent.
+    let mut stack = vec![(
+        root.proxy.clone(),
+        root.path.clone(),
+        0,
+        None::<Option<Role>>,
+        None,
+    )];
+    let _traversal = interrupt::Traversal::begin();
+
+    while let Some((proxy, path, depth, role, parent)) = stack.pop() {
+        if interrupt::interrupted() {
+            warn!(
+                "interrupted, the tree below {} is partial: {} nodes were not reached",
+                root.description,
+                stack.len() + 1
+            );
+            break;
+        }
+        let object = ObjectRef {
+            name: UniqueName::try_from(proxy.inner().destination().to_string())?.into(),
+            path: proxy.inner().path().to_owned().into(),
+        };
+        let index = nodes.len();
+        if let Some(parent) = parent {
+            let parent: &mut AuditNode = &mut nodes[parent];
+            parent.children.push(index);
+        }
+
+        let role = match role {
+            Some(role) => role.unwrap_or(Role::Invalid),
+            None => traversal
+                .call(|| proxy.get_role())
+                .await
+                .unwrap_or(Role::Invalid),
+        };
+        if !traversal.is_deepest(depth) {
+            let children = traversal.children(conn, &proxy).await?;
+            // Pushed in reverse, so the first child is visited first.
+            for (child_index, child, child_role) in children.into_iter().rev() {
+                let child_path = match path.as_str() {
+                    "" => child_index.to_string(),
+                    path => format!("{path}/{child_index}"),
+                };
+                stack.push((child, child_path, depth + 1, child_role, Some(index)));
+            }
+        }
+
+        let mut node = AuditNode {
+            object,
+            path,
+            parent,
+            children: Vec::new(),
+            role,
+            name: String::new(),
+            accessible_description: String::new(),
+            accessible_id: String::new(),
+            attributes: HashMap::new(),
+            states: StateSet::empty(),
+            relations: Vec::new(),
+            locale: String::new(),
+            table: None,
+            reported_parent: None,
+            index_in_parent: -1,
+            extents: None,
+            text: None,
+        };
+        // Only ask for what is wanted, each property is a round trip to the application.
+        if detail.has(Facet::Names) {
+            node.name = traversal.call(|| proxy.name()).await.unwrap_or_default();
+            node.accessible_description = traversal
+                .call(|| proxy.description())
+                .await
+                .unwrap_or_default();
+            node.accessible_id = traversal
+                .call(|| proxy.accessible_id())
+                .await
+                .unwrap_or_default();
+            node.locale = traversal.call(|| proxy.locale()).await.unwrap_or_default();
+        }
+        if detail.has(Facet::States) {
+            node.states = traversal
+                .call(|| proxy.get_state())
+                .await
+                .unwrap_or_else(|_| StateSet::empty());
+        }
+        if detail.has(Facet::Attributes) {
+            node.attributes = traversal
+                .call(|| proxy.get_attributes())
+                .await
+                .unwrap_or_default();
+            node.table = table_info(&proxy).await;
+        }
+        if detail.has(Facet::Relations) {
+            node.relations = traversal
+                .call(|| proxy.get_relation_set())
+                .await
+                .unwrap_or_default();
+            node.reported_parent = traversal
+                .call(|| proxy.parent())
+                .await
+                .ok()
+                .filter(|parent| parent.path.as_str() != watch::NULL_PATH);
+            node.index_in_parent = traversal
+                .call(|| proxy.get_index_in_parent())
+                .await
+                .unwrap_or(-1);
+        }
+        if detail.has(Facet::Geometry) {
+            node.extents = extents(&proxy).await;
+        }
+        if detail.has(Facet::Text) {
+            node.text = text(&proxy).await;
+        }
+        nodes.push(node);
+    }
+
+    let app_root = ObjectRef {
+        name: UniqueName::try_from(root.proxy.inner().destination().to_string())?.into(),
+        path: ObjectPath::from_static_str_unchecked(crate::ACCESSIBLE_ROOT).into(),
+    };
+    let app_locale = match watch::accessible_from_object_ref(conn, app_root).await {
+        Ok(app) => app.locale().await.unwrap_or_default(),
+        Err(_) => String::new(),
+    };
+
+    Ok(AuditTree {
+        nodes,
+        app_locale,
+        tab_order: None,
+    })
+}
+
+/// What the Table interface says about the node, or `None` if it does not implement it.
+async fn table_info(proxy: &AccessibleProxy<'_>) -> Option<TableInfo> {
+    let table = proxy.proxies().await.ok()?.table().await.ok()?;
+    let rows = table.nrows().await.ok()?;
+    let columns = table.ncolumns().await.ok()?;
+    let checked_columns = columns.clamp(0, MAX_TABLE_HEADERS);
+    let checked_rows = rows.clamp(0, MAX_TABLE_HEADERS);
+
+    let mut column_headers = 0;
+    for column in 0..checked_columns {
+        let header = table.get_column_header(column).await;
+        if exists(header.ok())
+            || !table
+                .get_column_description(column)
+                .await
+                .unwrap_or_default()
+                .is_empty()
+        {
+            column_headers += 1;
+        }
+    }
+    let mut row_headers = 0;
+    for row in 0..checked_rows {
+        let header = table.get_row_header(row).await;
+        if exists(header.ok())
+            || !table
+                .get_row_description(row)
+                .await
+                .unwrap_or_default()
+                .is_empty()
+        {
+            row_headers += 1;
+        }
+    }
+
+    Some(TableInfo {
+        rows,
+        columns,
+        column_headers,
+        row_headers,
+        checked_columns,
+        checked_rows,
+        has_caption: exists(table.caption().await.ok()),
+        has_summary: exists(table.summary().await.ok()),
+    })
+}
+
+/// Where the node is on the screen, or `None` if it does not implement Component.
+async fn extents(proxy: &AccessibleProxy<'_>) -> Option<Extents> {
+    let component = proxy.proxies().await.ok()?.component().await.ok()?;
+    let (x, y, width, height) = component.get_extents(CoordType::Screen).await.ok()?;
+    Some(Extents {
+        x,
+        y,
+        width,
+        height,
+    })
+}
+
+/// The text of the node, or `None` if it does not implement Text.
+async fn text(proxy: &AccessibleProxy<'_>) -> Option<String> {
+    let text = proxy.proxies().await.ok()?.text().await.ok()?;
+    let count = text.character_count().await.ok()?;
+    text.get_text(0, count).await.ok()
+}
+
+/// Whether an object reference points at an object.
+fn exists(object: Option<ObjectRef>) -> bool {
+    object.is_some_and(|object| object.path.as_str() != watch::NULL_PATH)
+}
+
+/// Press Tab through the application and map the nodes that got the focus into `tree`.
+async fn follow_tab_order(
+    a11y: &AccessibilityConnection,
+    root: &Selected,
+    tree: &AuditTree,
+    presses: Option<usize>,
+) -> Result<TabOrder> {
+    let focusable = tree
+        .nodes
+        .iter()
+        .filter(|node| node.states.contains(State::Focusable))
+        .count();
+    let presses = presses.unwrap_or((2 * focusable + 2).min(MAX_TAB_PRESSES));
+
+    eprintln!(
+        "Switch to the application's window, pressing Tab in {} seconds...",
+        TAB_ORDER_DELAY.as_secs()
+    );
+    tokio::time::sleep(TAB_ORDER_DELAY).await;
+    let sender = root.proxy.inner().destination().to_string();
+    let order = tab_order::follow(a11y, &sender, presses).await?;
+
+    let index_of = |object: &ObjectRef| tree.index_of(object);
+    let visited: Vec<usize> = order.visited.iter().filter_map(index_of).collect();
+    eprintln!("Tab reached {} nodes", visited.len());
+    Ok(TabOrder {
+        visited,
+        trap: order
+            .trap
+            .map(|trap| trap.iter().filter_map(index_of).collect())
+            .filter(|trap: &Vec<usize>| !trap.is_empty()),
+    })
+}
+
+/// Buttons, links, menu items and the like without a name or a label.
+fn unnamed_interactive(tree: &AuditTree, _: &AuditConfig) -> Vec<(usize, String)> {
+    tree.nodes
+        .iter()
+        .enumerate()
+        .filter(|(_, node)| INTERACTIVE_ROLES.contains(&node.role))
+        .filter(|(_, node)| {
+            node.name.trim().is_empty() && !node.has_relation(RelationType::LabelledBy)
+        })
+        .map(|(index, node)| {
+            (
+                index,
+                format!(
+                    "{} has no name and no labelled-by relation",
+                    node.role.name()
+                ),
+            )
+        })
+        .collect()
+}
+
+/// Entries, combo boxes, spin buttons and check boxes without a name or a label.
+fn unlabeled_form_field(tree: &AuditTree, _: &AuditConfig) -> Vec<(usize, String)> {
+    tree.nodes
+        .iter()
+        .enumerate()
+        .filter(|(_, node)| FORM_FIELD_ROLES.contains(&node.role))
+        .filter(|(_, node)| node.role != Role::Text || node.states.contains(State::Editable))
+        .filter(|(_, node)| {
+            node.name.trim().is_empty() && !node.has_relation(RelationType::LabelledBy)
+        })
+        .map(|(index, node)| {
+            (
+                index,
+                format!(
+                    "{} has no name and no labelled-by relation, so there is no telling what to enter",
+                    node.role.name()
+                ),
+            )
+        })
+        .collect()
+}
+
+/// Containers with several interactive children of the same name, e.g. five "Edit" buttons.
+fn duplicate_sibling_names(tree: &AuditTree, _: &AuditConfig) -> Vec<(usize, String)> {
+    let mut findings = Vec::new();
+    for (index, node) in tree.nodes.iter().enumerate() {
+        let mut by_name: Vec<(&str, Vec<&AuditNode>)> = Vec::new();
+        for child in node.children.iter().map(|child| &tree.nodes[*child]) {
+            let name = child.name.trim();
+            let interactive =
+                INTERACTIVE_ROLES.contains(&child.role) || FORM_FIELD_ROLES.contains(&child.role);
+            if !interactive || name.is_empty() {
+                continue;
+            }
+            match by_name.iter_mut().find(|(n, _)| *n == name) {
+                Some((_, siblings)) => siblings.push(child),
+                None => by_name.push((name, vec![child])),
+            }
+        }
+
+        for (name, siblings) in by_name.into_iter().filter(|(_, s)| s.len() > 1) {
+            let details: Vec<String> = siblings
+                .iter()
+                .map(|sibling| {
+                    let attributes = disambiguation(sibling);
+                    if attributes.is_empty() {
+                        sibling.location()
+                    } else {
+                        format!("{} ({})", sibling.location(), attributes.join(", "))
+                    }
+                })
+                .collect();
+            findings.push((
+                index,
+                format!(
+                    "{} interactive children are named {name:?}: {}",
+                    siblings.len(),
+                    details.join("; ")
+                ),
+            ));
+        }
+    }
+    findings
+}
+
+/// The description, id and attributes that tell a node apart from others with its name.
+fn disambiguation(node: &AuditNode) -> Vec<String> {
+    let mut attributes = Vec::new();
+    if !node.accessible_description.is_empty() {
+        attributes.push(format!("description {:?}", node.accessible_description));
+    }
+    if !node.accessible_id.is_empty() {
+        attributes.push(format!("accessible id {:?}", node.accessible_id));
+    }
+    for name in DISAMBIGUATING_ATTRIBUTES {
+        if let Some(value) = node.attributes.get(*name).filter(|value| !value.is_empty()) {
+            attributes.push(format!("{name} {value:?}"));
+        }
+    }
+    attributes
+}
+
+/// Showing, enabled, focusable buttons, links and fields that Tab never reached.
+///
+/// Groups like radio buttons and tab lists are reached at one member and traversed
+/// with the arrow keys, so a node is not reported when a sibling of its role was reached.
+fn unreachable_focusable(tree: &AuditTree, _: &AuditConfig) -> Vec<(usize, String)> {
+    let Some(order) = &tree.tab_order else {
+        return Vec::new();
+    };
+    // A trap cuts the traversal short, so what comes after it was never given a chance.
+    if order.trap.is_some() {
+        return Vec::new();
+    }
+    let visited_sibling = |index: usize, node: &AuditNode| {
+        node.parent.is_some_and(|parent| {
+            tree.nodes[parent].children.iter().any(|sibling| {
+                *sibling != index
+                    && tree.nodes[*sibling].role == node.role
+                    && order.visited.contains(sibling)
+            })
+        })
+    };
+
+    tree.nodes
+        .iter()
+        .enumerate()
+        .filter(|(_, node)| TAB_STOP_ROLES.contains(&node.role))
+        .filter(|(_, node)| node.role != Role::Text || node.states.contains(State::Editable))
+        .filter(|(_, node)| {
+            [State::Focusable, State::Showing, State::Enabled]
+                .iter()
+                .all(|state| node.states.contains(*state))
+        })
+        .filter(|(index, node)| !order.visited.contains(index) && !visited_sibling(*index, node))
+        .map(|(index, node)| {
+            (
+                index,
+                format!(
+                    "{} is focusable but pressing Tab never reached it",
+                    node.role.name()
+                ),
+            )
+        })
+        .collect()
+}
+
+/// Focus that cycles through some nodes with Tab and cannot get out, reported on the
+/// container the nodes share.
+fn keyboard_trap(tree: &AuditTree, _: &AuditConfig) -> Vec<(usize, String)> {
+    let Some(trap) = tree
+        .tab_order
+        .as_ref()
+        .and_then(|order| order.trap.as_ref())
+    else {
+        return Vec::new();
+    };
+    let container = trap
+        .iter()
+        .copied()
+        .reduce(|a, b| common_ancestor(tree, a, b))
+        .unwrap_or(0);
+    let nodes: Vec<String> = trap
+        .iter()
+        .map(|index| {
+            let node = &tree.nodes[*index];
+            format!("{} at {}", node.description(), node.location())
+        })
+        .collect();
+    let message = if trap.len() == 1 {
+        format!(
+            "pressing Tab does not move the focus away from {}",
+            nodes[0]
+        )
+    } else {
+        format!(
+            "pressing Tab cycles through {} nodes and cannot leave them: {}",
+            trap.len(),
+            nodes.join("; ")
+        )
+    };
+    vec![(container, message)]
+}
+
+/// The deepest node that has both `a` and `b` below it or is one of them.
+fn common_ancestor(tree: &AuditTree, a: usize, b: usize) -> usize {
+    let ancestors = |mut index: usize| {
+        let mut chain = vec![index];
+        while let Some(parent) = tree.nodes[index].parent {
+            chain.push(parent);
+            index = parent;
+        }
+        chain
+    };
+    let of_b = ancestors(b);
+    ancestors(a)
+        .into_iter()
+        .find(|index| of_b.contains(index))
+        .unwrap_or(0)
+}
+
+/// Trees with more nodes than `max_nodes`, reported on the audited node.
+fn tree_too_large(tree: &AuditTree, config: &AuditConfig) -> Vec<(usize, String)> {
+    if tree.nodes.len() <= config.max_nodes {
+        return Vec::new();
+    }
+    vec![(
+        0,
+        format!(
+            "the tree has {} nodes, more than the budget of {}",
+            tree.nodes.len(),
+            config.max_nodes
+        ),
+    )]
+}
+
+/// Trees deeper than `max_depth`, reported on the deepest node.
+fn tree_too_deep(tree: &AuditTree, config: &AuditConfig) -> Vec<(usize, String)> {
+    let depths = tree.depths();
+    let Some((deepest, depth)) = depths
+        .iter()
+        .copied()
+        .enumerate()
+        .max_by_key(|(_, depth)| *depth)
+    else {
+        return Vec::new();
+    };
+    if depth <= config.max_depth {
+        return Vec::new();
+    }
+    let too_deep = depths
+        .iter()
+        .filter(|depth| **depth > config.max_depth)
+        .count();
+    vec![(
+        deepest,
+        format!(
+            "the node is {depth} levels deep, more than the budget of {}; {too_deep} nodes are deeper than that",
+            config.max_depth
+        ),
+    )]
+}
+
+/// Nodes with more children than `max_children`.
+fn too_many_children(tree: &AuditTree, config: &AuditConfig) -> Vec<(usize, String)> {
+    tree.nodes
+        .iter()
+        .enumerate()
+        .filter(|(_, node)| node.children.len() > config.max_children)
+        .map(|(index, node)| {
+            (
+                index,
+                format!(
+                    "the node has {} children, more than the budget of {}",
+                    node.children.len(),
+                    config.max_children
+                ),
+            )
+        })
+        .collect()
+}
+
+/// Nodes with states that contradict each other or their role, which leaves assistive
+/// technologies to guess which one is right.
+fn inconsistent_states(tree: &AuditTree, _: &AuditConfig) -> Vec<(usize, String)> {
+    let mut findings = Vec::new();
+    for (index, node) in tree.nodes.iter().enumerate() {
+        let states = &node.states;
+        let mut problems: Vec<String> = IMPLIED_STATES
+            .iter()
+            .filter(|(state, implied)| states.contains(*state) && !states.contains(*implied))
+            .map(|(state, implied)| {
+                format!(
+                    "{} without {}",
+                    state.to_static_str(),
+                    implied.to_static_str()
+                )
+            })
+            .collect();
+
+        if states.contains(State::Checked)
+            && !states.contains(State::Checkable)
+            && !CHECKABLE_ROLES.contains(&node.role)
+        {
+            problems.push(format!(
+                "checked on a {}, which is not checkable",
+                node.role.name()
+            ));
+        }
+        // Disabled widgets are neither enabled nor sensitive; one without the other is a mix-up.
+        let interactive =
+            INTERACTIVE_ROLES.contains(&node.role) || FORM_FIELD_ROLES.contains(&node.role);
+        if interactive
+            && states.contains(State::Showing)
+            && states.contains(State::Sensitive)
+            && !states.contains(State::Enabled)
+        {
+            problems.push("shown and sensitive, but not enabled".to_string());
+        }
+
+        if !problems.is_empty() {
+            findings.push((
+                index,
+                format!("inconsistent states: {}", problems.join("; ")),
+            ));
+        }
+    }
+    findings
+}
+
+/// The language and territory of a locale, without the encoding and modifier,
+/// so `en_US.UTF-8` and `en_US` are the same.
+fn locale_language(locale: &str) -> &str {
+    locale.split(['.', '@']).next().unwrap_or_default()
+}
+
+/// Nodes whose locale differs from the application's, reported where it starts to differ.
+fn locale_mismatch(tree: &AuditTree, _: &AuditConfig) -> Vec<(usize, String)> {
+    let app_locale = locale_language(&tree.app_locale);
+    if app_locale.is_empty() {
+        return Vec::new();
+    }
+    // Nodes that do not say have the locale of their parent.
+    let mut locales: Vec<&str> = Vec::with_capacity(tree.nodes.len());
+    let mut findings = Vec::new();
+    for (index, node) in tree.nodes.iter().enumerate() {
+        let inherited = node.parent.map_or(app_locale, |parent| locales[parent]);
+        let locale = match locale_language(&node.locale) {
+            "" => inherited,
+            locale => locale,
+        };
+        locales.push(locale);
+        if locale != app_locale && locale != inherited {
+            findings.push((
+                index,
+                format!(
+                    "the node's locale is {locale}, the application's is {app_locale}; \
+                     it may not have been translated"
+                ),
+            ));
+        }
+    }
+    findings
+}
+
+/// Whether a name looks like a message id rather than text, e.g. `IDS_SAVE`, `gtk-ok`,
+/// `SAVE_BUTTON_LABEL` or `settings.general.title`.
+fn looks_untranslated(name: &str) -> bool {
+    let name = name.trim();
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return false;
+    }
+    let screaming = name.contains('_')
+        && name.len() > 3
+        && name
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_');
+    let dotted_key = name.matches('.').count() >= 2
+        && !name.ends_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '.' | '_' | '-'));
+    MESSAGE_ID_PREFIXES
+        .iter()
+        .any(|prefix| name.starts_with(prefix))
+        || screaming
+        || dotted_key
+}
+
+/// With `--untranslated`: names that look like message ids that were never translated.
+fn untranslated_name(tree: &AuditTree, config: &AuditConfig) -> Vec<(usize, String)> {
+    if !config.untranslated {
+        return Vec::new();
+    }
+    tree.nodes
+        .iter()
+        .enumerate()
+        .filter(|(_, node)| looks_untranslated(&node.name))
+        .map(|(index, node)| {
+            (
+                index,
+                format!(
+                    "the name {:?} looks like an untranslated message id",
+                    node.name
+                ),
+            )
+        })
+        .collect()
+}
+
+/// Data tables without any column or row headers, so cells are announced without
+/// saying what they are, e.g. a spreadsheet-like view of bare numbers.
+fn table_without_headers(tree: &AuditTree, _: &AuditConfig) -> Vec<(usize, String)> {
+    tree.nodes
+        .iter()
+        .enumerate()
+        .filter_map(|(index, node)| Some((index, node.table.as_ref()?)))
+        .filter(|(_, table)| table.is_data_table())
+        .filter(|(_, table)| table.column_headers == 0 && table.row_headers == 0)
+        .map(|(index, table)| {
+            (
+                index,
+                format!(
+                    "the table has {} rows and {} columns, but no column or row headers",
+                    table.rows, table.columns
+                ),
+            )
+        })
+        .collect()
+}
+
+/// Data tables with column headers for some columns but not all of them.
+fn table_missing_headers(tree: &AuditTree, _: &AuditConfig) -> Vec<(usize, String)> {
+    tree.nodes
+        .iter()
+        .enumerate()
+        .filter_map(|(index, node)| Some((index, node.table.as_ref()?)))
+        .filter(|(_, table)| table.is_data_table())
+        .filter(|(_, table)| {
+            table.column_headers > 0 && table.column_headers < table.checked_columns
+        })
+        .map(|(index, table)| {
+            (
+                index,
+                format!(
+                    "{} of the {} columns have no header",
+                    table.checked_columns - table.column_headers,
+                    table.checked_columns
+                ),
+            )
+        })
+        .collect()
+}
+
+/// Data tables without a caption, summary, name or label, so there is no telling what they hold.
+fn table_without_caption(tree: &AuditTree, _: &AuditConfig) -> Vec<(usize, String)> {
+    tree.nodes
+        .iter()
+        .enumerate()
+        .filter_map(|(index, node)| Some((index, node, node.table.as_ref()?)))
+        .filter(|(_, _, table)| table.is_data_table() && !table.has_caption && !table.has_summary)
+        .filter(|(_, node, _)| {
+            node.name.trim().is_empty() && !node.has_relation(RelationType::LabelledBy)
+        })
+        .map(|(index, _, _)| {
+            (
+                index,
+                "the table has no caption, summary, name or labelled-by relation".to_string(),
+            )
+        })
+        .collect()
+}
+
+/// The headings of each document in the tree, in document order, with their levels.
+/// Headings belong to the nearest document above them.
+fn document_headings(tree: &AuditTree) -> Vec<(usize, Vec<(usize, u32)>)> {
+    let mut documents: Vec<(usize, Vec<(usize, u32)>)> = tree
+        .nodes
+        .iter()
+        .enumerate()
+        .filter(|(_, node)| DOCUMENT_ROLES.contains(&node.role))
+        .map(|(index, _)| (index, Vec::new()))
+        .collect();
+    for (index, node) in tree.nodes.iter().enumerate() {
+        let Some(level) = node.heading_level() else {
+            continue;
+        };
+        let mut ancestor = node.parent;
+        while let Some(parent) = ancestor {
+            if let Some((_, headings)) = documents.iter_mut().find(|(doc, _)| *doc == parent) {
+                headings.push((index, level));
+                break;
+            }
+            ancestor = tree.nodes[parent].parent;
+        }
+    }
+    documents
+}
+
+/// Headings that skip levels, e.g. a heading of level 3 right after one of level 1,
+/// which breaks navigating the document's outline by heading level.
+fn heading_level_skipped(tree: &AuditTree, _: &AuditConfig) -> Vec<(usize, String)> {
+    let mut findings = Vec::new();
+    for (_, headings) in document_headings(tree) {
+        let mut previous = 0;
+        for (index, level) in headings {
+            if level > previous + 1 {
+                let after = match previous {
+                    0 => "as the first heading of the document".to_string(),
+                    previous => format!("after a heading of level {previous}"),
+                };
+                findings.push((index, format!("heading of level {level} {after}")));
+            }
+            previous = level;
+        }
+    }
+    findings
+}
+
+/// Documents with headings, but none of level 1.
+fn missing_top_heading(tree: &AuditTree, _: &AuditConfig) -> Vec<(usize, String)> {
+    document_headings(tree)
+        .into_iter()
+        .filter(|(_, headings)| !headings.is_empty())
+        .filter(|(_, headings)| !headings.iter().any(|(_, level)| *level == 1))
+        .map(|(document, headings)| {
+            let top = headings.iter().map(|(_, level)| *level).min().unwrap_or(1);
+            (
+                document,
+                format!(
+                    "the document has {} headings, but none of level 1; the highest is level {top}",
+                    headings.len()
+                ),
+            )
+        })
+        .collect()
+}
+
+/// Relations the node they point at does not mirror, e.g. an entry labelled by a label
+/// that is not a label for it, or a group member missing from another member's group.
+/// Targets outside the audited tree are not checked.
+fn one_sided_relation(tree: &AuditTree, _: &AuditConfig) -> Vec<(usize, String)> {
+    let mut findings = Vec::new();
+    for (index, node) in tree.nodes.iter().enumerate() {
+        for (relation, name, reciprocal, reciprocal_name) in RECIPROCAL_RELATIONS {
+            for object in node.relation_targets(*relation) {
+                let Some(target_index) = tree.index_of(object) else {
+                    continue;
+                };
+                // Members of a group usually list themselves too.
+                if target_index == index {
+                    continue;
+                }
+                let target = &tree.nodes[target_index];
+                if !target
+                    .relation_targets(*reciprocal)
+                    .any(|back| *back == node.object)
+                {
+                    findings.push((
+                        index,
+                        format!(
+                            "{name} {} at {}, which has no {reciprocal_name} relation back",
+                            target.description(),
+                            target.location()
+                        ),
+                    ));
+                }
+            }
+        }
+    }
+    findings
+}
+
+/// Children whose Parent property does not point back at the node that lists them,
+/// or whose index in parent differs from where the parent lists them. Screen readers
+/// walk both ways, so they get lost or skip nodes.
+fn broken_parent_link(tree: &AuditTree, _: &AuditConfig) -> Vec<(usize, String)> {
+    let mut findings = Vec::new();
+    for (index, node) in tree.nodes.iter().enumerate() {
+        let Some(parent_index) = node.parent else {
+            continue;
+        };
+        let parent = &tree.nodes[parent_index];
+        let listed_by = format!("{} at {}", parent.description(), parent.location());
+
+        if node.reported_parent.as_ref() != Some(&parent.object) {
+            let reported = match &node.reported_parent {
+                None => "no parent".to_string(),
+                Some(object) => match tree.index_of(object) {
+                    Some(other) => format!(
+                        "{} at {}",
+                        tree.nodes[other].description(),
+                        tree.nodes[other].location()
+                    ),
+                    None => format!(
+                        "{}{} outside the audited tree",
+                        object.name.as_str(),
+                        object.path.as_str()
+                    ),
+                },
+            };
+            findings.push((
+                index,
+                format!("listed as a child of {listed_by}, but its parent is {reported}"),
+            ));
+            continue;
+        }
+
+        let position = parent.children.iter().position(|child| *child == index);
+        if let Some(position) = position.filter(|p| *p as i32 != node.index_in_parent) {
+            findings.push((
+                index,
+                format!(
+                    "child {position} of {listed_by}, but its index in parent is {}",
+                    node.index_in_parent
+                ),
+            ));
+        }
+    }
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A tree of nodes given by their parent, role and name, parents before their children,
+    /// whose parent links and indices agree with the tree.
+    fn tree(nodes: &[(Option<usize>, Role, &str)]) -> AuditTree {
+        let object = |index: usize| ObjectRef {
+            name: UniqueName::from_static_str_unchecked(":1.1").into(),
+            path: ObjectPath::try_from(format!("/node/{index}"))
+                .unwrap()
+                .into(),
+        };
+        let mut tree = AuditTree {
+            nodes: Vec::new(),
+            app_locale: String::new(),
+            tab_order: None,
+        };
+        for (index, (parent, role, name)) in nodes.iter().enumerate() {
+            let (path, index_in_parent) = match parent {
+                None => (String::new(), -1),
+                Some(parent) => {
+                    let position = tree.nodes[*parent].children.len();
+                    tree.nodes[*parent].children.push(index);
+                    let path = match tree.nodes[*parent].path.as_str() {
+                        "" => position.to_string(),
+                        parent_path => format!("{parent_path}/{position}"),
+                    };
+                    (path, position as i32)
+                }
+            };
+            tree.nodes.push(AuditNode {
+                object: object(index),
+                path,
+                parent: *parent,
+                children: Vec::new(),
+                role: *role,
+                name: name.to_string(),
+                accessible_description: String::new(),
+                accessible_id: String::new(),
+                attributes: HashMap::new(),
+                states: StateSet::empty(),
+                relations: Vec::new(),
+                locale: String::new(),
+                table: None,
+                reported_parent: parent.map(object),
+                index_in_parent,
+                extents: None,
+                text: None,
+            });
+        }
+        tree
+    }
+
+    /// The findings of a built-in rule with the default configuration.
+    fn run(
+        rule: fn(&AuditTree, &AuditConfig) -> Vec<(usize, String)>,
+        tree: &AuditTree,
+    ) -> Vec<(usize, String)> {
+        rule(tree, &AuditConfig::default())
+    }
+
+    #[test]
+    fn unnamed_widgets_need_a_labelled_by_relation() {
+        let mut tree = tree(&[
+            (None, Role::Frame, "Editor"),
+            (Some(0), Role::Button, ""),
+            (Some(0), Role::Button, " "),
+            (Some(0), Role::Button, "Save"),
+            (Some(0), Role::Label, ""),
+        ]);
+        let findings = run(unnamed_interactive, &tree);
+        assert_eq!(findings.len(), 2);
+        assert_eq!(
+            findings[0],
+            (
+                1,
+                "button has no name and no labelled-by relation".to_string()
+            )
+        );
+        assert_eq!(findings[1].0, 2);
+
+        let label = tree.nodes[4].object.clone();
+        tree.nodes[1].relations = vec![(RelationType::LabelledBy, vec![label])];
+        assert_eq!(run(unnamed_interactive, &tree).len(), 1);
+    }
+
+    #[test]
+    fn duplicate_names_of_interactive_siblings_are_reported_on_the_parent() {
+        let tree = tree(&[
+            (None, Role::Frame, "Editor"),
+            (Some(0), Role::Button, "OK"),
+            (Some(0), Role::Button, "OK"),
+            (Some(0), Role::Label, "Name"),
+            (Some(0), Role::Label, "Name"),
+            (Some(0), Role::Panel, ""),
+            (Some(5), Role::Button, "OK"),
+        ]);
+        let findings = run(duplicate_sibling_names, &tree);
+        assert_eq!(
+            findings,
+            [(
+                0,
+                "2 interactive children are named \"OK\": path 0; path 1".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn skipped_heading_levels_are_reported() {
+        let mut tree = tree(&[
+            (None, Role::DocumentWeb, ""),
+            (Some(0), Role::Heading, "Title"),
+            (Some(0), Role::Heading, "Details"),
+            (Some(0), Role::Heading, "Summary"),
+        ]);
+        for (index, level) in [(1, "1"), (2, "3"), (3, "2")] {
+            tree.nodes[index]
+                .attributes
+                .insert("level".to_string(), level.to_string());
+        }
+        assert_eq!(
+            run(heading_level_skipped, &tree),
+            [(
+                2,
+                "heading of level 3 after a heading of level 1".to_string()
+            )]
+        );
+
+        tree.nodes[1]
+            .attributes
+            .insert("level".to_string(), "2".to_string());
+        let findings = run(heading_level_skipped, &tree);
+        assert_eq!(
+            findings[0],
+            (
+                1,
+                "heading of level 2 as the first heading of the document".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn relations_without_one_back_are_one_sided() {
+        let mut tree = tree(&[
+            (None, Role::Frame, "Editor"),
+            (Some(0), Role::Label, "Name"),
+            (Some(0), Role::Entry, ""),
+        ]);
+        let entry = tree.nodes[2].object.clone();
+        tree.nodes[1].relations = vec![(RelationType::LabelFor, vec![entry])];
+        assert_eq!(
+            run(one_sided_relation, &tree),
+            [(
+                1,
+                "label-for entry at path 1, which has no labelled-by relation back".to_string()
+            )]
+        );
+
+        let label = tree.nodes[1].object.clone();
+        tree.nodes[2].relations = vec![(RelationType::LabelledBy, vec![label])];
+        assert!(run(one_sided_relation, &tree).is_empty());
+    }
+
+    #[test]
+    fn parent_links_must_agree_with_the_children() {
+        let mut tree = tree(&[
+            (None, Role::Frame, "Editor"),
+            (Some(0), Role::Button, "Open"),
+            (Some(0), Role::Button, "Save"),
+        ]);
+        assert!(run(broken_parent_link, &tree).is_empty());
+
+        tree.nodes[1].reported_parent = None;
+        tree.nodes[2].index_in_parent = 0;
+        assert_eq!(
+            run(broken_parent_link, &tree),
+            [
+                (
+                    1,
+                    "listed as a child of frame \"Editor\" at the root, but its parent is no parent"
+                        .to_string()
+                ),
+                (
+                    2,
+                    "child 1 of frame \"Editor\" at the root, but its index in parent is 0"
+                        .to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn report_scores_findings_by_severity_relative_to_the_tree() {
+        let tree = tree(&[
+            (None, Role::Frame, "Editor"),
+            (Some(0), Role::Panel, ""),
+            (Some(1), Role::Button, ""),
+            (Some(1), Role::Button, ""),
+            (Some(0), Role::Label, "Name"),
+        ]);
+        let finding = |rule: &str, severity, node| Finding {
+            rule: rule.to_string(),
+            severity,
+            node,
+            message: String::new(),
+        };
+        assert_eq!(report(String::new(), &tree, &[]).score, 100);
+
+        let findings = [
+            finding("unnamed-interactive", Severity::Error, 2),
+            finding("unnamed-interactive", Severity::Error, 3),
+            finding("locale-mismatch", Severity::Info, 4),
+        ];
+        let report = report(String::new(), &tree, &findings);
+        // 5 nodes against a penalty of 10 + 10 + 1.
+        assert_eq!(report.score, 100 * 5 / 26);
+        assert_eq!((report.errors, report.warnings, report.infos), (2, 0, 1));
+        assert_eq!(report.rules[0].rule, "unnamed-interactive");
+        assert_eq!(report.rules[0].findings, 2);
+        assert_eq!(report.worst_subtrees.len(), 1);
+        assert_eq!(report.worst_subtrees[0].path, "0");
+        assert_eq!(report.worst_subtrees[0].penalty, 20);
+    }
+}
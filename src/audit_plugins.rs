@@ -0,0 +1,330 @@
+//! Audit rules from plugins, for requirements of a product or organization that the
+//! built-in rules do not cover: Rhai scripts, or shared libraries in any language that
+//! can export C functions.
+//!
+//! A Rhai script, a file ending in `.rhai`:
+//!
+//! ```rhai
+//! const ID = "toolbar-button-tooltip";
+//! const SEVERITY = "warning";
+//!
+//! fn check(node) {
+//!     if node.role == "push button" && node.parent_role == "tool bar"
+//!         && !("tooltip" in node.attributes) {
+//!         return "tool bar buttons need a tooltip";
+//!     }
+//! }
+//! ```
+//!
+//! `check` is called for every node and returns a message for a problem, or nothing. The
+//! script's top-level statements run once, when it is loaded, not for each call.
+//! Nodes are maps with `name`, `role`, `description`, `id`, `path`, `states`,
+//! `attributes`, `relations`, `children`, `parent`, `parent_role` and `parent_name`.
+//! `SEVERITY` is `error`, `warning` or `info`, and `warning` if the script leaves it out.
+//!
+//! A shared library, any other file, exports:
+//!
+//! ```c
+//! const char *a11y_rule_id(void);
+//! const char *a11y_rule_severity(void);          /* optional */
+//! char *a11y_rule_check(const char *node_json);  /* NULL for no problem */
+//! void a11y_rule_free(char *message);
+//! ```
+//!
+//! `a11y_rule_check` gets the node as a JSON object with the keys of the Rhai maps, and
+//! returns a message it allocated, which is given back to `a11y_rule_free`.
+
+use crate::{
+    audit::{self, AuditConfig, AuditRule, AuditTree, Severity},
+    Result,
+};
+use atspi::ObjectRef;
+use libloading::Library;
+use rhai::{CallFnOptions, Dynamic, Engine, ImmutableString, Scope, AST};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    ffi::{c_char, CStr, CString},
+    path::{Path, PathBuf},
+};
+
+/// Load the rule plugin at `path`, a Rhai script if it ends in `.rhai`, otherwise a shared
+/// library.
+pub(crate) fn load(path: &Path) -> Result<Box<dyn AuditRule>> {
+    if path
+        .extension()
+        .is_some_and(|extension| extension == "rhai")
+    {
+        Ok(Box::new(ScriptRule::load(path)?))
+    } else {
+        Ok(Box::new(LibraryRule::load(path)?))
+    }
+}
+
+/// A node as plugins get it.
+#[derive(Serialize)]
+struct PluginNode<'a> {
+    name: &'a str,
+    role: &'static str,
+    description: &'a str,
+    id: &'a str,
+    path: &'a str,
+    states: Vec<&'static str>,
+    attributes: BTreeMap<&'a str, &'a str>,
+    /// Targets are given by path, or as an empty string if they are outside the audited
+    /// tree.
+    relations: BTreeMap<String, Vec<&'a str>>,
+    children: Vec<&'a str>,
+    parent: Option<&'a str>,
+    parent_role: &'static str,
+    parent_name: &'a str,
+}
+
+/// The problems `check` finds in the nodes of `tree`, by node.
+fn check_nodes(
+    tree: &AuditTree,
+    mut check: impl FnMut(&PluginNode) -> Result<Option<String>>,
+) -> Result<Vec<(usize, String)>> {
+    // Relation targets are looked up by object for every node.
+    let indices: HashMap<&ObjectRef, usize> = tree
+        .nodes
+        .iter()
+        .enumerate()
+        .map(|(index, node)| (&node.object, index))
+        .collect();
+
+    let mut problems = Vec::new();
+    for index in 0..tree.nodes.len() {
+        if let Some(message) = check(&plugin_node(tree, &indices, index))? {
+            problems.push((index, message));
+        }
+    }
+    Ok(problems)
+}
+
+fn plugin_node<'a>(
+    tree: &'a AuditTree,
+    indices: &HashMap<&ObjectRef, usize>,
+    index: usize,
+) -> PluginNode<'a> {
+    let node = &tree.nodes[index];
+    let parent = node.parent.map(|parent| &tree.nodes[parent]);
+    PluginNode {
+        name: &node.name,
+        role: node.role.name(),
+        description: &node.accessible_description,
+        id: &node.accessible_id,
+        path: &node.path,
+        states: node
+            .states
+            .iter()
+            .map(|state| state.to_static_str())
+            .collect(),
+        attributes: node
+            .attributes
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect(),
+        relations: node
+            .relations
+            .iter()
+            .map(|(relation, targets)| {
+                let targets = targets
+                    .iter()
+                    .map(|target| {
+                        indices
+                            .get(target)
+                            .map_or("", |target| tree.nodes[*target].path.as_str())
+                    })
+                    .collect();
+                (audit::relation_name(*relation), targets)
+            })
+            .collect(),
+        children: node
+            .children
+            .iter()
+            .map(|child| tree.nodes[*child].path.as_str())
+            .collect(),
+        parent: parent.map(|parent| parent.path.as_str()),
+        parent_role: parent.map_or("", |parent| parent.role.name()),
+        parent_name: parent.map_or("", |parent| parent.name.as_str()),
+    }
+}
+
+/// A rule from a Rhai script.
+struct ScriptRule {
+    path: PathBuf,
+    id: String,
+    severity: Severity,
+    engine: Engine,
+    ast: AST,
+}
+
+impl ScriptRule {
+    fn load(path: &Path) -> Result<ScriptRule> {
+        let engine = Engine::new();
+        let ast = engine
+            .compile_file(path.to_path_buf())
+            .map_err(|e| format!("Invalid rule plugin {}: {e}", path.display()))?;
+
+        let mut scope = Scope::new();
+        engine
+            .run_ast_with_scope(&mut scope, &ast)
+            .map_err(|e| format!("Invalid rule plugin {}: {e}", path.display()))?;
+        let Some(id) = scope.get_value::<ImmutableString>("ID") else {
+            return Err(format!("The rule plugin {} does not set ID", path.display()).into());
+        };
+        let severity = scope
+            .get_value::<ImmutableString>("SEVERITY")
+            .map(|name| name.to_string());
+        let severity = parse_severity(severity.as_deref(), path)?;
+
+        Ok(ScriptRule {
+            path: path.to_path_buf(),
+            id: id.to_string(),
+            severity,
+            engine,
+            ast,
+        })
+    }
+}
+
+impl AuditRule for ScriptRule {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    fn check(&self, tree: &AuditTree, _config: &AuditConfig) -> Result<Vec<(usize, String)>> {
+        // The top-level statements ran when the script was loaded.
+        check_nodes(tree, |node| {
+            let node = rhai::serde::to_dynamic(node)?;
+            let problem: Dynamic = self
+                .engine
+                .call_fn_with_options(
+                    CallFnOptions::new().eval_ast(false),
+                    &mut Scope::new(),
+                    &self.ast,
+                    "check",
+                    (node,),
+                )
+                .map_err(|e| format!("The rule plugin {} failed: {e}", self.path.display()))?;
+
+            if problem.is_unit() {
+                return Ok(None);
+            }
+            match problem.into_immutable_string() {
+                Ok(message) => Ok(Some(message.to_string())),
+                Err(kind) => Err(format!(
+                    "The rule plugin {} returned a {kind} from check, expected a string or nothing",
+                    self.path.display()
+                )
+                .into()),
+            }
+        })
+    }
+}
+
+type IdFn = unsafe extern "C" fn() -> *const c_char;
+type CheckFn = unsafe extern "C" fn(*const c_char) -> *mut c_char;
+type FreeFn = unsafe extern "C" fn(*mut c_char);
+
+/// A rule from a shared library.
+struct LibraryRule {
+    path: PathBuf,
+    id: String,
+    severity: Severity,
+    check: CheckFn,
+    free: FreeFn,
+    /// Keeps `check` and `free` loaded.
+    _library: Library,
+}
+
+impl LibraryRule {
+    fn load(path: &Path) -> Result<LibraryRule> {
+        let invalid =
+            |e: &dyn std::fmt::Display| format!("Invalid rule plugin {}: {e}", path.display());
+        // SAFETY: loading a library runs its initializers; plugins are trusted like the
+        // scripts they stand in for. The functions are declared as the module docs say.
+        unsafe {
+            let library = Library::new(path).map_err(|e| invalid(&e))?;
+            let id_fn = *library
+                .get::<IdFn>(b"a11y_rule_id\0")
+                .map_err(|e| invalid(&e))?;
+            let severity_fn = library
+                .get::<IdFn>(b"a11y_rule_severity\0")
+                .ok()
+                .map(|f| *f);
+            let check = *library
+                .get::<CheckFn>(b"a11y_rule_check\0")
+                .map_err(|e| invalid(&e))?;
+            let free = *library
+                .get::<FreeFn>(b"a11y_rule_free\0")
+                .map_err(|e| invalid(&e))?;
+
+            let string = |ptr: *const c_char| {
+                (!ptr.is_null()).then(|| CStr::from_ptr(ptr).to_string_lossy().into_owned())
+            };
+            let Some(id) = string(id_fn()) else {
+                return Err(
+                    format!("The rule plugin {} does not set an id", path.display()).into(),
+                );
+            };
+            let severity = parse_severity(severity_fn.and_then(|f| string(f())).as_deref(), path)?;
+
+            Ok(LibraryRule {
+                path: path.to_path_buf(),
+                id,
+                severity,
+                check,
+                free,
+                _library: library,
+            })
+        }
+    }
+}
+
+impl AuditRule for LibraryRule {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    fn check(&self, tree: &AuditTree, _config: &AuditConfig) -> Result<Vec<(usize, String)>> {
+        check_nodes(tree, |node| {
+            let json = CString::new(serde_json::to_string(node)?)
+                .map_err(|e| format!("The node cannot be given to {}: {e}", self.path.display()))?;
+            // SAFETY: the plugin gets a valid string for the call, and returns NULL or a
+            // string that stays valid until it is given back to `free`.
+            unsafe {
+                let message = (self.check)(json.as_ptr());
+                if message.is_null() {
+                    return Ok(None);
+                }
+                let text = CStr::from_ptr(message).to_string_lossy().into_owned();
+                (self.free)(message);
+                Ok(Some(text))
+            }
+        })
+    }
+}
+
+/// The severity a plugin asks for, `warning` if it does not say.
+fn parse_severity(name: Option<&str>, path: &Path) -> Result<Severity> {
+    match name {
+        None => Ok(Severity::Warning),
+        Some(name) => Severity::parse(name).ok_or_else(|| {
+            format!(
+                "Unknown severity {name:?} in {}, expected error, warning or info",
+                path.display()
+            )
+            .into()
+        }),
+    }
+}
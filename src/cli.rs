@@ -0,0 +1,689 @@
+//! The command line interface: its arguments, and running the command they give.
+
+use crate::{
+    actions, apps, assertions, audit, component, config, diff, editable_text,
+    exit::{self, Failure},
+    expand, export, hyperlinks, inspect, interrupt, keys, latency, live, logging,
+    logging::LogFormat,
+    machine, man, parse_bus_name, parse_bus_names, print_application_sections,
+    print_properties_of_apps, print_snapshot, print_tree,
+    properties::{self, Properties},
+    recorder, registry_applications, render, retry, script, scripting, selection, snapshot, table,
+    tree::{self, TreeBuilder},
+    value, verify, version, wait, watch, Matching, Result, DRY_RUN, GLOB_PREFIX, MACHINE, MATCHING,
+    PID_PREFIX, REGEX_PREFIX, WINDOW_PREFIX,
+};
+use argh::{ArgsInfo, FromArgs};
+use atspi::{connection::set_session_accessibility, Role};
+use std::{
+    io::{BufRead, IsTerminal},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tracing::error;
+use zbus::{names::BusName, Connection};
+
+/// Inspect and test the accessibility trees of applications over AT-SPI
+#[derive(FromArgs, ArgsInfo)]
+#[argh(
+    example = "a11y-app gedit -p",
+    example = "a11y-app tree gedit --query 'dialog \"Preferences\"'",
+    example = "a11y-app --first audit gedit --format json",
+    note = "Without a command, a11y-app prints the accessible properties of the root \
+            objects of the applications, and with -p their trees. Applications are given \
+            by bus name or application name, by pid:<n>, window:<title>, glob:<pattern> \
+            or regex:<expression>, or as snapshot files saved with `snapshot`.",
+    error_code(1, "An error, or an invalid command line."),
+    error_code(3, "There is no accessibility bus to connect to."),
+    error_code(4, "No application, or no node of one, matches what was given."),
+    error_code(
+        5,
+        "Nodes could not be reached, e.g. in time, so the output is incomplete."
+    ),
+    error_code(6, "audit --fail-on found issues of that severity or worse."),
+    error_code(7, "An assertion of assert, or the comparison of verify, failed."),
+    error_code(
+        130,
+        "Interrupted with Ctrl-C; a traversal stops and its output is partial."
+    )
+)]
+struct AccessibleBusName {
+    /// the bus names or application names to be used
+    /// (default: org.a11y.atspi.Registry)
+    #[argh(positional)]
+    bus_names: Vec<String>,
+
+    /// the application with this process id, instead of a bus name or application name
+    #[argh(option)]
+    pid: Option<u32>,
+
+    /// the application with a window whose title contains this, case-insensitively,
+    /// instead of a bus name or application name
+    #[argh(option)]
+    window: Option<String>,
+
+    /// all applications with a bus name or application name matching this glob pattern,
+    /// with `*` and `?`, e.g. `org.gnome.*`
+    #[argh(option)]
+    glob: Option<String>,
+
+    /// all applications with a bus name or application name matching this regular
+    /// expression
+    #[argh(option)]
+    regex: Option<String>,
+
+    /// more applications, one per line of this file, or of standard input with `-`;
+    /// empty lines and lines starting with `#` are skipped, and applications that are
+    /// not found do not stop the others
+    #[argh(option)]
+    targets_file: Option<PathBuf>,
+
+    /// for scripts: never ask, pause or color, and print only what was asked for to
+    /// standard output, with everything else on standard error
+    #[argh(switch)]
+    machine: bool,
+
+    /// resolve the nodes commands act on, e.g. those of do-action, set-text or key, and
+    /// print what they would do to them, without doing it
+    #[argh(switch)]
+    dry_run: bool,
+
+    /// write more about what a11y-app does to standard error; give twice or three times
+    /// for more detail
+    #[argh(switch, short = 'v')]
+    verbose: u8,
+
+    /// write only errors to standard error, without warnings
+    #[argh(switch, short = 'q')]
+    quiet: bool,
+
+    /// the format of what is written to standard error: text, or json for log collectors
+    /// (default: text)
+    #[argh(
+        option,
+        from_str_fn(logging::parse_log_format),
+        default = "LogFormat::Text"
+    )]
+    log_format: LogFormat,
+
+    /// the properties to show, separated by commas: name, description, locale,
+    /// accessible-id, child-count, parent, help-text, role, states, interfaces, attributes,
+    /// index-in-parent, default (the first seven) or all (default: default)
+    #[argh(
+        option,
+        from_str_fn(properties::parse_properties),
+        default = "Properties::default()"
+    )]
+    props: Properties,
+
+    /// how many times to retry a call to an application that failed, while traversing
+    /// trees and fetching properties (default: 0)
+    #[argh(option, default = "0")]
+    retries: u32,
+
+    /// milliseconds to wait before retrying a failed call, doubled for each next retry
+    /// (default: 100)
+    #[argh(option, default = "retry::DEFAULT_BACKOFF")]
+    backoff: u64,
+
+    /// how many levels below the root to traverse the printed and audited trees; the
+    /// nodes at this depth are leaves (default: all)
+    #[argh(option)]
+    max_depth: Option<usize>,
+
+    /// leave the nodes with this role, and everything below them, out of the printed and
+    /// audited trees, e.g. `table cell`; may be given more than once
+    #[argh(option, from_str_fn(tree::parse_role))]
+    skip_role: Vec<Role>,
+
+    /// how many calls to make at once for the children of a node while traversing the
+    /// printed and audited trees (default: one for each child)
+    #[argh(option)]
+    concurrency: Option<usize>,
+
+    /// milliseconds to wait for each call to an application while traversing the
+    /// printed and audited trees, after which the call counts as failed (default: as long
+    /// as D-Bus waits)
+    #[argh(option)]
+    timeout: Option<u64>,
+
+    /// how to write the printed trees: tree, as `tree` prints directories, json, dot for
+    /// Graphviz, html, or a format of the program embedding a11y-app (default: tree)
+    #[argh(
+        option,
+        from_str_fn(render::parse_renderer),
+        default = "render::term_tree()"
+    )]
+    tree_format: render::SharedRenderer,
+
+    /// what the printed trees show of each node: shape, roles, names, or full for states
+    /// and attributes too (default: roles)
+    #[argh(
+        option,
+        from_str_fn(tree::parse_detail),
+        default = "tree::Detail::default()"
+    )]
+    tree_detail: tree::Detail,
+
+    /// whether to print the tree(s) of accessible objects
+    #[argh(switch, short = 'p')]
+    print_tree: bool,
+
+    /// whether to print the tree(s) of accessible objects continuously
+    #[argh(switch, short = 'c')]
+    print_tree_loop: bool,
+
+    /// whether to print the tree(s) of accessible objects once and keep them
+    /// current by applying change events, instead of re-traversing
+    #[argh(switch, short = 'l')]
+    live: bool,
+
+    /// print the tree(s) without waiting for 'Enter' first; there is no pause when
+    /// the output is not a terminal
+    #[argh(switch)]
+    no_pause: bool,
+
+    /// add applications whose names only match case-insensitively or partially,
+    /// without asking
+    #[argh(switch, short = 'y')]
+    yes: bool,
+
+    /// take only the application that matches the name best, without asking
+    #[argh(switch)]
+    first: bool,
+
+    /// fail instead of asking whether to add an application that matches loosely
+    #[argh(switch)]
+    no_input: bool,
+
+    /// take the application with this number, from 1, in the list of applications a
+    /// name matches, as printed when it matches several
+    #[argh(option)]
+    index: Option<usize>,
+
+    /// print the versions of a11y-app, its AT-SPI libraries and the accessibility bus,
+    /// and whether the session has accessibility enabled, for bug reports
+    #[argh(switch)]
+    version: bool,
+
+    #[argh(subcommand)]
+    command: Option<Command>,
+}
+
+impl AccessibleBusName {
+    /// How to traverse the trees, as the options say, or else the configuration.
+    fn traversal(&self) -> TreeBuilder {
+        let config = config::get();
+        let skip_roles = if self.skip_role.is_empty() {
+            &config.skip_roles
+        } else {
+            &self.skip_role
+        };
+        let mut builder = TreeBuilder::new()
+            .skip_roles(skip_roles.iter().copied())
+            .detail(self.tree_detail);
+        if let Some(token) = interrupt::cancellation() {
+            builder = builder.cancel_on(token);
+        }
+        if let Some(depth) = self.max_depth {
+            builder = builder.max_depth(depth);
+        }
+        if let Some(calls) = self.concurrency.or(config.concurrency) {
+            builder = builder.concurrency(calls);
+        }
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(Duration::from_millis(timeout));
+        }
+        builder
+    }
+
+    fn matching(&self) -> Result<Matching> {
+        match (self.yes, self.first, self.no_input, self.index) {
+            (false, false, false, None) if self.machine => Ok(Matching::Exact),
+            (false, false, false, None) => match config::get().matching {
+                // There is no one to ask about each of a batch of applications.
+                None | Some(config::Matching::Ask) if self.targets_file.is_some() => {
+                    Ok(Matching::Exact)
+                }
+                None | Some(config::Matching::Ask) => Ok(Matching::Ask),
+                Some(config::Matching::Yes) => Ok(Matching::All),
+                Some(config::Matching::First) => Ok(Matching::First),
+                Some(config::Matching::NoInput) => Ok(Matching::Exact),
+            },
+            (true, false, false, None) => Ok(Matching::All),
+            (false, true, false, None) => Ok(Matching::First),
+            (false, false, true, None) => Ok(Matching::Exact),
+            (false, false, false, Some(0)) => Err("Applications are numbered from 1".into()),
+            (false, false, false, Some(index)) => Ok(Matching::Index(index)),
+            _ => Err("Give only one of --yes, --first, --index and --no-input".into()),
+        }
+    }
+}
+
+#[derive(FromArgs, ArgsInfo)]
+#[argh(subcommand)]
+enum Command {
+    Man(man::ManArgs),
+    Apps(apps::AppsArgs),
+    Props(inspect::PropsArgs),
+    Tree(inspect::TreeArgs),
+    Search(inspect::SearchArgs),
+    Watch(watch::WatchArgs),
+    Replay(watch::ReplayArgs),
+    Latency(latency::LatencyArgs),
+    Actions(actions::ActionsArgs),
+    DoAction(actions::DoActionArgs),
+    Toggle(actions::ToggleArgs),
+    GrabFocus(component::GrabFocusArgs),
+    ScrollTo(component::ScrollToArgs),
+    Click(component::ClickArgs),
+    SetText(editable_text::SetTextArgs),
+    InsertText(editable_text::InsertTextArgs),
+    DeleteText(editable_text::DeleteTextArgs),
+    GetValue(value::GetValueArgs),
+    SetValue(value::SetValueArgs),
+    Selection(selection::SelectionArgs),
+    Select(selection::SelectArgs),
+    Deselect(selection::DeselectArgs),
+    Key(keys::KeyArgs),
+    Run(script::RunArgs),
+    Rhai(scripting::RhaiArgs),
+    Assert(assertions::AssertArgs),
+    RecordScript(recorder::RecordScriptArgs),
+    Export(export::ExportArgs),
+    WaitFor(wait::WaitForArgs),
+    TableDump(table::TableDumpArgs),
+    Links(hyperlinks::LinksArgs),
+    ActivateLink(hyperlinks::ActivateLinkArgs),
+    Expand(expand::ExpandArgs),
+    Audit(audit::AuditArgs),
+    Snapshot(snapshot::SnapshotArgs),
+    Diff(diff::DiffArgs),
+    Verify(verify::VerifyArgs),
+}
+
+/// Run the command line interface with the arguments of the process.
+pub async fn run() -> Result<()> {
+    run_command().await?;
+    if interrupt::interrupted() {
+        return Err(Failure::boxed(
+            exit::INTERRUPTED,
+            "Interrupted, the output is partial",
+        ));
+    }
+    match exit::incomplete() {
+        Some(failure) => Err(failure),
+        None => Ok(()),
+    }
+}
+
+/// Stop a [`run`] started after this when `token` is cancelled: trees being traversed
+/// end with a [`tree::Cancelled`] error, and `watch`, `--live` and `snapshot --every`
+/// return. Ctrl-C is then left to the embedding program.
+pub fn cancel_on(token: tree::CancellationToken) {
+    interrupt::cancel_on(token);
+}
+
+/// The code the process exits with for `error`, an error returned by [`run`].
+pub fn exit_code(error: &(dyn std::error::Error + 'static)) -> u8 {
+    exit::code(error)
+}
+
+async fn run_command() -> Result<()> {
+    let mut args: AccessibleBusName = argh::from_env();
+    logging::init(args.verbose, args.quiet, args.log_format)?;
+    config::load()?;
+    if let Some(pid) = args.pid {
+        if !args.bus_names.is_empty() {
+            return Err("Give an application by name or by --pid, not both".into());
+        }
+        args.bus_names = vec![format!("{PID_PREFIX}{pid}")];
+    }
+    if let Some(title) = args.window.take() {
+        if !args.bus_names.is_empty() {
+            return Err("Give an application by name, by --pid or by --window, not more".into());
+        }
+        args.bus_names = vec![format!("{WINDOW_PREFIX}{title}")];
+    }
+    // Patterns add to the applications given otherwise.
+    if let Some(pattern) = args.glob.take() {
+        args.bus_names.push(format!("{GLOB_PREFIX}{pattern}"));
+    }
+    if let Some(pattern) = args.regex.take() {
+        args.bus_names.push(format!("{REGEX_PREFIX}{pattern}"));
+    }
+    if let Some(path) = &args.targets_file {
+        if args.command.is_some() {
+            return Err(
+                "--targets-file gives the applications of a11y-app without a command, \
+                        pass them to a command as its arguments"
+                    .into(),
+            );
+        }
+        let targets = read_targets(path)?;
+        args.bus_names.extend(targets);
+    }
+    let _ = MACHINE.set(args.machine);
+    let _ = DRY_RUN.set(args.dry_run);
+    retry::set(args.retries, args.backoff);
+    tree::set(args.traversal());
+    render::set(args.tree_format.clone());
+    let _ = MATCHING.set(args.matching()?);
+
+    if args.version {
+        return version::version().await;
+    }
+
+    // Replaying a recording, exporting a script, writing the manual page and reading
+    // snapshots do not need the accessibility bus.
+    if let Some(Command::Replay(replay_args)) = args.command {
+        return watch::replay(replay_args).await;
+    }
+
+    if let Some(Command::Export(export_args)) = args.command {
+        return export::export(export_args);
+    }
+
+    if let Some(Command::Man(man_args)) = args.command {
+        return man::man(&AccessibleBusName::get_args_info(), man_args);
+    }
+
+    match args.command {
+        Some(Command::Diff(diff_args)) if !diff_args.is_live() => {
+            return diff::diff(diff_args);
+        }
+        Some(Command::Audit(audit_args)) if audit_args.is_offline() => {
+            return audit::audit_offline(audit_args);
+        }
+        Some(Command::Props(props_args)) if props_args.is_offline() => {
+            return inspect::props_offline(props_args);
+        }
+        Some(Command::Tree(tree_args)) if tree_args.is_offline() => {
+            return inspect::tree_offline(tree_args);
+        }
+        Some(Command::Search(search_args)) if search_args.is_offline() => {
+            return inspect::search_offline(search_args);
+        }
+        None if !args.bus_names.is_empty()
+            && args
+                .bus_names
+                .iter()
+                .all(|name| snapshot::is_snapshot(name)) =>
+        {
+            for name in &args.bus_names {
+                print_snapshot(Path::new(name), args.print_tree)?;
+            }
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    // An embedding program stops the run by its token, and handles Ctrl-C itself.
+    if interrupt::cancellation().is_none() {
+        interrupt::install();
+    }
+    set_session_accessibility(true).await.map_err(|e| {
+        Failure::boxed(
+            exit::NO_BUS,
+            format!("Could not turn on accessibility for the session: {e}"),
+        )
+    })?;
+
+    let a11y = atspi::AccessibilityConnection::new().await.map_err(|e| {
+        Failure::boxed(
+            exit::NO_BUS,
+            format!("Could not connect to the accessibility bus: {e}"),
+        )
+    })?;
+    let conn = a11y.connection();
+
+    if let Some(Command::Apps(apps_args)) = args.command {
+        return apps::apps(&a11y, apps_args).await;
+    }
+
+    if let Some(Command::Props(props_args)) = args.command {
+        return inspect::props(&a11y, props_args).await;
+    }
+
+    if let Some(Command::Tree(tree_args)) = args.command {
+        return inspect::tree(&a11y, tree_args).await;
+    }
+
+    if let Some(Command::Search(search_args)) = args.command {
+        return inspect::search(&a11y, search_args).await;
+    }
+
+    if let Some(Command::Watch(watch_args)) = args.command {
+        return watch::watch(&a11y, watch_args).await;
+    }
+
+    if let Some(Command::Latency(latency_args)) = args.command {
+        return latency::latency(&a11y, latency_args).await;
+    }
+
+    if let Some(Command::Actions(actions_args)) = args.command {
+        return actions::actions(&a11y, actions_args).await;
+    }
+
+    if let Some(Command::DoAction(do_action_args)) = args.command {
+        return actions::do_action(&a11y, do_action_args).await;
+    }
+
+    if let Some(Command::Toggle(toggle_args)) = args.command {
+        return actions::toggle(&a11y, toggle_args).await;
+    }
+
+    if let Some(Command::GrabFocus(grab_focus_args)) = args.command {
+        return component::grab_focus(&a11y, grab_focus_args).await;
+    }
+
+    if let Some(Command::ScrollTo(scroll_to_args)) = args.command {
+        return component::scroll_to(&a11y, scroll_to_args).await;
+    }
+
+    if let Some(Command::Click(click_args)) = args.command {
+        return component::click(&a11y, click_args).await;
+    }
+
+    if let Some(Command::SetText(set_text_args)) = args.command {
+        return editable_text::set_text(&a11y, set_text_args).await;
+    }
+
+    if let Some(Command::InsertText(insert_text_args)) = args.command {
+        return editable_text::insert_text(&a11y, insert_text_args).await;
+    }
+
+    if let Some(Command::DeleteText(delete_text_args)) = args.command {
+        return editable_text::delete_text(&a11y, delete_text_args).await;
+    }
+
+    if let Some(Command::GetValue(get_value_args)) = args.command {
+        return value::get_value(&a11y, get_value_args).await;
+    }
+
+    if let Some(Command::SetValue(set_value_args)) = args.command {
+        return value::set_value(&a11y, set_value_args).await;
+    }
+
+    if let Some(Command::Selection(selection_args)) = args.command {
+        return selection::selection(&a11y, selection_args).await;
+    }
+
+    if let Some(Command::Select(select_args)) = args.command {
+        return selection::select(&a11y, select_args).await;
+    }
+
+    if let Some(Command::Deselect(deselect_args)) = args.command {
+        return selection::deselect(&a11y, deselect_args).await;
+    }
+
+    if let Some(Command::Key(key_args)) = args.command {
+        return keys::key(&a11y, key_args).await;
+    }
+
+    if let Some(Command::Run(run_args)) = args.command {
+        return script::run(&a11y, run_args).await;
+    }
+
+    if let Some(Command::Rhai(rhai_args)) = args.command {
+        return scripting::rhai(&a11y, rhai_args).await;
+    }
+
+    if let Some(Command::Assert(assert_args)) = args.command {
+        return assertions::assert(&a11y, assert_args).await;
+    }
+
+    if let Some(Command::RecordScript(record_script_args)) = args.command {
+        return recorder::record_script(&a11y, record_script_args).await;
+    }
+
+    if let Some(Command::WaitFor(wait_for_args)) = args.command {
+        return wait::wait_for(&a11y, wait_for_args).await;
+    }
+
+    if let Some(Command::TableDump(table_dump_args)) = args.command {
+        return table::table_dump(&a11y, table_dump_args).await;
+    }
+
+    if let Some(Command::Links(links_args)) = args.command {
+        return hyperlinks::list_links(&a11y, links_args).await;
+    }
+
+    if let Some(Command::ActivateLink(activate_link_args)) = args.command {
+        return hyperlinks::activate_link(&a11y, activate_link_args).await;
+    }
+
+    if let Some(Command::Expand(expand_args)) = args.command {
+        return expand::expand(&a11y, expand_args).await;
+    }
+
+    if let Some(Command::Audit(audit_args)) = args.command {
+        return audit::audit(&a11y, audit_args).await;
+    }
+
+    if let Some(Command::Snapshot(snapshot_args)) = args.command {
+        return snapshot::snapshot(&a11y, snapshot_args).await;
+    }
+
+    if let Some(Command::Diff(diff_args)) = args.command {
+        return diff::diff_live(&a11y, diff_args).await;
+    }
+
+    if let Some(Command::Verify(verify_args)) = args.command {
+        return verify::verify(&a11y, verify_args).await;
+    }
+
+    // The registry's tree holds those of all applications; they are shown one by one.
+    let whole_registry = args.bus_names.is_empty();
+    let (applications, failed) = match args.targets_file {
+        Some(_) => parse_targets(&args.bus_names, conn).await,
+        None if whole_registry => (registry_applications(conn).await?, Vec::new()),
+        None => (parse_bus_names(&args.bus_names, conn).await?, Vec::new()),
+    };
+    let applications2 = applications.clone();
+
+    if applications.is_empty() {
+        return Err(Failure::not_found("No application found"));
+    }
+
+    let pause = !args.no_pause
+        && !machine()
+        && config::get().pause != Some(false)
+        && std::io::stdout().is_terminal();
+
+    if whole_registry && !args.live && !args.print_tree_loop {
+        if args.print_tree && pause {
+            println!("Press 'Enter' to print the properties and trees of all applications...");
+            let _ = std::io::stdin().read_line(&mut String::new());
+        }
+        return print_application_sections(conn, &applications, &args.props, args.print_tree).await;
+    }
+
+    print_properties_of_apps(conn, &applications, &args.props).await?;
+
+    if args.live {
+        return live::live_trees(&a11y, &applications2, std::time::Duration::from_secs(1)).await;
+    }
+
+    if args.print_tree_loop {
+        if pause {
+            println!("Press 'Enter' to print the tree continuously...");
+            let _ = std::io::stdin().read_line(&mut String::new());
+        }
+
+        loop {
+            tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+            print_tree(conn, &applications2).await?;
+            if interrupt::interrupted() {
+                return Ok(());
+            }
+        }
+    }
+
+    if args.print_tree {
+        if pause {
+            println!("Press 'Enter' to print the tree...");
+            let _ = std::io::stdin().read_line(&mut String::new());
+        }
+
+        print_tree(conn, &applications2).await?;
+    }
+
+    if !failed.is_empty() {
+        return Err(Failure::not_found(format!(
+            "{} of {} targets were not found: {}",
+            failed.len(),
+            args.bus_names.len(),
+            failed.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+/// Parse the bus names of a batch, keeping each application once, and the names that
+/// could not be parsed, reported as errors rather than stopping the batch
+async fn parse_targets(
+    names: &[String],
+    conn: &Connection,
+) -> (Vec<(String, BusName<'static>)>, Vec<String>) {
+    let mut apps: Vec<(String, BusName<'static>)> = Vec::new();
+    let mut failed = Vec::new();
+    for name in names {
+        match parse_bus_name(name.clone(), conn).await {
+            Ok(found) => {
+                for app in found {
+                    if !apps.iter().any(|(_, bus_name)| *bus_name == app.1) {
+                        apps.push(app);
+                    }
+                }
+            }
+            Err(e) => {
+                error!("{name}: {e}");
+                failed.push(name.clone());
+            }
+        }
+    }
+    (apps, failed)
+}
+
+/// The applications listed in `path`, or on standard input if it is `-`, one per line.
+fn read_targets(path: &Path) -> Result<Vec<String>> {
+    let lines: Vec<String> = if path == Path::new("-") {
+        std::io::stdin()
+            .lock()
+            .lines()
+            .collect::<std::io::Result<_>>()
+            .map_err(|e| format!("Could not read the targets from standard input: {e}"))?
+    } else {
+        std::fs::read_to_string(path)
+            .map_err(|e| format!("Could not read {}: {e}", path.display()))?
+            .lines()
+            .map(str::to_string)
+            .collect()
+    };
+    Ok(lines
+        .into_iter()
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .collect())
+}
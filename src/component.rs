@@ -0,0 +1,248 @@
+//! Acting on a node's on-screen presence through the Component interface.
+
+use crate::{
+    target::{self, Selected},
+    ArgResult, Result,
+};
+use argh::{ArgsInfo, FromArgs};
+use atspi::{
+    proxy::{
+        component::ComponentProxy, device_event_controller::DeviceEventControllerProxy,
+        proxy_ext::ProxyExt,
+    },
+    AccessibilityConnection, CoordType, ScrollType, State,
+};
+use std::time::Duration;
+use zbus::Connection;
+
+/// How long to give the toolkit to update the focused state after grabbing focus.
+const FOCUS_SETTLE_TIME: Duration = Duration::from_millis(200);
+
+/// Move the keyboard focus to a node
+#[derive(FromArgs, ArgsInfo)]
+#[argh(subcommand, name = "grab-focus")]
+pub struct GrabFocusArgs {
+    /// the application, by bus name or application name
+    #[argh(positional)]
+    app: String,
+
+    /// the node, by the child indices leading to it from the application's root, e.g. `0/2/1`
+    #[argh(option)]
+    path: Option<String>,
+
+    /// the first node with this role and/or name, e.g. `push button "Save"`
+    #[argh(option)]
+    query: Option<String>,
+}
+
+/// Scroll the view so a node becomes visible
+#[derive(FromArgs, ArgsInfo)]
+#[argh(subcommand, name = "scroll-to")]
+pub struct ScrollToArgs {
+    /// the application, by bus name or application name
+    #[argh(positional)]
+    app: String,
+
+    /// where to put the node: top-left, bottom-right, top-edge, bottom-edge,
+    /// left-edge, right-edge or anywhere (default: anywhere)
+    #[argh(
+        option,
+        from_str_fn(parse_scroll_type),
+        default = "ScrollType::Anywhere"
+    )]
+    align: ScrollType,
+
+    /// scroll the node's top left corner to this point in window coordinates, e.g. `0,100`
+    #[argh(option, from_str_fn(parse_point))]
+    point: Option<(i32, i32)>,
+
+    /// the node, by the child indices leading to it from the application's root, e.g. `0/2/1`
+    #[argh(option)]
+    path: Option<String>,
+
+    /// the first node with this role and/or name, e.g. `push button "Save"`
+    #[argh(option)]
+    query: Option<String>,
+}
+
+/// Click a node's center with the pointer, through the device event controller
+#[derive(FromArgs, ArgsInfo)]
+#[argh(subcommand, name = "click")]
+pub struct ClickArgs {
+    /// the application, by bus name or application name
+    #[argh(positional)]
+    app: String,
+
+    /// the mouse button to click, 1 to 5 (default: 1, the left button)
+    #[argh(option, default = "1")]
+    button: u8,
+
+    /// double click
+    #[argh(switch)]
+    double: bool,
+
+    /// the node, by the child indices leading to it from the application's root, e.g. `0/2/1`
+    #[argh(option)]
+    path: Option<String>,
+
+    /// the first node with this role and/or name, e.g. `push button "Save"`
+    #[argh(option)]
+    query: Option<String>,
+}
+
+fn parse_scroll_type(value: &str) -> ArgResult<ScrollType> {
+    match value {
+        "top-left" => Ok(ScrollType::TopLeft),
+        "bottom-right" => Ok(ScrollType::BottomRight),
+        "top-edge" => Ok(ScrollType::TopEdge),
+        "bottom-edge" => Ok(ScrollType::BottomEdge),
+        "left-edge" => Ok(ScrollType::LeftEdge),
+        "right-edge" => Ok(ScrollType::RightEdge),
+        "anywhere" => Ok(ScrollType::Anywhere),
+        _ => Err(format!("Unknown alignment: {value}")),
+    }
+}
+
+fn parse_point(value: &str) -> ArgResult<(i32, i32)> {
+    let invalid = || format!("Invalid point {value:?}, expected x,y");
+    let (x, y) = value.split_once(',').ok_or_else(invalid)?;
+    let x = x.trim().parse().map_err(|_| invalid())?;
+    let y = y.trim().parse().map_err(|_| invalid())?;
+    Ok((x, y))
+}
+
+/// Select the node and get its Component interface.
+async fn component(
+    conn: &Connection,
+    app: &str,
+    path: Option<&str>,
+    query: Option<&str>,
+) -> Result<(Selected, ComponentProxy<'static>)> {
+    let node = target::select(conn, app, path, query).await?;
+    let proxies = node.proxy.proxies().await?;
+    match proxies.component().await {
+        Ok(component) => Ok((node, component)),
+        Err(_) => Err(format!(
+            "{} at {} does not implement the Component interface",
+            node.description,
+            node.location()
+        )
+        .into()),
+    }
+}
+
+pub async fn grab_focus(a11y: &AccessibilityConnection, args: GrabFocusArgs) -> Result<()> {
+    let conn = a11y.connection();
+    let (node, component) =
+        component(conn, &args.app, args.path.as_deref(), args.query.as_deref()).await?;
+
+    if crate::dry_run() {
+        println!("Would focus {} at {}", node.description, node.location());
+        return Ok(());
+    }
+    if !component.grab_focus().await? {
+        return Err(format!(
+            "{} at {} refused to take the focus",
+            node.description,
+            node.location()
+        )
+        .into());
+    }
+
+    // The call succeeding does not mean the toolkit moved the focus, the states tell.
+    tokio::time::sleep(FOCUS_SETTLE_TIME).await;
+    let states = node.proxy.get_state().await?;
+    if states.contains(State::Focused) {
+        println!("Focused {} at {}", node.description, node.location());
+        Ok(())
+    } else {
+        Err(format!(
+            "{} at {} accepted the focus, but is not in the focused state",
+            node.description,
+            node.location()
+        )
+        .into())
+    }
+}
+
+pub async fn scroll_to(a11y: &AccessibilityConnection, args: ScrollToArgs) -> Result<()> {
+    let conn = a11y.connection();
+    let (node, component) =
+        component(conn, &args.app, args.path.as_deref(), args.query.as_deref()).await?;
+
+    if crate::dry_run() {
+        println!(
+            "Would scroll {} at {} into view",
+            node.description,
+            node.location()
+        );
+        return Ok(());
+    }
+    let scrolled = match args.point {
+        Some((x, y)) => component.scroll_to_point(CoordType::Window, x, y).await?,
+        None => component.scroll_to(args.align).await?,
+    };
+    if !scrolled {
+        return Err(format!(
+            "{} at {} could not be scrolled into view",
+            node.description,
+            node.location()
+        )
+        .into());
+    }
+
+    let (x, y, width, height) = component.get_extents(CoordType::Screen).await?;
+    println!(
+        "Scrolled {} at {} into view, it is now at ({x}, {y}), {width}x{height} on screen",
+        node.description,
+        node.location()
+    );
+    Ok(())
+}
+
+pub async fn click(a11y: &AccessibilityConnection, args: ClickArgs) -> Result<()> {
+    if !(1..=5).contains(&args.button) {
+        return Err(format!("There is no mouse button {}, give 1 to 5", args.button).into());
+    }
+
+    let conn = a11y.connection();
+    let (node, component) =
+        component(conn, &args.app, args.path.as_deref(), args.query.as_deref()).await?;
+
+    let (x, y, width, height) = component.get_extents(CoordType::Screen).await?;
+    if width <= 0 || height <= 0 {
+        return Err(format!(
+            "{} at {} has no extents on screen, try scroll-to first",
+            node.description,
+            node.location()
+        )
+        .into());
+    }
+    let (center_x, center_y) = (x + width / 2, y + height / 2);
+
+    // Event names are `b<button>` followed by `c` for a click or `d` for a double click.
+    let kind = if args.double { 'd' } else { 'c' };
+    let event_name = format!("b{}{kind}", args.button);
+    if crate::dry_run() {
+        println!(
+            "Would {} {} at {} at ({center_x}, {center_y}) on screen with button {}",
+            if args.double { "double-click" } else { "click" },
+            node.description,
+            node.location(),
+            args.button
+        );
+        return Ok(());
+    }
+    let controller = DeviceEventControllerProxy::new(conn).await?;
+    controller
+        .generate_mouse_event(center_x, center_y, &event_name)
+        .await
+        .map_err(|e| format!("Could not generate the click: {e}"))?;
+
+    println!(
+        "Clicked {} at {} at ({center_x}, {center_y}) on screen",
+        node.description,
+        node.location()
+    );
+    Ok(())
+}
@@ -0,0 +1,223 @@
+//! The user's defaults for the command line, from `~/.config/a11y-app/config.toml`, e.g.
+//!
+//! ```toml
+//! # How names that match applications only loosely are treated: ask, yes, first or no-input.
+//! matching = "first"
+//! # The output format of audit and diff: text or json.
+//! format = "json"
+//! # Whether to color diffs printed to a terminal.
+//! color = false
+//! # Whether to wait for 'Enter' before printing trees.
+//! pause = false
+//! # Milliseconds wait-for waits before failing, without its --timeout.
+//! wait-timeout = 10000
+//! # Roles of the nodes left out of traversed trees, with everything below them.
+//! skip-roles = ["table cell"]
+//! # How many calls to make at once for the children of a node.
+//! concurrency = 8
+//!
+//! # Queries to give as `--query @<name>`.
+//! [queries]
+//! save = 'push button "Save"'
+//!
+//! # The rules configuration of audit, as in `a11y-audit.toml`.
+//! [audit]
+//! max-nodes = 10000
+//! ```
+//!
+//! Environment variables, e.g. `A11Y_APP_FORMAT=json`, win over the configuration file, for
+//! containers and CI, and options given on the command line win over both.
+
+use crate::{
+    audit::{self, AuditConfig, Format},
+    snapshot, Result,
+};
+use atspi::Role;
+use serde::{de::Error, Deserialize, Deserializer};
+use std::{collections::HashMap, path::PathBuf, sync::OnceLock};
+use tracing::info;
+
+/// The configuration of the run, set once by `load`.
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// Where the configuration is, below the configuration directory.
+const CONFIG_FILE: &str = "a11y-app/config.toml";
+
+/// The prefix of the environment variables that override the configuration.
+const ENV_PREFIX: &str = "A11Y_APP_";
+
+#[derive(Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub(crate) struct Config {
+    pub(crate) matching: Option<Matching>,
+    pub(crate) format: Option<Format>,
+    pub(crate) color: Option<bool>,
+    pub(crate) pause: Option<bool>,
+    pub(crate) wait_timeout: Option<u64>,
+    #[serde(deserialize_with = "deserialize_roles")]
+    pub(crate) skip_roles: Vec<Role>,
+    pub(crate) concurrency: Option<usize>,
+    pub(crate) queries: HashMap<String, String>,
+    pub(crate) audit: Option<AuditConfig>,
+}
+
+/// How names that match applications only loosely are treated, as `--yes`, `--first`
+/// and `--no-input` say.
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Matching {
+    Ask,
+    Yes,
+    First,
+    NoInput,
+}
+
+impl Matching {
+    fn parse(value: &str) -> Option<Matching> {
+        match value {
+            "ask" => Some(Matching::Ask),
+            "yes" => Some(Matching::Yes),
+            "first" => Some(Matching::First),
+            "no-input" => Some(Matching::NoInput),
+            _ => None,
+        }
+    }
+}
+
+/// Roles by their names, e.g. `table cell`.
+fn deserialize_roles<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Vec<Role>, D::Error> {
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|name| {
+            snapshot::role_from_name(name)
+                .ok_or_else(|| D::Error::custom(format!("unknown role: {name}")))
+        })
+        .collect()
+}
+
+/// The configuration file: in `$XDG_CONFIG_HOME`, or else `~/.config`.
+fn path() -> Option<PathBuf> {
+    let dir = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(dir.join(CONFIG_FILE))
+}
+
+/// Read the configuration file, if there is one, for the rest of the run.
+pub(crate) fn load() -> Result<()> {
+    let mut config = match path().filter(|path| path.is_file()) {
+        Some(path) => {
+            info!("Reading the configuration {}", path.display());
+            let contents = std::fs::read_to_string(&path)
+                .map_err(|e| format!("Could not read {}: {e}", path.display()))?;
+            let mut config: Config = toml::from_str(&contents)
+                .map_err(|e| format!("Invalid configuration {}: {e}", path.display()))?;
+            // Plugins are relative to the configuration file, as in a rules configuration.
+            if let Some(audit) = &mut config.audit {
+                let dir = path.parent().unwrap_or(&path);
+                audit.plugins = audit
+                    .plugins
+                    .iter()
+                    .map(|plugin| dir.join(plugin))
+                    .collect();
+            }
+            config
+        }
+        None => Config::default(),
+    };
+    config.apply_environment()?;
+    let _ = CONFIG.set(config);
+    Ok(())
+}
+
+impl Config {
+    /// Override the configuration with the `A11Y_APP_*` environment variables that are set.
+    fn apply_environment(&mut self) -> Result<()> {
+        if let Some(value) = variable("MATCHING") {
+            self.matching = Some(
+                Matching::parse(&value)
+                    .ok_or_else(|| invalid("MATCHING", &value, "ask, yes, first or no-input"))?,
+            );
+        }
+        if let Some(value) = variable("NO_INPUT") {
+            if parse_bool(&value).ok_or_else(|| invalid("NO_INPUT", &value, "1 or 0"))? {
+                self.matching = Some(Matching::NoInput);
+            }
+        }
+        if let Some(value) = variable("FORMAT") {
+            self.format = Some(
+                audit::parse_format(&value)
+                    .map_err(|_| invalid("FORMAT", &value, "text or json"))?,
+            );
+        }
+        if let Some(value) = variable("COLOR") {
+            self.color =
+                Some(parse_bool(&value).ok_or_else(|| invalid("COLOR", &value, "1 or 0"))?);
+        }
+        if let Some(value) = variable("PAUSE") {
+            self.pause =
+                Some(parse_bool(&value).ok_or_else(|| invalid("PAUSE", &value, "1 or 0"))?);
+        }
+        if let Some(value) = variable("WAIT_TIMEOUT") {
+            self.wait_timeout = Some(
+                value
+                    .parse()
+                    .map_err(|_| invalid("WAIT_TIMEOUT", &value, "milliseconds"))?,
+            );
+        }
+        if let Some(value) = variable("SKIP_ROLES") {
+            self.skip_roles = value
+                .split(',')
+                .map(|name| {
+                    snapshot::role_from_name(name.trim()).ok_or_else(|| {
+                        invalid("SKIP_ROLES", &value, "role names separated by commas")
+                    })
+                })
+                .collect::<std::result::Result<_, _>>()?;
+        }
+        if let Some(value) = variable("CONCURRENCY") {
+            self.concurrency = Some(
+                value
+                    .parse()
+                    .map_err(|_| invalid("CONCURRENCY", &value, "a number of calls"))?,
+            );
+        }
+        Ok(())
+    }
+}
+
+/// The value of the environment variable `A11Y_APP_<name>`, if it is set and not empty.
+fn variable(name: &str) -> Option<String> {
+    std::env::var(format!("{ENV_PREFIX}{name}"))
+        .ok()
+        .filter(|value| !value.is_empty())
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.to_lowercase().as_str() {
+        "1" | "true" | "yes" => Some(true),
+        "0" | "false" | "no" => Some(false),
+        _ => None,
+    }
+}
+
+fn invalid(name: &str, value: &str, expected: &str) -> String {
+    format!("Invalid {ENV_PREFIX}{name}: {value}, expected {expected}")
+}
+
+/// The configuration of the run, the defaults if none was loaded.
+pub(crate) fn get() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+/// The query saved as `name` in the configuration, for `@<name>`.
+pub(crate) fn query(name: &str) -> Result<&'static str> {
+    get()
+        .queries
+        .get(name)
+        .map(String::as_str)
+        .ok_or_else(|| format!("There is no query {name:?} in the configuration").into())
+}
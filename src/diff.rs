@@ -0,0 +1,704 @@
+//! Comparing two trees: which nodes were added, removed or moved, and which properties changed.
+//!
+//! Children are matched to the children of the same node in the other tree by their
+//! accessible id, or else their role and name, keeping their order where possible.
+//! Children that are left are matched by role, as the same node with another name.
+//! Subtrees that were removed in one place and added unchanged in another were moved.
+
+use crate::{
+    audit::{location, parse_format, Format},
+    config,
+    diff_ignore::Ignore,
+    snapshot::{self, child_path, ParentLink, Snapshot, SnapshotNode},
+    Result,
+};
+use argh::{ArgsInfo, FromArgs};
+use atspi::AccessibilityConnection;
+use serde::Serialize;
+use std::{collections::BTreeMap, path::PathBuf};
+
+/// Above this many pairs of children to compare, children are matched in a single pass.
+const MAX_ALIGNMENT: usize = 4_000_000;
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const CYAN: &str = "\x1b[36m";
+const RESET: &str = "\x1b[0m";
+
+/// Compare two snapshots, or a running application to a snapshot, and list the added,
+/// removed, moved and changed nodes
+#[derive(FromArgs, ArgsInfo)]
+#[argh(subcommand, name = "diff")]
+pub struct DiffArgs {
+    /// the earlier snapshot, or the baseline with `--live`
+    #[argh(positional)]
+    old: PathBuf,
+
+    /// the later snapshot, left out with `--live`
+    #[argh(positional)]
+    new: Option<PathBuf>,
+
+    /// compare the running application, by bus name or application name, to the baseline
+    #[argh(option)]
+    live: Option<String>,
+
+    /// do not color the output, which is colored when printing to a terminal
+    #[argh(switch)]
+    no_color: bool,
+
+    /// do not compare the names of nodes matching this query, e.g. `label` (repeatable)
+    #[argh(option)]
+    ignore_name: Vec<String>,
+
+    /// do not compare nodes matching this query and the nodes below them,
+    /// e.g. `panel "Notifications"` (repeatable)
+    #[argh(option)]
+    ignore_subtree: Vec<String>,
+
+    /// do not compare this property of any node, e.g. `geometry` or `attribute tooltip`
+    /// (repeatable)
+    #[argh(option)]
+    ignore_property: Vec<String>,
+
+    /// read more ignore rules from this TOML file
+    #[argh(option)]
+    ignore_file: Option<PathBuf>,
+
+    /// the output format: text, or json for a list of patch operations on node paths
+    /// for other tools (default: text)
+    #[argh(option, from_str_fn(parse_format))]
+    format: Option<Format>,
+}
+
+/// A difference between two trees. Paths are index paths from the application's root,
+/// in the earlier tree for removed nodes and in the later tree otherwise.
+#[derive(Clone, PartialEq)]
+pub(crate) enum Change {
+    Added {
+        path: String,
+        node: String,
+        /// The number of nodes in the added subtree.
+        nodes: usize,
+    },
+    Removed {
+        path: String,
+        node: String,
+        nodes: usize,
+    },
+    Moved {
+        from: String,
+        to: String,
+        node: String,
+    },
+    Changed {
+        path: String,
+        node: String,
+        /// E.g. `name`, `states` or `attribute tooltip`.
+        property: String,
+        /// `None` when the property was not there.
+        old: Option<String>,
+        new: Option<String>,
+    },
+}
+
+impl DiffArgs {
+    /// Whether a running application is compared, which needs the accessibility bus.
+    pub fn is_live(&self) -> bool {
+        self.live.is_some()
+    }
+
+    fn ignore(&self) -> Result<Ignore> {
+        Ignore::from_options(
+            &self.ignore_name,
+            &self.ignore_subtree,
+            &self.ignore_property,
+            self.ignore_file.as_deref(),
+        )
+    }
+
+    fn print(&self, old: &Snapshot, new: &Snapshot, changes: &[Change]) -> Result<()> {
+        match self.format.or(config::get().format).unwrap_or(Format::Text) {
+            Format::Text => {
+                if let (Some(old), Some(new)) = (&old.metadata, &new.metadata) {
+                    let differences = old.differences(new);
+                    if !differences.is_empty() {
+                        println!("The snapshots were taken in different environments:");
+                        for difference in differences {
+                            println!("  {difference}");
+                        }
+                        println!();
+                    }
+                }
+                let color = crate::color(self.no_color);
+                print_changes(changes, color);
+            }
+            Format::Json => {
+                let operations: Vec<Operation> = changes.iter().map(Operation::from).collect();
+                println!("{}", serde_json::to_string_pretty(&operations)?);
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn diff(args: DiffArgs) -> Result<()> {
+    let Some(new) = &args.new else {
+        return Err("Give the later snapshot, or an application with --live".into());
+    };
+    let old = snapshot::load(&args.old)?;
+    let new = snapshot::load(new)?;
+    let changes = args.ignore()?.diff(&old, &new);
+    args.print(&old, &new, &changes)
+}
+
+/// Compare the running application to the baseline, at the node the baseline was taken of.
+pub async fn diff_live(a11y: &AccessibilityConnection, args: DiffArgs) -> Result<()> {
+    let Some(app) = &args.live else {
+        return diff(args);
+    };
+    if args.new.is_some() {
+        return Err("With --live, give only the baseline snapshot".into());
+    }
+    let old = snapshot::load(&args.old)?;
+    let new = snapshot::capture(a11y, app, Some(&old.path), None, &old.detail).await?;
+    let changes = args.ignore()?.diff(&old, &new);
+    args.print(&old, &new, &changes)
+}
+
+/// The differences between two snapshots, in what both hold of their nodes.
+pub(crate) fn diff_snapshots(old: &Snapshot, new: &Snapshot) -> Vec<Change> {
+    if old.detail != new.detail {
+        let old = old.with_detail(&new.detail);
+        let new = new.with_detail(&old.detail);
+        return diff_trees(&old.root, &old.path, &new.root, &new.path);
+    }
+    diff_trees(&old.root, &old.path, &new.root, &new.path)
+}
+
+/// The differences between the tree below `old` and the tree below `new`.
+pub(crate) fn diff_trees(
+    old: &SnapshotNode,
+    old_path: &str,
+    new: &SnapshotNode,
+    new_path: &str,
+) -> Vec<Change> {
+    let mut diff = TreeDiff::default();
+    diff.node(old, old_path, new, new_path);
+
+    // A subtree removed in one place and added in another was moved.
+    let mut changes = diff.changes;
+    let mut removed = diff.removed;
+    let mut added = Vec::new();
+    for (node, path) in diff.added {
+        match removed.iter().position(|(old, _)| *old == node) {
+            Some(position) => {
+                let (_, from) = removed.remove(position);
+                changes.push(Change::Moved {
+                    from,
+                    to: path,
+                    node: node.description(),
+                });
+            }
+            None => added.push(Change::Added {
+                path,
+                node: node.description(),
+                nodes: node.count(),
+            }),
+        }
+    }
+    changes.extend(removed.into_iter().map(|(node, path)| Change::Removed {
+        path,
+        node: node.description(),
+        nodes: node.count(),
+    }));
+    changes.extend(added);
+    changes
+}
+
+#[derive(Default)]
+struct TreeDiff<'a> {
+    changes: Vec<Change>,
+    removed: Vec<(&'a SnapshotNode, String)>,
+    added: Vec<(&'a SnapshotNode, String)>,
+}
+
+impl<'a> TreeDiff<'a> {
+    fn node(
+        &mut self,
+        old: &'a SnapshotNode,
+        old_path: &str,
+        new: &'a SnapshotNode,
+        new_path: &str,
+    ) {
+        self.properties(old, new, new_path);
+
+        let matching = match_children(&old.children, &new.children);
+        for (old_index, new_index, reordered) in matching.pairs {
+            let old_child_path = child_path(old_path, old_index);
+            let new_child_path = child_path(new_path, new_index);
+            if reordered {
+                self.changes.push(Change::Moved {
+                    from: old_child_path.clone(),
+                    to: new_child_path.clone(),
+                    node: new.children[new_index].description(),
+                });
+            }
+            self.node(
+                &old.children[old_index],
+                &old_child_path,
+                &new.children[new_index],
+                &new_child_path,
+            );
+        }
+        for index in matching.removed {
+            self.removed
+                .push((&old.children[index], child_path(old_path, index)));
+        }
+        for index in matching.added {
+            self.added
+                .push((&new.children[index], child_path(new_path, index)));
+        }
+    }
+
+    fn properties(&mut self, old: &SnapshotNode, new: &SnapshotNode, path: &str) {
+        let node = new.description();
+        let mut change = |property: &str, old: Option<String>, new: Option<String>| {
+            if old != new {
+                self.changes.push(Change::Changed {
+                    path: path.to_string(),
+                    node: node.clone(),
+                    property: property.to_string(),
+                    old,
+                    new,
+                });
+            }
+        };
+        let text = |value: &str| (!value.is_empty()).then(|| value.to_string());
+
+        change("role", text(&old.role), text(&new.role));
+        change("name", text(&old.name), text(&new.name));
+        change(
+            "description",
+            text(&old.description),
+            text(&new.description),
+        );
+        change(
+            "accessible id",
+            text(&old.accessible_id),
+            text(&new.accessible_id),
+        );
+        change("locale", text(&old.locale), text(&new.locale));
+        if old.states != new.states {
+            change(
+                "states",
+                Some(old.states.join(", ")),
+                Some(new.states.join(", ")),
+            );
+        }
+        for (name, old_value, new_value) in zip_maps(&old.attributes, &new.attributes) {
+            change(
+                &format!("attribute {name}"),
+                old_value.cloned(),
+                new_value.cloned(),
+            );
+        }
+        for (name, old_targets, new_targets) in zip_maps(&old.relations, &new.relations) {
+            change(
+                &format!("relation {name}"),
+                old_targets.map(|targets| targets.join(", ")),
+                new_targets.map(|targets| targets.join(", ")),
+            );
+        }
+        if old.table != new.table {
+            let describe = |node: &SnapshotNode| {
+                node.table.as_ref().map(|table| {
+                    format!(
+                        "{} rows, {} columns, {} column headers, {} row headers",
+                        table.rows, table.columns, table.column_headers, table.row_headers
+                    )
+                })
+            };
+            change("table", describe(old), describe(new));
+        }
+        if old.reported_parent != new.reported_parent {
+            let describe =
+                |node: &SnapshotNode| node.reported_parent.as_ref().map(ParentLink::description);
+            change("parent", describe(old), describe(new));
+        }
+        change(
+            "index in parent",
+            old.index_in_parent.map(|index| index.to_string()),
+            new.index_in_parent.map(|index| index.to_string()),
+        );
+        let extents = |node: &SnapshotNode| {
+            node.extents.map(|extents| {
+                format!(
+                    "{}x{} at {},{}",
+                    extents.width, extents.height, extents.x, extents.y
+                )
+            })
+        };
+        change("geometry", extents(old), extents(new));
+        change("text", old.text.clone(), new.text.clone());
+    }
+}
+
+/// A change as an operation in the manner of JSON Patch (RFC 6902), on JSON Pointer paths
+/// of nodes by their child indices, e.g. `/0/2/1`, and of their properties, e.g.
+/// `/0/2/1/name` or `/0/2/1/attributes/tooltip`.
+#[derive(Serialize)]
+struct Operation {
+    /// `add`, `remove`, `move` or `replace`.
+    op: &'static str,
+    path: String,
+    /// Where a moved node was.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    from: Option<String>,
+    /// The new value of an added or replaced property.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    value: Option<String>,
+    /// The value of a removed or replaced property, which JSON Patch leaves out.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    old: Option<String>,
+    /// Role and name of the node.
+    node: String,
+    /// The number of nodes in an added or removed subtree.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    nodes: Option<usize>,
+}
+
+impl From<&Change> for Operation {
+    fn from(change: &Change) -> Operation {
+        let operation = |op, path: &str, node: &str| Operation {
+            op,
+            path: pointer(path, &[]),
+            from: None,
+            value: None,
+            old: None,
+            node: node.to_string(),
+            nodes: None,
+        };
+        match change {
+            Change::Added { path, node, nodes } => Operation {
+                nodes: Some(*nodes),
+                ..operation("add", path, node)
+            },
+            Change::Removed { path, node, nodes } => Operation {
+                nodes: Some(*nodes),
+                ..operation("remove", path, node)
+            },
+            Change::Moved { from, to, node } => Operation {
+                from: Some(pointer(from, &[])),
+                ..operation("move", to, node)
+            },
+            Change::Changed {
+                path,
+                node,
+                property,
+                old,
+                new,
+            } => {
+                // E.g. `attribute tooltip` is at `attributes/tooltip`, `accessible id`
+                // at `accessible_id`.
+                let property = match property.split_once(' ') {
+                    Some(("attribute", name)) => vec!["attributes".to_string(), name.to_string()],
+                    Some(("relation", name)) => vec!["relations".to_string(), name.to_string()],
+                    _ => vec![property.replace(' ', "_")],
+                };
+                let op = match (old, new) {
+                    (None, _) => "add",
+                    (_, None) => "remove",
+                    _ => "replace",
+                };
+                Operation {
+                    path: pointer(path, &property),
+                    value: new.clone(),
+                    old: old.clone(),
+                    ..operation(op, path, node)
+                }
+            }
+        }
+    }
+}
+
+/// The JSON Pointer of a node's index path and the segments after it.
+fn pointer(path: &str, segments: &[String]) -> String {
+    path.split('/')
+        .filter(|index| !index.is_empty())
+        .chain(segments.iter().map(String::as_str))
+        .map(|segment| format!("/{}", segment.replace('~', "~0").replace('/', "~1")))
+        .collect()
+}
+
+/// How the children of a node in two trees correspond: pairs of old and new indices,
+/// and whether the pair is out of order, and the children only in either tree.
+struct Matching {
+    pairs: Vec<(usize, usize, bool)>,
+    removed: Vec<usize>,
+    added: Vec<usize>,
+}
+
+/// What identifies a node among its siblings.
+fn key(node: &SnapshotNode) -> (&str, &str, &str) {
+    if node.accessible_id.is_empty() {
+        (&node.role, "", &node.name)
+    } else {
+        (&node.role, &node.accessible_id, "")
+    }
+}
+
+fn match_children(old: &[SnapshotNode], new: &[SnapshotNode]) -> Matching {
+    let old_keys: Vec<_> = old.iter().map(key).collect();
+    let new_keys: Vec<_> = new.iter().map(key).collect();
+    let mut old_matched = vec![false; old.len()];
+    let mut new_matched = vec![false; new.len()];
+    let mut pairs = Vec::new();
+
+    // In order, by key.
+    for (o, n) in align(&old_keys, &new_keys) {
+        old_matched[o] = true;
+        new_matched[n] = true;
+        pairs.push((o, n, false));
+    }
+    // Out of order, by key, then in order by role for nodes that changed their name.
+    for by_role in [false, true] {
+        for o in 0..old.len() {
+            if old_matched[o] {
+                continue;
+            }
+            let found = (0..new.len()).find(|n| {
+                !new_matched[*n]
+                    && if by_role {
+                        old[o].role == new[*n].role
+                    } else {
+                        old_keys[o] == new_keys[*n]
+                    }
+            });
+            if let Some(n) = found {
+                old_matched[o] = true;
+                new_matched[n] = true;
+                pairs.push((o, n, !by_role));
+            }
+        }
+    }
+
+    pairs.sort_by_key(|(_, n, _)| *n);
+    Matching {
+        pairs,
+        removed: (0..old.len()).filter(|o| !old_matched[*o]).collect(),
+        added: (0..new.len()).filter(|n| !new_matched[*n]).collect(),
+    }
+}
+
+/// The longest sequence of equal keys in the same order in both lists, as index pairs.
+fn align<T: PartialEq>(old: &[T], new: &[T]) -> Vec<(usize, usize)> {
+    if old.len() * new.len() > MAX_ALIGNMENT {
+        // Too many to align; match each to the next equal one.
+        let mut pairs = Vec::new();
+        let mut next = 0;
+        for (o, key) in old.iter().enumerate() {
+            if let Some(n) = (next..new.len()).find(|n| new[*n] == *key) {
+                pairs.push((o, n));
+                next = n + 1;
+            }
+        }
+        return pairs;
+    }
+
+    // lengths[o][n] is the length of the longest common sequence of old[o..] and new[n..].
+    let mut lengths = vec![vec![0u32; new.len() + 1]; old.len() + 1];
+    for o in (0..old.len()).rev() {
+        for n in (0..new.len()).rev() {
+            lengths[o][n] = if old[o] == new[n] {
+                lengths[o + 1][n + 1] + 1
+            } else {
+                lengths[o + 1][n].max(lengths[o][n + 1])
+            };
+        }
+    }
+    let (mut o, mut n) = (0, 0);
+    let mut pairs = Vec::new();
+    while o < old.len() && n < new.len() {
+        if old[o] == new[n] {
+            pairs.push((o, n));
+            o += 1;
+            n += 1;
+        } else if lengths[o + 1][n] >= lengths[o][n + 1] {
+            o += 1;
+        } else {
+            n += 1;
+        }
+    }
+    pairs
+}
+
+/// The keys of both maps, with the value in either.
+fn zip_maps<'a, V>(
+    old: &'a BTreeMap<String, V>,
+    new: &'a BTreeMap<String, V>,
+) -> Vec<(&'a str, Option<&'a V>, Option<&'a V>)> {
+    let mut names: Vec<&str> = old.keys().chain(new.keys()).map(String::as_str).collect();
+    names.sort_unstable();
+    names.dedup();
+    names
+        .into_iter()
+        .map(|name| (name, old.get(name), new.get(name)))
+        .collect()
+}
+
+pub(crate) fn print_changes(changes: &[Change], color: bool) {
+    for line in change_lines(changes, color) {
+        println!("{line}");
+    }
+}
+
+/// A line for each change, colored with `color`, and a line summing them up.
+pub(crate) fn change_lines(changes: &[Change], color: bool) -> Vec<String> {
+    let paint = |code: &str, text: String| {
+        if color {
+            format!("{code}{text}{RESET}")
+        } else {
+            text
+        }
+    };
+
+    let mut lines = Vec::with_capacity(changes.len() + 1);
+    let mut counts = [0; 4];
+    for change in changes {
+        let line = match change {
+            Change::Added { path, node, nodes } => {
+                counts[0] += 1;
+                let below = match nodes {
+                    1 => String::new(),
+                    nodes => format!(" ({nodes} nodes)"),
+                };
+                paint(GREEN, format!("+ {node} at {}{below}", location(path)))
+            }
+            Change::Removed { path, node, nodes } => {
+                counts[1] += 1;
+                let below = match nodes {
+                    1 => String::new(),
+                    nodes => format!(" ({nodes} nodes)"),
+                };
+                paint(RED, format!("- {node} at {}{below}", location(path)))
+            }
+            Change::Moved { from, to, node } => {
+                counts[2] += 1;
+                paint(
+                    CYAN,
+                    format!("> {node} moved from {} to {}", location(from), location(to)),
+                )
+            }
+            Change::Changed {
+                path,
+                node,
+                property,
+                old,
+                new,
+            } => {
+                counts[3] += 1;
+                let value = |value: &Option<String>| match value {
+                    Some(value) => format!("{value:?}"),
+                    None => "(none)".to_string(),
+                };
+                paint(
+                    YELLOW,
+                    format!(
+                        "~ {node} at {}: {property} {} -> {}",
+                        location(path),
+                        value(old),
+                        value(new)
+                    ),
+                )
+            }
+        };
+        lines.push(line);
+    }
+
+    if changes.is_empty() {
+        lines.push("No differences".to_string());
+    } else {
+        lines.push(format!(
+            "{} added, {} removed, {} moved, {} changed",
+            counts[0], counts[1], counts[2], counts[3]
+        ));
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// A node with a role, a name and children.
+    fn node(role: &str, name: &str, children: Vec<SnapshotNode>) -> SnapshotNode {
+        serde_json::from_value(json!({ "role": role, "name": name, "children": children })).unwrap()
+    }
+
+    fn button(name: &str) -> SnapshotNode {
+        node("push button", name, Vec::new())
+    }
+
+    #[test]
+    fn reordered_children_are_paired_out_of_order() {
+        let old = [button("A"), button("B"), button("C")];
+        let new = [button("C"), button("A"), button("B")];
+        let matching = match_children(&old, &new);
+        assert_eq!(matching.pairs, [(2, 0, true), (0, 1, false), (1, 2, false)]);
+        assert!(matching.removed.is_empty());
+        assert!(matching.added.is_empty());
+    }
+
+    #[test]
+    fn renamed_child_is_paired_by_role() {
+        let old = node("panel", "", vec![button("Save")]);
+        let new = node("panel", "", vec![button("Store")]);
+        let matching = match_children(&old.children, &new.children);
+        assert_eq!(matching.pairs, [(0, 0, false)]);
+
+        let changes = diff_trees(&old, "", &new, "");
+        assert_eq!(changes.len(), 1);
+        assert!(matches!(
+            &changes[0],
+            Change::Changed { path, property, old, new, .. }
+                if path == "0"
+                    && property == "name"
+                    && old.as_deref() == Some("Save")
+                    && new.as_deref() == Some("Store")
+        ));
+    }
+
+    #[test]
+    fn subtree_removed_and_added_elsewhere_is_moved() {
+        let moved = node("panel", "P", vec![node("label", "x", Vec::new())]);
+        let old = node(
+            "frame",
+            "",
+            vec![moved.clone(), node("panel", "Q", Vec::new())],
+        );
+        let new = node("frame", "", vec![node("panel", "Q", vec![moved])]);
+
+        let changes = diff_trees(&old, "", &new, "");
+        assert_eq!(changes.len(), 1);
+        assert!(matches!(
+            &changes[0],
+            Change::Moved { from, to, .. } if from == "0" && to == "0/0"
+        ));
+    }
+
+    #[test]
+    fn long_lists_are_aligned_greedily() {
+        // The first two are swapped; the longest common sequence would keep the second.
+        let filler = 2..=MAX_ALIGNMENT.isqrt();
+        let old: Vec<usize> = [1, 0].into_iter().chain(filler.clone()).collect();
+        let new: Vec<usize> = [0, 1].into_iter().chain(filler).collect();
+        assert!(old.len() * new.len() > MAX_ALIGNMENT);
+
+        let pairs = align(&old, &new);
+        assert_eq!(pairs[0], (0, 1));
+        assert_eq!(pairs.len(), old.len() - 1);
+        assert!(pairs[1..].iter().all(|(o, n)| o == n));
+    }
+}
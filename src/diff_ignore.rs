@@ -0,0 +1,246 @@
+//! Leaving volatile content out of comparisons, such as clocks, counters and notification
+//! text, so they do not show up as differences on every run.
+//!
+//! Besides the `--ignore-*` options, ignore rules can come from a TOML file:
+//!
+//! ```toml
+//! # The names of labels in the status bar.
+//! [[ignore]]
+//! query = 'label'
+//! properties = ["name"]
+//!
+//! # Notifications, and everything below them.
+//! [[ignore]]
+//! query = 'notification'
+//!
+//! # Where any node is.
+//! [[ignore]]
+//! properties = ["geometry"]
+//! ```
+//!
+//! A rule without `query` applies to all nodes, and one without `properties` leaves out
+//! the matching nodes with the nodes below them.
+
+use crate::{
+    diff::{self, Change},
+    snapshot::{child_path, Snapshot, SnapshotNode},
+    target::Query,
+    Result,
+};
+use serde::Deserialize;
+use std::path::Path;
+
+/// The properties that can be ignored, by the names `diff` shows them with. Single
+/// attributes and relations are ignored as e.g. `attribute tooltip`.
+const PROPERTIES: &[&str] = &[
+    "name",
+    "description",
+    "accessible id",
+    "locale",
+    "states",
+    "attributes",
+    "relations",
+    "table",
+    "parent",
+    "index in parent",
+    "geometry",
+    "text",
+];
+
+/// An ignore rules file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct IgnoreFile {
+    #[serde(default)]
+    ignore: Vec<RuleEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleEntry {
+    query: Option<String>,
+    #[serde(default)]
+    properties: Vec<String>,
+}
+
+/// What to leave out when comparing two snapshots.
+#[derive(Default)]
+pub(crate) struct Ignore {
+    rules: Vec<Rule>,
+}
+
+struct Rule {
+    /// The nodes the rule is about, all if `None`.
+    query: Option<Query>,
+    /// The properties to leave out, or the nodes with the nodes below them if empty.
+    properties: Vec<String>,
+}
+
+impl Ignore {
+    /// The rules of the `--ignore-*` options of a command.
+    pub(crate) fn from_options(
+        names: &[String],
+        subtrees: &[String],
+        properties: &[String],
+        file: Option<&Path>,
+    ) -> Result<Ignore> {
+        let mut ignore = match file {
+            Some(path) => Ignore::load(path)?,
+            None => Ignore::default(),
+        };
+        for query in names {
+            ignore.add(Some(query), vec!["name".to_string()])?;
+        }
+        for query in subtrees {
+            ignore.add(Some(query), Vec::new())?;
+        }
+        if !properties.is_empty() {
+            ignore.add(None, properties.to_vec())?;
+        }
+        Ok(ignore)
+    }
+
+    pub(crate) fn load(path: &Path) -> Result<Ignore> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Could not read {}: {e}", path.display()))?;
+        let file: IgnoreFile = toml::from_str(&contents)
+            .map_err(|e| format!("Invalid ignore rules {}: {e}", path.display()))?;
+
+        let mut ignore = Ignore::default();
+        for entry in file.ignore {
+            ignore
+                .add(entry.query.as_deref(), entry.properties)
+                .map_err(|e| format!("Invalid ignore rules {}: {e}", path.display()))?;
+        }
+        Ok(ignore)
+    }
+
+    /// Leave out `properties` of the nodes matching `query`, or all of those nodes
+    /// without properties.
+    pub(crate) fn add(&mut self, query: Option<&str>, properties: Vec<String>) -> Result<()> {
+        if let Some(property) = properties.iter().find(|property| !known(property)) {
+            return Err(format!(
+                "Unknown property {property:?} to ignore, expected one of {}, \
+                 or `attribute <name>` or `relation <name>`",
+                PROPERTIES.join(", ")
+            )
+            .into());
+        }
+        if query.is_none() && properties.is_empty() {
+            return Err("An ignore rule without a query needs properties".into());
+        }
+        self.rules.push(Rule {
+            query: query.map(Query::parse).transpose()?,
+            properties,
+        });
+        Ok(())
+    }
+
+    /// The differences between two snapshots, without what is ignored.
+    pub(crate) fn diff(&self, old: &Snapshot, new: &Snapshot) -> Vec<Change> {
+        if self.rules.is_empty() {
+            return diff::diff_snapshots(old, new);
+        }
+
+        let mut ignored_old = Vec::new();
+        let mut ignored_new = Vec::new();
+        self.subtrees(&old.root, &old.path, &mut ignored_old);
+        self.subtrees(&new.root, &new.path, &mut ignored_new);
+
+        // Properties are forgotten before comparing, so nodes with an ignored name
+        // are still matched to each other.
+        let mut old = old.clone();
+        let mut new = new.clone();
+        self.forget(&mut old.root);
+        self.forget(&mut new.root);
+
+        let ignored =
+            |paths: &[String], path: &str| paths.iter().any(|ignored| below(path, ignored));
+        diff::diff_snapshots(&old, &new)
+            .into_iter()
+            .filter(|change| match change {
+                Change::Added { path, .. } | Change::Changed { path, .. } => {
+                    !ignored(&ignored_new, path)
+                }
+                Change::Removed { path, .. } => !ignored(&ignored_old, path),
+                Change::Moved { from, to, .. } => {
+                    !ignored(&ignored_old, from) && !ignored(&ignored_new, to)
+                }
+            })
+            .collect()
+    }
+
+    /// The rules that apply to `node`.
+    fn matching<'a>(&'a self, node: &'a SnapshotNode) -> impl Iterator<Item = &'a Rule> {
+        self.rules.iter().filter(|rule| {
+            rule.query
+                .as_ref()
+                .is_none_or(|query| query.matches_values(&node.role, &node.name))
+        })
+    }
+
+    /// The paths of the ignored subtrees at and below `node`.
+    fn subtrees(&self, node: &SnapshotNode, path: &str, paths: &mut Vec<String>) {
+        if self.matching(node).any(|rule| rule.properties.is_empty()) {
+            paths.push(path.to_string());
+            return;
+        }
+        for (index, child) in node.children.iter().enumerate() {
+            self.subtrees(child, &child_path(path, index), paths);
+        }
+    }
+
+    /// Clear the ignored properties of `node` and the nodes below it.
+    fn forget(&self, node: &mut SnapshotNode) {
+        // Matched before clearing any, as clearing the name changes what matches.
+        let properties: Vec<String> = self
+            .matching(node)
+            .flat_map(|rule| rule.properties.iter().cloned())
+            .collect();
+        for property in properties {
+            forget(node, &property);
+        }
+        for child in &mut node.children {
+            self.forget(child);
+        }
+    }
+}
+
+fn known(property: &str) -> bool {
+    PROPERTIES.contains(&property)
+        || property.starts_with("attribute ")
+        || property.starts_with("relation ")
+}
+
+fn forget(node: &mut SnapshotNode, property: &str) {
+    match property {
+        "name" => node.name.clear(),
+        "description" => node.description.clear(),
+        "accessible id" => node.accessible_id.clear(),
+        "locale" => node.locale.clear(),
+        "states" => node.states.clear(),
+        "attributes" => node.attributes.clear(),
+        "relations" => node.relations.clear(),
+        "table" => node.table = None,
+        "parent" => node.reported_parent = None,
+        "index in parent" => node.index_in_parent = None,
+        "geometry" => node.extents = None,
+        "text" => node.text = None,
+        _ => {
+            if let Some(name) = property.strip_prefix("attribute ") {
+                node.attributes.remove(name);
+            } else if let Some(name) = property.strip_prefix("relation ") {
+                node.relations.remove(name);
+            }
+        }
+    }
+}
+
+/// Whether the node at `path` is the node at `ancestor` or below it.
+fn below(path: &str, ancestor: &str) -> bool {
+    ancestor.is_empty()
+        || path == ancestor
+        || path
+            .strip_prefix(ancestor)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
@@ -0,0 +1,217 @@
+//! Changing the text of a node through the EditableText interface.
+
+use crate::{
+    target::{self, Selected},
+    Result,
+};
+use argh::{ArgsInfo, FromArgs};
+use atspi::{
+    proxy::{editable_text::EditableTextProxy, proxy_ext::ProxyExt},
+    AccessibilityConnection,
+};
+use zbus::Connection;
+
+/// Replace the text of a node
+#[derive(FromArgs, ArgsInfo)]
+#[argh(subcommand, name = "set-text")]
+pub struct SetTextArgs {
+    /// the application, by bus name or application name
+    #[argh(positional)]
+    app: String,
+
+    /// the new text
+    #[argh(positional)]
+    text: String,
+
+    /// the node, by the child indices leading to it from the application's root, e.g. `0/2/1`
+    #[argh(option)]
+    path: Option<String>,
+
+    /// the first node with this role and/or name, e.g. `text "Search"`
+    #[argh(option)]
+    query: Option<String>,
+}
+
+/// Insert text into the text of a node
+#[derive(FromArgs, ArgsInfo)]
+#[argh(subcommand, name = "insert-text")]
+pub struct InsertTextArgs {
+    /// the application, by bus name or application name
+    #[argh(positional)]
+    app: String,
+
+    /// the text to insert
+    #[argh(positional)]
+    text: String,
+
+    /// the character offset to insert at (default: the end of the text)
+    #[argh(option)]
+    position: Option<i32>,
+
+    /// the node, by the child indices leading to it from the application's root, e.g. `0/2/1`
+    #[argh(option)]
+    path: Option<String>,
+
+    /// the first node with this role and/or name, e.g. `text "Search"`
+    #[argh(option)]
+    query: Option<String>,
+}
+
+/// Delete a range of the text of a node
+#[derive(FromArgs, ArgsInfo)]
+#[argh(subcommand, name = "delete-text")]
+pub struct DeleteTextArgs {
+    /// the application, by bus name or application name
+    #[argh(positional)]
+    app: String,
+
+    /// the character offset of the first character to delete (default: 0)
+    #[argh(option, default = "0")]
+    start: i32,
+
+    /// the character offset just past the last character to delete (default: the end of the text)
+    #[argh(option)]
+    end: Option<i32>,
+
+    /// the node, by the child indices leading to it from the application's root, e.g. `0/2/1`
+    #[argh(option)]
+    path: Option<String>,
+
+    /// the first node with this role and/or name, e.g. `text "Search"`
+    #[argh(option)]
+    query: Option<String>,
+}
+
+/// Select the node and get its EditableText interface.
+async fn editable(
+    conn: &Connection,
+    app: &str,
+    path: Option<&str>,
+    query: Option<&str>,
+) -> Result<(Selected, EditableTextProxy<'static>)> {
+    let node = target::select(conn, app, path, query).await?;
+    let proxies = node.proxy.proxies().await?;
+    match proxies.editable_text().await {
+        Ok(editable_text) => Ok((node, editable_text)),
+        Err(_) => Err(format!(
+            "{} at {} does not implement the EditableText interface",
+            node.description,
+            node.location()
+        )
+        .into()),
+    }
+}
+
+/// The number of characters in the node's text.
+async fn character_count(node: &Selected) -> Result<i32> {
+    let text = node.proxy.proxies().await?.text().await?;
+    Ok(text.character_count().await?)
+}
+
+/// Print the node's text after a change, so the effect can be checked.
+async fn print_text(node: &Selected) -> Result<()> {
+    let text = node.proxy.proxies().await?.text().await?;
+    let contents = text.get_text(0, -1).await?;
+    println!(
+        "Text of {} at {} is now: {contents:?}",
+        node.description,
+        node.location()
+    );
+    Ok(())
+}
+
+pub async fn set_text(a11y: &AccessibilityConnection, args: SetTextArgs) -> Result<()> {
+    let conn = a11y.connection();
+    let node = target::select(conn, &args.app, args.path.as_deref(), args.query.as_deref()).await?;
+    set_contents(&node, &args.text).await?;
+    print_text(&node).await
+}
+
+/// Replace the text of the node.
+pub(crate) async fn set_contents(node: &Selected, text: &str) -> Result<()> {
+    let proxies = node.proxy.proxies().await?;
+    let Ok(editable_text) = proxies.editable_text().await else {
+        return Err(format!(
+            "{} at {} does not implement the EditableText interface",
+            node.description,
+            node.location()
+        )
+        .into());
+    };
+
+    if crate::dry_run() {
+        println!(
+            "Would set the text of {} at {} to {text:?}",
+            node.description,
+            node.location()
+        );
+        return Ok(());
+    }
+    if !editable_text.set_text_contents(text).await? {
+        return Err(format!("{} refused the new text", node.description).into());
+    }
+    Ok(())
+}
+
+pub async fn insert_text(a11y: &AccessibilityConnection, args: InsertTextArgs) -> Result<()> {
+    let conn = a11y.connection();
+    let (node, editable_text) =
+        editable(conn, &args.app, args.path.as_deref(), args.query.as_deref()).await?;
+
+    let position = match args.position {
+        Some(position) => position,
+        None => character_count(&node).await?,
+    };
+    // The length is in characters, not bytes.
+    let length = args.text.chars().count() as i32;
+    if crate::dry_run() {
+        println!(
+            "Would insert {:?} at offset {position} of {} at {}",
+            args.text,
+            node.description,
+            node.location()
+        );
+        return Ok(());
+    }
+
+    if !editable_text
+        .insert_text(position, &args.text, length)
+        .await?
+    {
+        return Err(format!(
+            "{} refused to insert text at offset {position}",
+            node.description
+        )
+        .into());
+    }
+    print_text(&node).await
+}
+
+pub async fn delete_text(a11y: &AccessibilityConnection, args: DeleteTextArgs) -> Result<()> {
+    let conn = a11y.connection();
+    let (node, editable_text) =
+        editable(conn, &args.app, args.path.as_deref(), args.query.as_deref()).await?;
+
+    let end = match args.end {
+        Some(end) => end,
+        None => character_count(&node).await?,
+    };
+
+    if crate::dry_run() {
+        println!(
+            "Would delete the text from offset {} to {end} of {} at {}",
+            args.start,
+            node.description,
+            node.location()
+        );
+        return Ok(());
+    }
+    if !editable_text.delete_text(args.start, end).await? {
+        return Err(format!(
+            "{} refused to delete the text from offset {} to {end}",
+            node.description, args.start
+        )
+        .into());
+    }
+    print_text(&node).await
+}
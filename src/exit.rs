@@ -0,0 +1,89 @@
+//! The exit codes of a11y-app, so scripts can tell why a run failed, e.g. retry when an
+//! application has not started yet, but report findings of an audit.
+//!
+//! Errors without a code of their own exit with 1, and argh exits with 1 when the command
+//! line is invalid.
+
+use std::{
+    fmt,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Anything else went wrong.
+pub(crate) const ERROR: u8 = 1;
+/// There is no accessibility bus to connect to.
+pub(crate) const NO_BUS: u8 = 3;
+/// No application, or no node of one, matches what was given.
+pub(crate) const NOT_FOUND: u8 = 4;
+/// Nodes of a tree could not be reached, e.g. because the application did not answer in
+/// time, so what was printed is incomplete.
+pub(crate) const INCOMPLETE: u8 = 5;
+/// `audit --fail-on` found issues of that severity or worse.
+pub(crate) const FINDINGS: u8 = 6;
+/// An assertion of `assert`, or the comparison of `verify`, failed.
+pub(crate) const ASSERTION_FAILED: u8 = 7;
+/// Ctrl-C was pressed, as shells report a process ended by SIGINT.
+pub(crate) const INTERRUPTED: u8 = 130;
+
+/// How many nodes or applications could not be reached during the run.
+static UNREACHED: AtomicUsize = AtomicUsize::new(0);
+
+/// An error that exits with its own code.
+#[derive(Debug)]
+pub(crate) struct Failure {
+    code: u8,
+    message: String,
+}
+
+impl Failure {
+    /// A failure exiting with `code`, as the error of a command.
+    pub(crate) fn boxed(code: u8, message: impl Into<String>) -> Box<dyn std::error::Error> {
+        Box::new(Failure {
+            code,
+            message: message.into(),
+        })
+    }
+
+    /// A `NOT_FOUND` failure.
+    pub(crate) fn not_found(message: impl Into<String>) -> Box<dyn std::error::Error> {
+        Failure::boxed(NOT_FOUND, message)
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Failure {}
+
+/// Note that a node or application could not be reached, so the run exits with
+/// `INCOMPLETE` even when it succeeds otherwise.
+pub(crate) fn unreached() {
+    UNREACHED.fetch_add(1, Ordering::Relaxed);
+}
+
+/// The failure of a run that succeeded otherwise: `INCOMPLETE` if something could not be
+/// reached.
+pub(crate) fn incomplete() -> Option<Box<dyn std::error::Error>> {
+    match UNREACHED.load(Ordering::Relaxed) {
+        0 => None,
+        count => Some(Failure::boxed(
+            INCOMPLETE,
+            format!("{count} nodes or applications could not be reached, the output is incomplete"),
+        )),
+    }
+}
+
+/// The code to exit with for `error`.
+pub(crate) fn code(error: &(dyn std::error::Error + 'static)) -> u8 {
+    error
+        .downcast_ref::<Failure>()
+        .map_or(ERROR, |failure| failure.code)
+}
+
+/// Exit with `code`, for failures reported already.
+pub(crate) fn exit(code: u8) -> ! {
+    std::process::exit(code.into())
+}
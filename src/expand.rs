@@ -0,0 +1,144 @@
+//! Expanding and collapsing tree items, so lazily populated tree views can be fully
+//! materialized before printing or searching their tree.
+
+use crate::{
+    actions,
+    target::{self, Selected},
+    Result,
+};
+use argh::{ArgsInfo, FromArgs};
+use atspi::{proxy::proxy_ext::ProxyExt, AccessibilityConnection, State};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// The actions that expand a node, in order of preference.
+const EXPAND_ACTIONS: &[&str] = &["expand", "expand or contract", "toggle", "activate"];
+
+/// The actions that collapse a node, in order of preference.
+const COLLAPSE_ACTIONS: &[&str] = &["collapse", "expand or contract", "toggle", "activate"];
+
+/// How long to wait for the toolkit to update the state after expanding or collapsing.
+const STATE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Expand the expandable nodes below a node, level by level, or collapse them
+#[derive(FromArgs, ArgsInfo)]
+#[argh(subcommand, name = "expand")]
+pub struct ExpandArgs {
+    /// the application, by bus name or application name
+    #[argh(positional)]
+    app: String,
+
+    /// how many levels to expand: nodes that appear by expanding are expanded in the
+    /// next level (default: 1)
+    #[argh(option, default = "1")]
+    depth: u32,
+
+    /// collapse the expanded nodes instead, deepest first
+    #[argh(switch)]
+    collapse: bool,
+
+    /// the node, by the child indices leading to it from the application's root, e.g. `0/2/1`
+    #[argh(option)]
+    path: Option<String>,
+
+    /// the first node with this role and/or name, e.g. `tree table "Files"`
+    #[argh(option)]
+    query: Option<String>,
+}
+
+pub async fn expand(a11y: &AccessibilityConnection, args: ExpandArgs) -> Result<()> {
+    let conn = a11y.connection();
+    let node = target::select(conn, &args.app, args.path.as_deref(), args.query.as_deref()).await?;
+    banner!("Node: {} at {}", node.description, node.location());
+
+    if args.collapse {
+        // Deepest first, so collapsing a parent does not take its expanded children along.
+        let nodes = target::descendants(conn, &node).await?;
+        let mut collapsed = 0;
+        for node in nodes.iter().rev() {
+            if has_states(node, &[State::Expandable, State::Expanded]).await
+                && flip(node, COLLAPSE_ACTIONS, false).await
+            {
+                collapsed += 1;
+            }
+        }
+        println!("Collapsed {collapsed} nodes");
+        return Ok(());
+    }
+
+    let mut total = 0;
+    for level in 1..=args.depth {
+        let mut expanded = 0;
+        for node in target::descendants(conn, &node).await? {
+            if has_states(&node, &[State::Expandable]).await
+                && !has_states(&node, &[State::Expanded]).await
+                && flip(&node, EXPAND_ACTIONS, true).await
+            {
+                expanded += 1;
+            }
+        }
+        println!("Level {level}: expanded {expanded} nodes");
+        total += expanded;
+        // In a dry run nothing expanded, so there are no deeper levels to find.
+        if expanded == 0 || crate::dry_run() {
+            break;
+        }
+    }
+    println!("Expanded {total} nodes");
+    Ok(())
+}
+
+async fn has_states(node: &Selected, states: &[State]) -> bool {
+    match node.proxy.get_state().await {
+        Ok(set) => states.iter().all(|state| set.contains(*state)),
+        Err(_) => false,
+    }
+}
+
+/// Perform the first of `candidates` the node offers and wait for its expanded state to
+/// become `expanded`. Failures are reported and skipped, so one node does not stop the rest.
+async fn flip(node: &Selected, candidates: &[&str], expanded: bool) -> bool {
+    let names = match node.proxy.proxies().await {
+        Ok(proxies) => match proxies.action().await {
+            Ok(action) => action.get_actions().await.unwrap_or_default(),
+            Err(_) => Vec::new(),
+        },
+        Err(_) => Vec::new(),
+    };
+    let Some(name) = candidates
+        .iter()
+        .find(|name| names.iter().any(|a| a.name.eq_ignore_ascii_case(name)))
+    else {
+        warn!(
+            "{} at {} has none of the actions {}",
+            node.description,
+            node.location(),
+            candidates.join(", ")
+        );
+        return false;
+    };
+
+    if let Err(e) = actions::perform(node, name).await {
+        warn!("{e}");
+        return false;
+    }
+    if crate::dry_run() {
+        return true;
+    }
+
+    // The toolkit updates the state after handling the action, so give it a moment.
+    let start = Instant::now();
+    while start.elapsed() < STATE_TIMEOUT {
+        if has_states(node, &[State::Expanded]).await == expanded {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    warn!(
+        "performed {name:?} on {} at {}, but it did not {}",
+        node.description,
+        node.location(),
+        if expanded { "expand" } else { "collapse" }
+    );
+    false
+}
@@ -0,0 +1,305 @@
+//! Exporting scripts, or a query, as skeleton Python tests for dogtail or pyatspi,
+//! for teams whose test harness is built on those.
+
+use crate::{
+    keys,
+    script::{self, Step},
+    target::Query,
+    watch::EventFilter,
+    ArgResult, Result,
+};
+use argh::{ArgsInfo, FromArgs};
+use atspi::State;
+use std::path::PathBuf;
+
+/// Python helpers every test gets: `wait_for` is used by both formats, the rest by pyatspi.
+const WAIT_FOR_HELPER: &str = r#"def wait_for(event_type, timeout_ms):
+    """Wait for an event of this type, failing if none arrives within timeout_ms."""
+    events = []
+    timed_out = []
+
+    def on_event(event):
+        events.append(event)
+        pyatspi.Registry.stop()
+
+    def on_timeout():
+        timed_out.append(True)
+        pyatspi.Registry.stop()
+        return False
+
+    pyatspi.Registry.registerEventListener(on_event, event_type)
+    source = GLib.timeout_add(timeout_ms, on_timeout)
+    pyatspi.Registry.start()
+    if not timed_out:
+        GLib.source_remove(source)
+    pyatspi.Registry.deregisterEventListener(on_event, event_type)
+    assert events, f"no {event_type} event within {timeout_ms} ms"
+"#;
+
+const PYATSPI_HELPERS: &str = r#"def find(root, role=None, name=None):
+    """The first node, root included, with this role and/or name."""
+
+    def matches(node):
+        return (role is None or node.getRoleName() == role) and (
+            name is None or node.name == name
+        )
+
+    node = root if matches(root) else pyatspi.findDescendant(root, matches)
+    assert node is not None, f"no node matches {role} {name!r}"
+    return node
+
+
+def do_action(node, name):
+    action = node.queryAction()
+    for index in range(action.nActions):
+        if action.getName(index) == name:
+            return action.doAction(index)
+    raise AssertionError(f"{node} has no action {name!r}")
+
+
+def press_key(keysym):
+    pyatspi.Registry.generateKeyboardEvent(keysym, None, pyatspi.KEY_SYM)
+    time.sleep(0.05)
+"#;
+
+/// Export a script, or a query, as a skeleton dogtail or pyatspi test
+#[derive(FromArgs, ArgsInfo)]
+#[argh(subcommand, name = "export")]
+pub struct ExportArgs {
+    /// the script, as run by `run` or written by `record-script`
+    #[argh(positional)]
+    script: Option<PathBuf>,
+
+    /// export finding the first node with this role and/or name instead of a script
+    #[argh(option)]
+    query: Option<String>,
+
+    /// the application, by application name (default: the script's `app`)
+    #[argh(option)]
+    app: Option<String>,
+
+    /// the test framework: dogtail or pyatspi (default: dogtail)
+    #[argh(option, from_str_fn(parse_format), default = "Format::Dogtail")]
+    format: Format,
+
+    /// write the test to this file instead of printing it
+    #[argh(option)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Dogtail,
+    Pyatspi,
+}
+
+fn parse_format(value: &str) -> ArgResult<Format> {
+    match value {
+        "dogtail" => Ok(Format::Dogtail),
+        "pyatspi" => Ok(Format::Pyatspi),
+        _ => Err(format!(
+            "Unknown format: {value}, expected dogtail or pyatspi"
+        )),
+    }
+}
+
+pub fn export(args: ExportArgs) -> Result<()> {
+    let (app, timeout, steps, test_name) = match (&args.script, &args.query) {
+        (Some(path), None) => {
+            let script = script::load(path)?;
+            let name = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_default();
+            (args.app.or(script.app), script.timeout, script.steps, name)
+        }
+        (None, Some(query)) => (
+            args.app,
+            5000,
+            vec![Step::Find(query.clone())],
+            "find".to_string(),
+        ),
+        _ => return Err("Give either a script or a --query".into()),
+    };
+    let app = app.ok_or("The script names no app, give one with --app")?;
+
+    let test = generate(args.format, &app, &test_name, timeout, &steps)?;
+    match &args.output {
+        Some(path) => std::fs::write(path, test)
+            .map_err(|e| format!("Could not write {}: {e}", path.display()))?,
+        None => print!("{test}"),
+    }
+    Ok(())
+}
+
+fn generate(
+    format: Format,
+    app: &str,
+    test_name: &str,
+    timeout: u64,
+    steps: &[Step],
+) -> Result<String> {
+    let mut lines = vec![
+        "#!/usr/bin/env python3".to_string(),
+        "# Generated by a11y-app export, adapt it to your test suite.".to_string(),
+        "import time".to_string(),
+        String::new(),
+        "import pyatspi".to_string(),
+    ];
+    if format == Format::Dogtail {
+        lines.push("from dogtail.rawinput import pressKey, typeText".to_string());
+        lines.push("from dogtail.tree import root".to_string());
+    }
+    lines.push("from gi.repository import GLib".to_string());
+    lines.push(String::new());
+    lines.push(String::new());
+    lines.push(WAIT_FOR_HELPER.to_string());
+    if format == Format::Pyatspi {
+        lines.push(String::new());
+        lines.push(PYATSPI_HELPERS.to_string());
+    }
+    lines.push(String::new());
+
+    let function = format!("test_{}", identifier(test_name));
+    lines.push(format!("def {function}():"));
+    lines.push(match format {
+        Format::Dogtail => format!("    app = root.application({})", string(app)),
+        Format::Pyatspi => format!(
+            "    app = next(a for a in pyatspi.Registry.getDesktop(0) if a is not None and a.name == {})",
+            string(app)
+        ),
+    });
+    lines.push("    node = app".to_string());
+
+    for step in steps {
+        lines.push(String::new());
+        lines.push(format!("    # {step}"));
+        for line in step_lines(format, step, timeout)? {
+            lines.push(format!("    {line}"));
+        }
+    }
+
+    lines.push(String::new());
+    lines.push(String::new());
+    lines.push("if __name__ == \"__main__\":".to_string());
+    lines.push(format!("    {function}()"));
+
+    let mut test = lines.join("\n");
+    test.push('\n');
+    Ok(test)
+}
+
+/// The Python statements for one step, acting on `node`.
+fn step_lines(format: Format, step: &Step, timeout: u64) -> Result<Vec<String>> {
+    let lines = match step {
+        Step::Find(query) => {
+            let query = Query::parse(query)?;
+            let role = query.role.as_deref().map(string);
+            let name = query.name.as_deref().map(string);
+            let mut arguments = Vec::new();
+            match format {
+                Format::Dogtail => {
+                    arguments.extend(name.map(|name| format!("name={name}")));
+                    arguments.extend(role.map(|role| format!("roleName={role}")));
+                    vec![format!("node = app.child({})", arguments.join(", "))]
+                }
+                Format::Pyatspi => {
+                    arguments.extend(role.map(|role| format!("role={role}")));
+                    arguments.extend(name.map(|name| format!("name={name}")));
+                    vec![format!("node = find(app, {})", arguments.join(", "))]
+                }
+            }
+        }
+        Step::Path(path) => {
+            let mut node = "app".to_string();
+            for segment in path.split('/').filter(|segment| !segment.is_empty()) {
+                let index: usize = segment.parse().map_err(|_| {
+                    format!("Invalid path {path:?}: {segment:?} is not a child index")
+                })?;
+                match format {
+                    Format::Dogtail => node.push_str(&format!(".children[{index}]")),
+                    Format::Pyatspi => node.push_str(&format!(".getChildAtIndex({index})")),
+                }
+            }
+            vec![format!("node = {node}")]
+        }
+        Step::Action(action) => match (action.parse::<usize>(), format) {
+            (Ok(index), _) => vec![format!("node.queryAction().doAction({index})")],
+            (Err(_), Format::Dogtail) => vec![format!("node.doActionNamed({})", string(action))],
+            (Err(_), Format::Pyatspi) => vec![format!("do_action(node, {})", string(action))],
+        },
+        Step::Key(keys) => keys
+            .to_vec()
+            .iter()
+            .map(|key| match format {
+                Format::Dogtail => Ok(format!("pressKey({})", string(key))),
+                Format::Pyatspi => Ok(format!("press_key({:#x})  # {key}", keys::keysym(key)?)),
+            })
+            .collect::<Result<Vec<_>>>()?,
+        Step::Type(text) => match format {
+            Format::Dogtail => vec![format!("typeText({})", string(text))],
+            Format::Pyatspi => vec![format!(
+                "pyatspi.Registry.generateKeyboardEvent(0, {}, pyatspi.KEY_STRING)",
+                string(text)
+            )],
+        },
+        Step::SetText(text) => match format {
+            Format::Dogtail => vec![format!("node.text = {}", string(text))],
+            Format::Pyatspi => vec![format!(
+                "node.queryEditableText().setTextContents({})",
+                string(text)
+            )],
+        },
+        Step::WaitFor(pattern) => {
+            let event_type = EventFilter::parse(pattern)?.registry_event();
+            vec![format!("wait_for({}, {timeout})", string(&event_type))]
+        }
+        Step::Assert(assertion) => {
+            let mut lines = Vec::new();
+            for (state, expected) in [(&assertion.state, true), (&assertion.not_state, false)] {
+                let Some(state) = state else {
+                    continue;
+                };
+                if State::from(state.as_str()) == State::Invalid {
+                    return Err(format!("Unknown state: {state}").into());
+                }
+                let constant = format!("pyatspi.STATE_{}", state.to_uppercase().replace('-', "_"));
+                let not = if expected { "" } else { "not " };
+                lines.push(format!(
+                    "assert {not}node.getState().contains({constant}), {}",
+                    string(&format!("expected {not}{state}"))
+                ));
+            }
+            if let Some(name) = &assertion.name {
+                lines.push(format!("assert node.name == {}", string(name)));
+            }
+            if let Some(role) = &assertion.role {
+                lines.push(format!(
+                    "assert node.getRoleName() == {}",
+                    string(&role.to_lowercase())
+                ));
+            }
+            lines
+        }
+        Step::Sleep(ms) => vec![format!("time.sleep({})", *ms as f64 / 1000.0)],
+    };
+    Ok(lines)
+}
+
+/// A Python string literal; JSON strings are valid Python.
+fn string(value: &str) -> String {
+    serde_json::to_string(value).unwrap_or_else(|_| "\"\"".to_string())
+}
+
+/// A Python identifier made from a file name, e.g. `open_file` for `open-file`.
+fn identifier(name: &str) -> String {
+    let identifier: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if identifier.is_empty() {
+        "script".to_string()
+    } else {
+        identifier.to_lowercase()
+    }
+}
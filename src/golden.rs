@@ -0,0 +1,134 @@
+//! Golden tests of accessibility trees, for an application's own integration tests.
+//!
+//! A test starts the application and compares its tree to a snapshot kept with the tests,
+//! as `verify` does from the command line:
+//!
+//! ```no_run
+//! # async fn test() {
+//! // Start the application under test first.
+//! a11y_app::golden::assert_tree_matches("gedit", "tests/gedit.a11y.json").await;
+//! # }
+//! ```
+//!
+//! With the environment variable `A11Y_APP_UPDATE_GOLDEN` set, the tree is written to the
+//! golden file instead, to create it or to accept the changes.
+
+use crate::{
+    diff,
+    diff_ignore::Ignore,
+    snapshot::{self, Detail},
+    Result,
+};
+use atspi::{connection::set_session_accessibility, AccessibilityConnection};
+use std::path::Path;
+
+/// Set to write the trees to the golden files instead of comparing them.
+pub const UPDATE_VARIABLE: &str = "A11Y_APP_UPDATE_GOLDEN";
+
+/// What to leave out of the comparison, for content that changes from run to run.
+///
+/// Nodes are given by query, a role and/or a quoted name, e.g. `push button "Save"`.
+#[derive(Clone, Default)]
+pub struct Options {
+    ignore_names: Vec<String>,
+    ignore_subtrees: Vec<String>,
+    ignore_properties: Vec<String>,
+}
+
+impl Options {
+    pub fn new() -> Options {
+        Options::default()
+    }
+
+    /// Do not compare the names of nodes matching `query`, e.g. a label showing the time.
+    pub fn ignore_name(mut self, query: &str) -> Options {
+        self.ignore_names.push(query.to_string());
+        self
+    }
+
+    /// Do not compare nodes matching `query` and the nodes below them.
+    pub fn ignore_subtree(mut self, query: &str) -> Options {
+        self.ignore_subtrees.push(query.to_string());
+        self
+    }
+
+    /// Do not compare `property` of any node, e.g. `geometry` or `attribute tooltip`.
+    pub fn ignore_property(mut self, property: &str) -> Options {
+        self.ignore_properties.push(property.to_string());
+        self
+    }
+
+    fn ignore(&self) -> Result<Ignore> {
+        Ignore::from_options(
+            &self.ignore_names,
+            &self.ignore_subtrees,
+            &self.ignore_properties,
+            None,
+        )
+    }
+}
+
+/// Panic with the differences if the tree of `app`, by bus name or application name,
+/// does not match the snapshot in `golden`.
+pub async fn assert_tree_matches(app: &str, golden: impl AsRef<Path>) {
+    assert_tree_matches_with(app, golden, &Options::default()).await;
+}
+
+/// [`assert_tree_matches`], leaving out what `options` say.
+pub async fn assert_tree_matches_with(app: &str, golden: impl AsRef<Path>, options: &Options) {
+    let golden = golden.as_ref();
+    match tree_differences(app, golden, options).await {
+        Ok(differences) if differences.is_empty() => {}
+        Ok(differences) => panic!(
+            "The tree of {app} deviates from {}:\n{}",
+            golden.display(),
+            differences.join("\n")
+        ),
+        Err(e) => panic!("Could not compare {app} to {}: {e}", golden.display()),
+    }
+}
+
+/// The differences between the tree of `app` and the snapshot in `golden`, a line each
+/// and a summary, or nothing if they match.
+///
+/// With `A11Y_APP_UPDATE_GOLDEN` set, the tree is written to `golden` and there are none.
+pub async fn tree_differences(
+    app: &str,
+    golden: impl AsRef<Path>,
+    options: &Options,
+) -> Result<Vec<String>> {
+    let golden = golden.as_ref();
+    let ignore = options.ignore()?;
+    let update = std::env::var_os(UPDATE_VARIABLE).is_some();
+    if !update && !golden.exists() {
+        return Err(format!(
+            "There is no golden file {}, set {UPDATE_VARIABLE} to write it",
+            golden.display()
+        )
+        .into());
+    }
+
+    set_session_accessibility(true).await?;
+    let a11y = AccessibilityConnection::new().await?;
+
+    if update {
+        // Keep the node and detail an existing golden file was taken with.
+        let (path, detail) = if golden.exists() {
+            let previous = snapshot::load(golden)?;
+            (previous.path, previous.detail)
+        } else {
+            (String::new(), Detail::default())
+        };
+        let live = snapshot::capture(&a11y, app, Some(&path), None, &detail).await?;
+        snapshot::save(&live, Some(golden))?;
+        return Ok(Vec::new());
+    }
+
+    let expected = snapshot::load(golden)?;
+    let live = snapshot::capture(&a11y, app, Some(&expected.path), None, &expected.detail).await?;
+    let changes = ignore.diff(&expected, &live);
+    if changes.is_empty() {
+        return Ok(Vec::new());
+    }
+    Ok(diff::change_lines(&changes, false))
+}
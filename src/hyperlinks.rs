@@ -0,0 +1,240 @@
+//! Listing and activating the links in text, through the Hypertext and Hyperlink interfaces.
+
+use crate::{
+    actions,
+    target::{self, Selected},
+    watch, Result,
+};
+use argh::{ArgsInfo, FromArgs};
+use atspi::{
+    proxy::{hyperlink::HyperlinkProxy, proxy_ext::ProxyExt},
+    zbus::proxy::CacheProperties,
+    AccessibilityConnection, ObjectRef,
+};
+use zbus::Connection;
+
+/// The actions that follow a link, in order of preference.
+const LINK_ACTIONS: &[&str] = &["jump", "click", "activate", "press"];
+
+/// List the links of a node's text: anchor text, URI and character range
+#[derive(FromArgs, ArgsInfo)]
+#[argh(subcommand, name = "links")]
+pub struct LinksArgs {
+    /// the application, by bus name or application name
+    #[argh(positional)]
+    app: String,
+
+    /// also list the links of the nodes below it, e.g. of all paragraphs of a document
+    #[argh(switch, short = 'r')]
+    recursive: bool,
+
+    /// the node, by the child indices leading to it from the application's root, e.g. `0/2/1`
+    #[argh(option)]
+    path: Option<String>,
+
+    /// the first node with this role and/or name, e.g. `document web`
+    #[argh(option)]
+    query: Option<String>,
+}
+
+/// Follow a link of a node's text, by its number as listed by `links`, its anchor text or URI
+#[derive(FromArgs, ArgsInfo)]
+#[argh(subcommand, name = "activate-link")]
+pub struct ActivateLinkArgs {
+    /// the application, by bus name or application name
+    #[argh(positional)]
+    app: String,
+
+    /// the link: its number as listed by `links`, its anchor text or its URI
+    #[argh(positional)]
+    link: String,
+
+    /// also look for the link in the nodes below it
+    #[argh(switch, short = 'r')]
+    recursive: bool,
+
+    /// the node, by the child indices leading to it from the application's root, e.g. `0/2/1`
+    #[argh(option)]
+    path: Option<String>,
+
+    /// the first node with this role and/or name, e.g. `document web`
+    #[argh(option)]
+    query: Option<String>,
+}
+
+/// A link in the text of a node.
+struct Link {
+    /// The node whose text has the link.
+    node: Selected,
+    /// Index of the link among the node's links.
+    index: i32,
+    start: i32,
+    end: i32,
+    anchor_text: String,
+    uri: String,
+    valid: bool,
+    /// The object representing the anchor, which has the actions to follow the link.
+    anchor: Option<Selected>,
+}
+
+async fn hyperlink(conn: &Connection, object: ObjectRef) -> Result<HyperlinkProxy<'static>> {
+    let proxy = HyperlinkProxy::builder(conn)
+        .destination(object.name)?
+        .path(object.path)?
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await?;
+
+    Ok(proxy)
+}
+
+/// The links of `node`, and with `recursive` of the nodes below it.
+async fn links(conn: &Connection, node: &Selected, recursive: bool) -> Result<Vec<Link>> {
+    let nodes = if recursive {
+        target::descendants(conn, node).await?
+    } else {
+        vec![node.clone()]
+    };
+
+    let mut links = Vec::new();
+    for node in nodes {
+        let proxies = node.proxy.proxies().await?;
+        let Ok(hypertext) = proxies.hypertext().await else {
+            continue;
+        };
+        let text = proxies.text().await.ok();
+
+        for index in 0..hypertext.get_nlinks().await? {
+            let object = hypertext.get_link(index).await?;
+            let link = hyperlink(conn, object).await?;
+            let start = link.start_index().await?;
+            let end = link.end_index().await?;
+            let anchor_text = match &text {
+                Some(text) => text.get_text(start, end).await.unwrap_or_default(),
+                None => String::new(),
+            };
+
+            let anchor = match link.get_object(0).await {
+                Ok(object) if object.path.as_str() != watch::NULL_PATH => {
+                    let proxy = watch::accessible_from_object_ref(conn, object).await?;
+                    Some(Selected {
+                        description: watch::describe_node(&proxy).await,
+                        path: target::index_path(conn, &proxy).await.unwrap_or_default(),
+                        proxy,
+                    })
+                }
+                _ => None,
+            };
+
+            links.push(Link {
+                node: node.clone(),
+                index,
+                start,
+                end,
+                anchor_text,
+                uri: link.get_uri(0).await.unwrap_or_default(),
+                valid: link.is_valid().await.unwrap_or(false),
+                anchor,
+            });
+        }
+    }
+    Ok(links)
+}
+
+pub async fn list_links(a11y: &AccessibilityConnection, args: LinksArgs) -> Result<()> {
+    let conn = a11y.connection();
+    let node = target::select(conn, &args.app, args.path.as_deref(), args.query.as_deref()).await?;
+    banner!("Node: {} at {}", node.description, node.location());
+
+    let links = links(conn, &node, args.recursive).await?;
+    if links.is_empty() {
+        println!("No links found.");
+        return Ok(());
+    }
+
+    let rows: Vec<Vec<String>> = links
+        .iter()
+        .enumerate()
+        .map(|(number, link)| {
+            vec![
+                number.to_string(),
+                link.node.location(),
+                link.index.to_string(),
+                format!("{}..{}", link.start, link.end),
+                link.anchor_text.clone(),
+                link.uri.clone(),
+                if link.valid { "yes" } else { "no" }.to_string(),
+            ]
+        })
+        .collect();
+    crate::print_table(
+        &[
+            "Number",
+            "Node",
+            "Index",
+            "Range",
+            "Anchor text",
+            "URI",
+            "Valid",
+        ],
+        &rows,
+    );
+    Ok(())
+}
+
+pub async fn activate_link(a11y: &AccessibilityConnection, args: ActivateLinkArgs) -> Result<()> {
+    let conn = a11y.connection();
+    let node = target::select(conn, &args.app, args.path.as_deref(), args.query.as_deref()).await?;
+    let mut links = links(conn, &node, args.recursive).await?;
+
+    let position = match args.link.parse::<usize>() {
+        Ok(number) if number < links.len() => Some(number),
+        _ => links
+            .iter()
+            .position(|link| link.anchor_text == args.link || link.uri == args.link),
+    };
+    let Some(position) = position else {
+        return Err(format!(
+            "{} has no link {:?} among its {} links",
+            node.description,
+            args.link,
+            links.len()
+        )
+        .into());
+    };
+    let link = links.swap_remove(position);
+
+    let Some(anchor) = &link.anchor else {
+        return Err(format!("The link to {} has no anchor object to activate", link.uri).into());
+    };
+    let proxies = anchor.proxy.proxies().await?;
+    let Ok(action) = proxies.action().await else {
+        return Err(format!(
+            "The anchor {} does not implement the Action interface",
+            anchor.description
+        )
+        .into());
+    };
+    let names = action.get_actions().await?;
+    let Some(name) = LINK_ACTIONS
+        .iter()
+        .find(|name| names.iter().any(|a| a.name.eq_ignore_ascii_case(name)))
+    else {
+        return Err(format!(
+            "The anchor {} has none of the actions {}",
+            anchor.description,
+            LINK_ACTIONS.join(", ")
+        )
+        .into());
+    };
+
+    actions::perform(anchor, name).await?;
+    if crate::dry_run() {
+        return Ok(());
+    }
+    println!(
+        "Activated the link {:?} to {} with {name:?}",
+        link.anchor_text, link.uri
+    );
+    Ok(())
+}
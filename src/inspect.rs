@@ -0,0 +1,322 @@
+//! Printing the properties and trees of applications and finding their nodes, the
+//! subcommands behind the default command `a11y-app [<bus name>...] [-p] [-c] [-l]`.
+//!
+//! Each takes applications, or snapshot files instead, and with `--path` or `--query`
+//! a node of each, like the commands that act on a node.
+
+use crate::{
+    audit, interrupt, live,
+    properties::{self, Properties},
+    render,
+    snapshot::{self, child_path},
+    target::{self, Query, Selected},
+    tree, Result,
+};
+use argh::{ArgsInfo, FromArgs};
+use atspi::AccessibilityConnection;
+use futures::future::try_join_all;
+use std::{path::Path, time::Duration};
+use zbus::{names::BusName, Connection};
+
+/// Print the accessible properties of the root of an application, or of a node
+#[derive(FromArgs, ArgsInfo)]
+#[argh(subcommand, name = "props")]
+pub struct PropsArgs {
+    /// the applications, by bus name or application name, or snapshot files
+    /// (default: org.a11y.atspi.Registry)
+    #[argh(positional)]
+    apps: Vec<String>,
+
+    /// the node, by the child indices leading to it from the application's root, e.g. `0/2/1`
+    #[argh(option)]
+    path: Option<String>,
+
+    /// the first node with this role and/or name, e.g. `push button "Save"`
+    #[argh(option)]
+    query: Option<String>,
+
+    /// the properties to show, separated by commas, as for a11y-app --props
+    /// (default: default)
+    #[argh(
+        option,
+        from_str_fn(properties::parse_properties),
+        default = "Properties::default()"
+    )]
+    props: Properties,
+}
+
+/// Print the tree of accessible objects of an application, or below a node
+#[derive(FromArgs, ArgsInfo)]
+#[argh(subcommand, name = "tree")]
+pub struct TreeArgs {
+    /// the applications, by bus name or application name, or snapshot files
+    /// (default: org.a11y.atspi.Registry)
+    #[argh(positional)]
+    apps: Vec<String>,
+
+    /// the node, by the child indices leading to it from the application's root, e.g. `0/2/1`
+    #[argh(option)]
+    path: Option<String>,
+
+    /// the first node with this role and/or name, e.g. `push button "Save"`
+    #[argh(option)]
+    query: Option<String>,
+
+    /// print the tree again every second, until interrupted
+    #[argh(switch, short = 'c')]
+    continuous: bool,
+
+    /// print the tree once and keep it current by applying change events, instead of
+    /// re-traversing
+    #[argh(switch, short = 'l')]
+    live: bool,
+
+    /// traverse the trees of the applications at the same time, rather than one after
+    /// another
+    #[argh(switch)]
+    parallel: bool,
+}
+
+/// List the nodes of an application that match a query, with their paths
+#[derive(FromArgs, ArgsInfo)]
+#[argh(subcommand, name = "search")]
+pub struct SearchArgs {
+    /// the application, by bus name or application name, or a snapshot file
+    #[argh(positional)]
+    app: String,
+
+    /// the role and/or name to look for, e.g. `push button` or `"Save"`
+    #[argh(positional)]
+    query: String,
+
+    /// search only below this node, by the child indices leading to it from the
+    /// application's root, e.g. `0/2/1`
+    #[argh(option)]
+    path: Option<String>,
+
+    /// list at most this many nodes
+    #[argh(option)]
+    limit: Option<usize>,
+}
+
+impl PropsArgs {
+    pub fn is_offline(&self) -> bool {
+        is_offline(&self.apps)
+    }
+}
+
+impl TreeArgs {
+    pub fn is_offline(&self) -> bool {
+        is_offline(&self.apps)
+    }
+}
+
+impl SearchArgs {
+    pub fn is_offline(&self) -> bool {
+        snapshot::is_snapshot(&self.app)
+    }
+}
+
+pub async fn props(a11y: &AccessibilityConnection, args: PropsArgs) -> Result<()> {
+    let conn = a11y.connection();
+    if args.path.is_none() && args.query.is_none() {
+        let apps = crate::parse_bus_names(&args.apps, conn).await?;
+        return crate::print_properties_of_apps(conn, &apps, &args.props).await;
+    }
+
+    for node in select(
+        a11y,
+        &args.apps,
+        args.path.as_deref(),
+        args.query.as_deref(),
+    )
+    .await?
+    {
+        banner!(
+            "Node: {} at {} - Accessible Properties:",
+            node.description,
+            node.location()
+        );
+        crate::table_of_accessible_properties(&node.proxy, &args.props).await?;
+        println!();
+    }
+    Ok(())
+}
+
+pub fn props_offline(args: PropsArgs) -> Result<()> {
+    for file in &args.apps {
+        let file = Path::new(file);
+        let snapshot = snapshot::load(file)?;
+        let (node, path) = snapshot.select(args.path.as_deref(), args.query.as_deref())?;
+        crate::print_snapshot_properties(file, &snapshot, node, &path);
+    }
+    Ok(())
+}
+
+pub async fn tree(a11y: &AccessibilityConnection, args: TreeArgs) -> Result<()> {
+    let conn = a11y.connection();
+    if args.path.is_none() && args.query.is_none() {
+        let apps = crate::parse_bus_names(&args.apps, conn).await?;
+        if args.live {
+            return live::live_trees(a11y, &apps, Duration::from_secs(1)).await;
+        }
+        loop {
+            if args.parallel {
+                print_trees_parallel(conn, &apps).await?;
+            } else {
+                crate::print_tree(conn, &apps).await?;
+            }
+            if !args.continuous || interrupt::interrupted() {
+                return Ok(());
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    }
+
+    if args.live {
+        return Err(
+            "--live keeps the trees of whole applications, without --path or --query".into(),
+        );
+    }
+    let nodes = select(
+        a11y,
+        &args.apps,
+        args.path.as_deref(),
+        args.query.as_deref(),
+    )
+    .await?;
+    loop {
+        for node in &nodes {
+            print_node_tree(node).await?;
+        }
+        if !args.continuous || interrupt::interrupted() {
+            return Ok(());
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+}
+
+pub fn tree_offline(args: TreeArgs) -> Result<()> {
+    if args.continuous || args.live {
+        return Err(
+            "A snapshot does not change, --continuous and --live need the application".into(),
+        );
+    }
+    for file in &args.apps {
+        let file = Path::new(file);
+        let snapshot = snapshot::load(file)?;
+        let (node, _) = snapshot.select(args.path.as_deref(), args.query.as_deref())?;
+        crate::print_snapshot_tree(file, node)?;
+    }
+    Ok(())
+}
+
+pub async fn search(a11y: &AccessibilityConnection, args: SearchArgs) -> Result<()> {
+    let conn = a11y.connection();
+    let node = target::select(conn, &args.app, args.path.as_deref(), None).await?;
+    let limit = args.limit.unwrap_or(usize::MAX);
+    let found = target::search(conn, &node, &args.query, limit).await?;
+
+    print_found(
+        &args.query,
+        &node.description,
+        found
+            .into_iter()
+            .map(|node| (node.path, node.description))
+            .collect(),
+    );
+    Ok(())
+}
+
+pub fn search_offline(args: SearchArgs) -> Result<()> {
+    let snapshot = snapshot::load(Path::new(&args.app))?;
+    let query = Query::parse(&args.query)?;
+    let (root, root_path) = snapshot.select(args.path.as_deref(), None)?;
+    let limit = args.limit.unwrap_or(usize::MAX);
+
+    // Depth-first, as on the bus.
+    let mut found = Vec::new();
+    let mut stack = vec![(root, root_path)];
+    while let Some((node, path)) = stack.pop() {
+        if found.len() >= limit {
+            break;
+        }
+        if query.matches_values(&node.role, &node.name) {
+            found.push((path.clone(), node.description()));
+        }
+        for (index, child) in node.children.iter().enumerate().rev() {
+            stack.push((child, child_path(&path, index)));
+        }
+    }
+
+    print_found(&args.query, &root.description(), found);
+    Ok(())
+}
+
+/// Whether `apps` are all snapshot files.
+fn is_offline(apps: &[String]) -> bool {
+    !apps.is_empty() && apps.iter().all(|app| snapshot::is_snapshot(app))
+}
+
+/// The node at `path` or matching `query` in each of `apps`, the registry if there are none.
+async fn select(
+    a11y: &AccessibilityConnection,
+    apps: &[String],
+    path: Option<&str>,
+    query: Option<&str>,
+) -> Result<Vec<Selected>> {
+    let conn = a11y.connection();
+    let registry = [String::new()];
+    let apps = if apps.is_empty() { &registry[..] } else { apps };
+
+    let mut nodes = Vec::with_capacity(apps.len());
+    for app in apps {
+        nodes.push(target::select(conn, app, path, query).await?);
+    }
+    Ok(nodes)
+}
+
+/// Print the trees of the applications, traversed at the same time.
+async fn print_trees_parallel(
+    conn: &Connection,
+    apps: &[(String, BusName<'static>)],
+) -> Result<()> {
+    let trees = try_join_all(apps.iter().map(|(_, bus_name)| async move {
+        let acc_proxy = crate::get_root_accessible(bus_name.clone(), conn).await?;
+        tree::configured().build(acc_proxy).await
+    }))
+    .await?;
+
+    for ((name, bus_name), tree) in apps.iter().zip(trees) {
+        banner!("Application: {name} ({bus_name}) - Tree of Accessible Objects:");
+        render::print(&tree)?;
+        println!();
+    }
+    Ok(())
+}
+
+/// Print the tree below a selected node.
+async fn print_node_tree(node: &Selected) -> Result<()> {
+    banner!(
+        "Node: {} at {} - Tree of Accessible Objects:",
+        node.description,
+        node.location()
+    );
+    let tree = tree::configured().build(node.proxy.clone()).await?;
+    render::print(&tree)?;
+    println!();
+    Ok(())
+}
+
+/// Print the paths and descriptions of the nodes found below `root`.
+fn print_found(query: &str, root: &str, found: Vec<(String, String)>) {
+    if found.is_empty() {
+        println!("No node below {root} matches {query:?}.");
+        return;
+    }
+    let rows: Vec<Vec<String>> = found
+        .into_iter()
+        .map(|(path, description)| vec![audit::location(&path), description])
+        .collect();
+    crate::print_table(&["Where", "Node"], &rows);
+}
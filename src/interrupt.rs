@@ -0,0 +1,78 @@
+//! Ctrl-C during a traversal of a tree stops the traversal, rather than the process, so
+//! what was gathered so far is still printed or written, marked as partial. Ctrl-C at any
+//! other time, or a second time, quits as usual.
+//!
+//! A program embedding the command line stops a run by cancelling the token given to
+//! [`crate::cancel_on`] instead: traversals end with a `tree::Cancelled` error, and the
+//! commands that watch until interrupted return. Ctrl-C is not handled then, so it does
+//! not quit the embedding program.
+
+use crate::exit;
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    OnceLock,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+
+/// Stops the run when cancelled, if the embedding program gave one.
+static CANCELLATION: OnceLock<CancellationToken> = OnceLock::new();
+
+/// Whether Ctrl-C was pressed during a traversal.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// How many traversals are going on.
+static TRAVERSALS: AtomicUsize = AtomicUsize::new(0);
+
+/// Handle Ctrl-C for the rest of the run.
+pub(crate) fn install() {
+    tokio::spawn(async {
+        while tokio::signal::ctrl_c().await.is_ok() {
+            if TRAVERSALS.load(Ordering::SeqCst) == 0 || INTERRUPTED.swap(true, Ordering::SeqCst) {
+                exit::exit(exit::INTERRUPTED);
+            }
+            warn!(
+                "interrupted, finishing with the nodes gathered so far; press Ctrl-C again to quit"
+            );
+        }
+    });
+}
+
+/// Stop the run when `token` is cancelled.
+pub(crate) fn cancel_on(token: CancellationToken) {
+    let _ = CANCELLATION.set(token);
+}
+
+/// The token that stops the run, if there is one.
+pub(crate) fn cancellation() -> Option<CancellationToken> {
+    CANCELLATION.get().cloned()
+}
+
+/// Completes when the run is cancelled, never if it cannot be.
+pub(crate) async fn cancelled() {
+    match CANCELLATION.get() {
+        Some(token) => token.cancelled().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Whether a traversal was interrupted, after which traversals stop where they are.
+pub(crate) fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// A traversal going on, until dropped.
+pub(crate) struct Traversal(());
+
+impl Traversal {
+    pub(crate) fn begin() -> Traversal {
+        TRAVERSALS.fetch_add(1, Ordering::SeqCst);
+        Traversal(())
+    }
+}
+
+impl Drop for Traversal {
+    fn drop(&mut self) {
+        TRAVERSALS.fetch_sub(1, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,305 @@
+//! Key events, as reported to listeners registered with the registry's `DeviceEventController`,
+//! and synthesized through it.
+//!
+//! Key events are not broadcast like other AT-SPI events: a listener object is
+//! registered with the controller, which then calls its `NotifyEvent` method.
+
+use crate::Result;
+use argh::{ArgsInfo, FromArgs};
+use atspi::{
+    proxy::device_event_controller::{DeviceEventControllerProxy, KeySynthType},
+    AccessibilityConnection,
+};
+use futures::{
+    channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender},
+    future::try_join_all,
+};
+use std::time::Duration;
+use zbus::{message::Header, zvariant::ObjectPath, Connection};
+
+const DEVICE_EVENT_CONTROLLER_PATH: &str = "/org/a11y/atspi/registry/deviceeventcontroller";
+const DEVICE_EVENT_CONTROLLER_INTERFACE: &str = "org.a11y.atspi.DeviceEventController";
+const LISTENER_PATH: &str = "/org/a11y/atspi/listeners/a11y_app";
+
+/// `KEY_PRESS | KEY_RELEASE`, the event types we listen for.
+const KEY_EVENT_TYPES: u32 = 1 << 0 | 1 << 1;
+
+/// The controller only notifies listeners registered for the exact modifier mask
+/// of a key event, so we register for every combination of the eight X11 modifiers.
+const MODIFIER_MASKS: std::ops::RangeInclusive<u32> = 0..=0xff;
+
+/// `ControlMask | Mod1Mask | Mod4Mask`: Control, Alt and Super.
+const SHORTCUT_MODIFIERS: u32 = 1 << 2 | 1 << 3 | 1 << 6;
+
+/// Keysyms of keys without a character, by their X11 name.
+const NAMED_KEYSYMS: &[(&str, i32)] = &[
+    ("BackSpace", 0xff08),
+    ("Tab", 0xff09),
+    ("Return", 0xff0d),
+    ("Escape", 0xff1b),
+    ("Delete", 0xffff),
+    ("Home", 0xff50),
+    ("Left", 0xff51),
+    ("Up", 0xff52),
+    ("Right", 0xff53),
+    ("Down", 0xff54),
+    ("Page_Up", 0xff55),
+    ("Page_Down", 0xff56),
+    ("End", 0xff57),
+    ("Insert", 0xff63),
+    ("Menu", 0xff67),
+    ("space", 0x20),
+    ("F1", 0xffbe),
+    ("F2", 0xffbf),
+    ("F3", 0xffc0),
+    ("F4", 0xffc1),
+    ("F5", 0xffc2),
+    ("F6", 0xffc3),
+    ("F7", 0xffc4),
+    ("F8", 0xffc5),
+    ("F9", 0xffc6),
+    ("F10", 0xffc7),
+    ("F11", 0xffc8),
+    ("F12", 0xffc9),
+    ("Shift_L", 0xffe1),
+    ("Shift_R", 0xffe2),
+    ("Control_L", 0xffe3),
+    ("Control_R", 0xffe4),
+    ("Caps_Lock", 0xffe5),
+    ("Alt_L", 0xffe9),
+    ("Alt_R", 0xffea),
+    ("Super_L", 0xffeb),
+    ("Super_R", 0xffec),
+];
+
+/// Press and release keys, or type a string, through the device event controller
+#[derive(FromArgs, ArgsInfo)]
+#[argh(subcommand, name = "key")]
+pub struct KeyArgs {
+    /// the keys, by X11 keysym name (e.g. `Tab`, `Return`, `Page_Down`), keysym
+    /// number (e.g. `0xff09`) or character
+    #[argh(positional)]
+    keys: Vec<String>,
+
+    /// type this string after the keys
+    #[argh(option)]
+    string: Option<String>,
+
+    /// only press the keys, which are then hardware key codes, e.g. `50` for the left
+    /// Shift key on most keyboards
+    #[argh(switch)]
+    press: bool,
+
+    /// only release the keys, which are then hardware key codes
+    #[argh(switch)]
+    release: bool,
+
+    /// milliseconds to wait between keys (default: 50)
+    #[argh(option, default = "50")]
+    delay: u64,
+}
+
+/// The keysym for a key name, a keysym number or a single character.
+pub(crate) fn keysym(key: &str) -> Result<i32> {
+    if let Some(&(_, keysym)) = NAMED_KEYSYMS.iter().find(|(name, _)| *name == key) {
+        return Ok(keysym);
+    }
+    if let Some(hex) = key.strip_prefix("0x") {
+        return i32::from_str_radix(hex, 16).map_err(|_| format!("Invalid keysym: {key}").into());
+    }
+
+    let mut chars = key.chars();
+    match (chars.next(), chars.next()) {
+        // Latin-1 keysyms equal their code point, others are offset into the Unicode range.
+        (Some(c), None) if (' '..='\u{ff}').contains(&c) => Ok(c as i32),
+        (Some(c), None) => Ok(0x0100_0000 + c as i32),
+        _ => key.parse().map_err(|_| {
+            format!("Unknown key: {key}, give a keysym name, number or character").into()
+        }),
+    }
+}
+
+/// The X11 name of a keysym without a character, e.g. `Tab`.
+pub(crate) fn key_name(keysym: i32) -> Option<&'static str> {
+    NAMED_KEYSYMS
+        .iter()
+        .find(|(_, named)| *named == keysym)
+        .map(|(name, _)| *name)
+}
+
+pub async fn key(a11y: &AccessibilityConnection, args: KeyArgs) -> Result<()> {
+    if args.keys.is_empty() && args.string.is_none() {
+        return Err("Give the keys to press, or a --string to type".into());
+    }
+    if args.press && args.release {
+        return Err("Give either --press or --release, not both".into());
+    }
+
+    let conn = a11y.connection();
+    let delay = Duration::from_millis(args.delay);
+
+    if args.press || args.release {
+        let synth_type = if args.press {
+            KeySynthType::Press
+        } else {
+            KeySynthType::Release
+        };
+        let controller = DeviceEventControllerProxy::new(conn).await?;
+        for key in &args.keys {
+            let code: i32 = key.parse().map_err(|_| {
+                format!("Invalid key code: {key}, --press and --release take hardware key codes")
+            })?;
+            if crate::dry_run() {
+                let verb = if args.press { "press" } else { "release" };
+                println!("Would {verb} key code {code}");
+                continue;
+            }
+            controller
+                .generate_keyboard_event(code, "", synth_type)
+                .await
+                .map_err(|e| format!("Could not generate key {key}: {e}"))?;
+            tokio::time::sleep(delay).await;
+        }
+    } else {
+        press_keys(conn, &args.keys, delay).await?;
+    }
+
+    if let Some(string) = &args.string {
+        type_string(conn, string).await?;
+    }
+
+    Ok(())
+}
+
+/// Press and release each key, given as for the `key` command, with `delay` in between.
+pub(crate) async fn press_keys(conn: &Connection, keys: &[String], delay: Duration) -> Result<()> {
+    let controller = DeviceEventControllerProxy::new(conn).await?;
+    for key in keys {
+        let code = keysym(key)?;
+        if crate::dry_run() {
+            println!("Would press {key}");
+            continue;
+        }
+        controller
+            .generate_keyboard_event(code, "", KeySynthType::Sym)
+            .await
+            .map_err(|e| format!("Could not generate key {key}: {e}"))?;
+        tokio::time::sleep(delay).await;
+    }
+    Ok(())
+}
+
+/// Type a string, as if entered on the keyboard.
+pub(crate) async fn type_string(conn: &Connection, string: &str) -> Result<()> {
+    if crate::dry_run() {
+        println!("Would type {string:?}");
+        return Ok(());
+    }
+    let controller = DeviceEventControllerProxy::new(conn).await?;
+    controller
+        .generate_keyboard_event(0, string, KeySynthType::String)
+        .await
+        .map_err(|e| format!("Could not type {string:?}: {e}"))?;
+    Ok(())
+}
+
+/// A key press or release.
+pub(crate) struct KeyEvent {
+    /// The unique bus name of the controller that reported the event.
+    pub(crate) sender: String,
+    pub(crate) pressed: bool,
+    pub(crate) keysym: i32,
+    pub(crate) hw_code: u32,
+    pub(crate) modifiers: u32,
+    pub(crate) string: String,
+}
+
+impl KeyEvent {
+    /// The event type in the registry notation, so `--event keyboard:press` selects it.
+    pub(crate) fn event_type(&self) -> &'static str {
+        if self.pressed {
+            "keyboard:press"
+        } else {
+            "keyboard:release"
+        }
+    }
+
+    /// Whether Control, Alt or Super were held, which makes the key a shortcut rather than text.
+    pub(crate) fn is_shortcut(&self) -> bool {
+        self.modifiers & SHORTCUT_MODIFIERS != 0
+    }
+
+    /// Whether the key is a modifier itself, e.g. `Shift_L`.
+    pub(crate) fn is_modifier(&self) -> bool {
+        (0xffe1..=0xffee).contains(&self.keysym)
+    }
+
+    pub(crate) fn detail(&self) -> String {
+        let mut detail = format!(
+            "{:?} keysym {:#x} code {}",
+            self.string, self.keysym, self.hw_code
+        );
+        if self.modifiers != 0 {
+            detail.push_str(&format!(" modifiers {:#x}", self.modifiers));
+        }
+        detail
+    }
+}
+
+struct KeyListener {
+    events: UnboundedSender<KeyEvent>,
+}
+
+#[zbus::interface(name = "org.a11y.atspi.DeviceEventListener")]
+impl KeyListener {
+    /// Receive a key event, returns whether we consumed it, which we never do.
+    fn notify_event(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        event: (u32, i32, u32, u32, i32, String, bool),
+    ) -> bool {
+        let (event_type, keysym, hw_code, modifiers, _timestamp, string, _is_text) = event;
+        let _ = self.events.unbounded_send(KeyEvent {
+            sender: header.sender().map(|s| s.to_string()).unwrap_or_default(),
+            pressed: event_type == 0,
+            keysym,
+            hw_code,
+            modifiers,
+            string,
+        });
+        false
+    }
+}
+
+/// Register a listener for all key presses and releases with the device event controller.
+pub(crate) async fn listen(conn: &Connection) -> Result<UnboundedReceiver<KeyEvent>> {
+    let (events, receiver) = unbounded();
+    conn.object_server()
+        .at(LISTENER_PATH, KeyListener { events })
+        .await?;
+
+    let listener = ObjectPath::from_static_str_unchecked(LISTENER_PATH);
+    let all_keys: Vec<(i32, i32, String, i32)> = Vec::new();
+    // Neither synchronous, nor preemptive, nor global: we only observe what toolkits report.
+    let mode = (false, false, false);
+
+    // One call for each mask, all at once rather than one round trip after the other.
+    let registrations = MODIFIER_MASKS.map(|mask| {
+        let body = (&listener, &all_keys, mask, KEY_EVENT_TYPES, mode);
+        async move {
+            conn.call_method(
+                Some(crate::REGISTRY_DEST),
+                DEVICE_EVENT_CONTROLLER_PATH,
+                Some(DEVICE_EVENT_CONTROLLER_INTERFACE),
+                "RegisterKeystrokeListener",
+                &body,
+            )
+            .await
+        }
+    });
+    try_join_all(registrations)
+        .await
+        .map_err(|e| format!("Could not register for key events: {e}"))?;
+
+    Ok(receiver)
+}
@@ -0,0 +1,203 @@
+//! Measuring the time from an action to the AT-SPI events it causes.
+//!
+//! Screen reader responsiveness problems are usually latency problems: the
+//! application is slow to report what changed, so the screen reader is slow to speak.
+
+use crate::{
+    watch::{self, EventFilter},
+    Result,
+};
+use argh::{ArgsInfo, FromArgs};
+use atspi::{AccessibilityConnection, Event, EventProperties, MouseEvents, ObjectRef};
+use futures::StreamExt;
+use std::time::{Duration, Instant};
+use tracing::warn;
+use zbus::{fdo::DBusProxy, names::OwnedUniqueName, Connection};
+
+/// Measure the time from an action to the accessibility events it causes
+#[derive(FromArgs, ArgsInfo)]
+#[argh(subcommand, name = "latency")]
+pub struct LatencyArgs {
+    /// shell command that performs the action, e.g. `xdotool key Tab`
+    /// (default: wait for a mouse button press)
+    #[argh(option)]
+    run: Option<String>,
+
+    /// only measure events of this type, e.g. `object:state-changed:focused` or `window:*`
+    /// (may be repeated, default: all events)
+    #[argh(option)]
+    event: Vec<String>,
+
+    /// only measure events from this application, by bus name or application name
+    /// (may be repeated, default: all applications)
+    #[argh(option)]
+    app: Vec<String>,
+
+    /// how long to collect events after each action, in milliseconds (default: 1000)
+    #[argh(option, default = "1000")]
+    window: u64,
+
+    /// number of times to perform the action (default: 1)
+    #[argh(option, default = "1")]
+    repeat: usize,
+}
+
+/// An event that arrived after the action.
+struct Arrival {
+    latency: Duration,
+    sender: String,
+    event_type: String,
+    target: ObjectRef,
+}
+
+/// Perform the action, or wait for the user to act, and report how long each event took to arrive.
+pub async fn latency(a11y: &AccessibilityConnection, args: LatencyArgs) -> Result<()> {
+    let conn = a11y.connection();
+    let dbus = DBusProxy::new(conn).await?;
+
+    let filters = args
+        .event
+        .iter()
+        .map(|pattern| EventFilter::parse(pattern))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut senders: Vec<OwnedUniqueName> = Vec::new();
+    for app in &args.app {
+        for (name, bus_name) in crate::parse_bus_name(app.clone(), conn).await? {
+            let unique_name = watch::unique_name(&dbus, bus_name).await?;
+            banner!("Measuring application: {name} ({unique_name})");
+            senders.push(unique_name);
+        }
+    }
+
+    // Without a command, the user's button press starts the clock.
+    let mut subscription = args.event.clone();
+    if args.run.is_none() && !subscription.is_empty() {
+        subscription.push("mouse:button".to_string());
+    }
+    let subscription = subscription
+        .iter()
+        .map(|pattern| EventFilter::parse(pattern))
+        .collect::<Result<Vec<_>>>()?;
+    watch::subscribe(a11y, &subscription).await?;
+
+    let events = a11y.event_stream();
+    futures::pin_mut!(events);
+
+    let window = Duration::from_millis(args.window);
+    let mut first_latencies = Vec::new();
+
+    for round in 1..=args.repeat.max(1) {
+        let start = match &args.run {
+            Some(command) => {
+                println!("Round {round}: running `{command}`...");
+                let start = Instant::now();
+                let mut child = std::process::Command::new("sh")
+                    .arg("-c")
+                    .arg(command)
+                    .spawn()?;
+                // Reap the child in the background, it may outlive the window.
+                tokio::task::spawn_blocking(move || child.wait());
+                start
+            }
+            None => {
+                println!("Round {round}: press a mouse button in the application...");
+                loop {
+                    let Some(event) = events.next().await else {
+                        return Ok(());
+                    };
+                    if let Ok(Event::Mouse(MouseEvents::Button(e))) = event {
+                        if e.detail.ends_with('p') {
+                            break Instant::now();
+                        }
+                    }
+                }
+            }
+        };
+
+        let deadline = tokio::time::Instant::from_std(start + window);
+        let mut arrivals = Vec::new();
+
+        loop {
+            let event = tokio::select! {
+                event = events.next() => event,
+                _ = tokio::time::sleep_until(deadline) => break,
+            };
+            let Some(event) = event else {
+                break;
+            };
+            let latency = start.elapsed();
+
+            let event = match event {
+                Ok(event) => event,
+                Err(e) => {
+                    warn!("could not parse event: {e}");
+                    continue;
+                }
+            };
+
+            // The press that started the clock, and any that follow, are not the application's response.
+            if let Event::Mouse(_) = event {
+                if args.run.is_none() {
+                    continue;
+                }
+            }
+            if !senders.is_empty() && !senders.iter().any(|sender| *sender == event.sender()) {
+                continue;
+            }
+            let event_type = watch::event_type(&event);
+            if !filters.is_empty() && !filters.iter().any(|f| f.matches(&event_type)) {
+                continue;
+            }
+
+            // The target is described after the window, so looking it up
+            // does not delay the arrival of the next event.
+            arrivals.push(Arrival {
+                latency,
+                sender: event.sender().to_string(),
+                event_type,
+                target: event.object_ref(),
+            });
+        }
+
+        print_arrivals(conn, &arrivals).await;
+        if let Some(first) = arrivals.first() {
+            first_latencies.push(first.latency);
+        }
+    }
+
+    if first_latencies.len() > 1 {
+        first_latencies.sort();
+        let ms = |d: &Duration| format!("{:.1}", d.as_secs_f64() * 1000.0);
+        println!(
+            "First event after {} of {} actions: min {} ms, median {} ms, max {} ms",
+            first_latencies.len(),
+            args.repeat,
+            ms(&first_latencies[0]),
+            ms(&first_latencies[first_latencies.len() / 2]),
+            ms(&first_latencies[first_latencies.len() - 1]),
+        );
+    }
+
+    Ok(())
+}
+
+async fn print_arrivals(conn: &Connection, arrivals: &[Arrival]) {
+    if arrivals.is_empty() {
+        println!("No events arrived within the window.");
+        println!();
+        return;
+    }
+
+    let mut rows = Vec::with_capacity(arrivals.len());
+    for arrival in arrivals {
+        rows.push(vec![
+            format!("{:.1}", arrival.latency.as_secs_f64() * 1000.0),
+            arrival.sender.clone(),
+            arrival.event_type.clone(),
+            watch::describe_target(conn, arrival.target.clone()).await,
+        ]);
+    }
+    crate::print_table(&["Latency (ms)", "Sender", "Event type", "Target"], &rows);
+    println!();
+}
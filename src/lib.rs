@@ -0,0 +1,849 @@
+//! Inspecting and testing the accessibility trees of applications over AT-SPI.
+//!
+//! The command line interface is [`run`]; [`golden`] compares trees to golden
+//! snapshots from an application's own tests. Programs that want the trees and
+//! properties without running `a11y-app` can build them with [`tree`] and fetch
+//! them with [`properties`].
+
+use atspi::{
+    proxy::accessible::{AccessibleProxy, ObjectRefExt},
+    zbus::proxy::CacheProperties,
+    Role,
+};
+use exit::Failure;
+use futures::future::join_all;
+use properties::Properties;
+use std::{io::IsTerminal, path::Path, sync::OnceLock, vec};
+use tracing::warn;
+use tree::A11yNode;
+use zbus::{fdo::DBusProxy, names::BusName, Connection};
+
+/// Print a line about what follows, e.g. which application a table is of. In machine mode
+/// standard output only has what was asked for, so there are none.
+macro_rules! banner {
+    ($($arg:tt)*) => {
+        if !$crate::machine() {
+            println!($($arg)*);
+        }
+    };
+}
+
+mod actions;
+mod apps;
+mod assertions;
+mod audit;
+mod audit_plugins;
+mod cli;
+mod component;
+mod config;
+mod diff;
+mod diff_ignore;
+mod editable_text;
+mod exit;
+mod expand;
+mod export;
+pub mod golden;
+mod hyperlinks;
+mod inspect;
+mod interrupt;
+mod keys;
+mod latency;
+mod live;
+mod logging;
+mod man;
+pub mod properties;
+mod recorder;
+pub mod render;
+mod retry;
+mod script;
+mod scripting;
+mod selection;
+mod snapshot;
+mod snapshot_metadata;
+mod tab_order;
+mod table;
+mod target;
+pub mod tree;
+mod value;
+mod verify;
+mod version;
+mod wait;
+mod watch;
+
+pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+type ArgResult<T> = std::result::Result<T, String>;
+
+pub use cli::{cancel_on, exit_code, run};
+
+const REGISTRY_DEST: &str = "org.a11y.atspi.Registry";
+const ACCESSIBLE_ROOT: &str = "/org/a11y/atspi/accessible/root";
+const ACCESSIBLE_INTERFACE: &str = "org.a11y.atspi.Accessible";
+
+/// The prefix of an application given by its process id, e.g. `pid:4242`.
+const PID_PREFIX: &str = "pid:";
+
+/// The prefix of an application given by the title of one of its windows,
+/// e.g. `window:README.md`.
+const WINDOW_PREFIX: &str = "window:";
+
+/// The prefix of applications given by a glob pattern on their bus names and application
+/// names, e.g. `glob:org.gnome.*`.
+const GLOB_PREFIX: &str = "glob:";
+
+/// The prefix of applications given by a regular expression on their bus names and
+/// application names, e.g. `regex:^(gedit|nautilus)$`.
+const REGEX_PREFIX: &str = "regex:";
+
+/// The roles of the top-level windows of an application.
+const WINDOW_ROLES: &[Role] = &[Role::Frame, Role::Window, Role::Dialog];
+
+/// How applications whose names only match the sought name loosely are treated, for the
+/// whole run.
+static MATCHING: OnceLock<Matching> = OnceLock::new();
+
+/// Whether the run is in machine mode, see `--machine`.
+static MACHINE: OnceLock<bool> = OnceLock::new();
+
+/// Whether commands only say what they would do to applications, see `--dry-run`.
+static DRY_RUN: OnceLock<bool> = OnceLock::new();
+
+async fn get_registry_accessible<'a>(conn: &Connection) -> Result<AccessibleProxy<'a>> {
+    let registry = AccessibleProxy::builder(conn)
+        .destination(REGISTRY_DEST)?
+        .path(ACCESSIBLE_ROOT)?
+        .interface(ACCESSIBLE_INTERFACE)?
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await?;
+
+    Ok(registry)
+}
+
+async fn get_root_accessible<'c>(
+    bus_name: BusName<'c>,
+    conn: &'c Connection,
+) -> Result<AccessibleProxy<'c>> {
+    let root_accessible = AccessibleProxy::builder(conn)
+        .destination(bus_name)?
+        .path(ACCESSIBLE_ROOT)?
+        .interface(ACCESSIBLE_INTERFACE)?
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await?;
+
+    Ok(root_accessible)
+}
+
+/// How `from_app_name` treats applications whose names only match loosely.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Matching {
+    /// Ask whether to add each of them.
+    Ask,
+    /// Add all of them.
+    All,
+    /// Take only the best match.
+    First,
+    /// Fail, for running without a user.
+    Exact,
+    /// Take the application with this number in the list of matches, from 1.
+    Index(usize),
+}
+
+/// How well an application's name matches the sought name, best first.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Match {
+    Perfect,
+    CaseInsensitive,
+    Partial,
+}
+
+/// Parse the bus name from the command line argument
+async fn parse_bus_name(
+    name: String,
+    conn: &Connection,
+) -> Result<Vec<(String, BusName<'static>)>> {
+    // If the name is empty, use the default bus name
+    if name.is_empty() {
+        let bus_name = match BusName::try_from(REGISTRY_DEST) {
+            Ok(name) => name.to_owned(),
+            Err(e) => return Err(format!("Invalid bus name: {REGISTRY_DEST} ({e})").into()),
+        };
+
+        return Ok(vec![(REGISTRY_DEST.to_string(), bus_name)]);
+    }
+
+    if let Some(pid) = name.strip_prefix(PID_PREFIX) {
+        let pid = pid
+            .parse()
+            .map_err(|_| format!("Invalid process id: {pid}"))?;
+        return from_pid(pid, conn).await;
+    }
+    if let Some(title) = name.strip_prefix(WINDOW_PREFIX) {
+        return from_window(title, conn).await;
+    }
+    if let Some(pattern) = name.strip_prefix(GLOB_PREFIX) {
+        return from_pattern(&name, |candidate| glob_matches(pattern, candidate), conn).await;
+    }
+    if let Some(pattern) = name.strip_prefix(REGEX_PREFIX) {
+        let regex = regex::Regex::new(pattern)
+            .map_err(|e| format!("Invalid regular expression {pattern:?}: {e}"))?;
+        return from_pattern(&name, |candidate| regex.is_match(candidate), conn).await;
+    }
+
+    match BusName::try_from(name.clone()) {
+        Ok(bus_name) => Ok(vec![(name, bus_name.to_owned())]),
+        _ => {
+            // If the name is not a valid bus-name, try find it as an application name
+            from_app_name(name, conn).await
+        }
+    }
+}
+
+/// Parse several bus names, the default bus name if there are none, keeping each
+/// application once
+async fn parse_bus_names(
+    names: &[String],
+    conn: &Connection,
+) -> Result<Vec<(String, BusName<'static>)>> {
+    if names.is_empty() {
+        return parse_bus_name(String::new(), conn).await;
+    }
+
+    let mut apps: Vec<(String, BusName<'static>)> = Vec::new();
+    for name in names {
+        for app in parse_bus_name(name.clone(), conn).await? {
+            if !apps.iter().any(|(_, bus_name)| *bus_name == app.1) {
+                apps.push(app);
+            }
+        }
+    }
+    Ok(apps)
+}
+
+fn get_user_yn_response(question: &str) -> ArgResult<bool> {
+    println!("{question} (Y/n)");
+    let mut answer = String::new();
+    std::io::stdin()
+        .read_line(&mut answer)
+        .expect("Failed to read line");
+    let answer = answer.trim().to_lowercase();
+    if answer == "y" || answer == "yes" || answer.is_empty() {
+        Ok(true)
+    } else if answer == "n" || answer == "no" {
+        Ok(false)
+    } else {
+        Err(format!("Invalid answer: {answer}"))
+    }
+}
+
+/// BusName from the process id of the application, as the bus knows it
+async fn from_pid(pid: u32, conn: &Connection) -> Result<Vec<(String, BusName<'static>)>> {
+    let roots = registered_roots(conn).await?;
+    let dbus = DBusProxy::new(conn).await?;
+    let pids = join_all(
+        roots
+            .iter()
+            .map(|(bus_name, _)| dbus.get_connection_unix_process_id(bus_name.clone())),
+    )
+    .await;
+
+    // An application may have more than one connection to the bus.
+    let mut matching_apps: Vec<(String, BusName<'static>)> = Vec::new();
+    for ((bus_name, acc_proxy), app_pid) in roots.into_iter().zip(pids) {
+        match app_pid {
+            Ok(app_pid) if app_pid == pid => {}
+            Ok(_) => continue,
+            Err(e) => {
+                warn!("{bus_name} returned an error getting its process id: {e}");
+                continue;
+            }
+        }
+        let name = acc_proxy.name().await.unwrap_or_default();
+        matching_apps.push((name, bus_name));
+    }
+
+    if matching_apps.is_empty() {
+        return Err(Failure::not_found(format!(
+            "No application found with process id: {pid}"
+        )));
+    }
+    Ok(matching_apps)
+}
+
+/// BusNames of all applications with a bus name or application name that `matches`,
+/// without asking
+async fn from_pattern(
+    pattern: &str,
+    matches: impl Fn(&str) -> bool,
+    conn: &Connection,
+) -> Result<Vec<(String, BusName<'static>)>> {
+    let roots = registered_roots(conn).await?;
+    let dbus = DBusProxy::new(conn).await?;
+
+    // Applications are registered by their unique names; their well-known names, such
+    // as `org.gnome.gedit`, are found through their owners.
+    let well_known_names: Vec<_> = dbus
+        .list_names()
+        .await?
+        .into_iter()
+        .filter(|name| !name.as_str().starts_with(':'))
+        .collect();
+    let owners = join_all(
+        well_known_names
+            .iter()
+            .map(|name| dbus.get_name_owner(name.inner().clone())),
+    )
+    .await;
+    let well_known: Vec<(String, String)> = well_known_names
+        .iter()
+        .zip(owners)
+        .filter_map(|(name, owner)| Some((owner.ok()?.to_string(), name.to_string())))
+        .collect();
+
+    let names = join_all(roots.iter().map(|(_, acc_proxy)| acc_proxy.name())).await;
+    let mut matching_apps: Vec<(String, BusName<'static>)> = Vec::new();
+    for ((bus_name, _), name) in roots.into_iter().zip(names) {
+        let unique_name = bus_name.to_string();
+        let name = name.unwrap_or_default();
+
+        let is_match = matches(&unique_name)
+            || (!name.is_empty() && matches(&name))
+            || well_known
+                .iter()
+                .any(|(owner, well_known)| *owner == unique_name && matches(well_known));
+        if is_match {
+            matching_apps.push((name, bus_name));
+        }
+    }
+
+    if matching_apps.is_empty() {
+        return Err(Failure::not_found(format!(
+            "No application found matching: {pattern}"
+        )));
+    }
+    Ok(matching_apps)
+}
+
+/// Whether `text` matches the glob `pattern`, in which `*` is any text and `?` any
+/// character
+fn glob_matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    // Where the last `*` is in the pattern, and what of the text it takes up to.
+    let mut star: Option<(usize, usize)> = None;
+    let (mut p, mut t) = (0, 0);
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                // Let the last `*` take one more character.
+                Some((star_p, star_t)) => {
+                    star = Some((star_p, star_t + 1));
+                    p = star_p + 1;
+                    t = star_t + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// BusName from the title of one of the application's top-level windows
+async fn from_window(title: &str, conn: &Connection) -> Result<Vec<(String, BusName<'static>)>> {
+    let mut roots = registered_roots(conn).await?;
+    // most recently entered apps first, as for application names
+    roots.reverse();
+    let title = title.to_lowercase();
+    let owned = join_all(
+        roots
+            .iter()
+            .map(|(_, acc_proxy)| owns_window(acc_proxy, &title, conn)),
+    )
+    .await;
+
+    let mut matching_apps: Vec<(String, BusName<'static>)> = Vec::new();
+    for ((bus_name, acc_proxy), owns_window) in roots.into_iter().zip(owned) {
+        if owns_window {
+            let name = acc_proxy.name().await.unwrap_or_default();
+            matching_apps.push((name, bus_name));
+        }
+    }
+
+    if matching_apps.is_empty() {
+        return Err(Failure::not_found(format!(
+            "No application found with a window titled: {title}"
+        )));
+    }
+    match pick_numbered(&format!("window {title:?}"), &matching_apps)? {
+        Some(app) => Ok(vec![app]),
+        None => Ok(matching_apps),
+    }
+}
+
+/// Whether the application of `root` has a top-level window with `title`, in lower case,
+/// in its title
+async fn owns_window(root: &AccessibleProxy<'_>, title: &str, conn: &Connection) -> bool {
+    for window in root.get_children().await.unwrap_or_default() {
+        let Ok(window) = window.into_accessible_proxy(conn).await else {
+            continue;
+        };
+        let is_window = window
+            .get_role()
+            .await
+            .is_ok_and(|role| WINDOW_ROLES.contains(&role));
+        if is_window
+            && window
+                .name()
+                .await
+                .is_ok_and(|name| name.to_lowercase().contains(title))
+        {
+            return true;
+        }
+    }
+    false
+}
+
+/// BusName from application name
+async fn from_app_name(
+    sought_after: String,
+    conn: &Connection,
+) -> Result<Vec<(String, BusName<'static>)>> {
+    let mut roots = registered_roots(conn).await?;
+    // get apps in reverse order - most recently entered apps first
+    roots.reverse();
+    // Ask all applications for their names at once, rather than waiting for each in turn.
+    let names = join_all(roots.iter().map(|(_, acc_proxy)| acc_proxy.name())).await;
+
+    // We might find multiple applications with the same name, so we want to ask the user about each
+    // of them. We will store the matching applications here.
+    let mut candidates: Vec<(Match, String, BusName<'static>)> = Vec::new();
+
+    for ((bus_name, _), name) in roots.into_iter().zip(names) {
+        let name = match name {
+            Ok(name) => name,
+            Err(e) => {
+                warn!("{:?} returned an error getting name: {e}", &bus_name);
+                continue;
+            }
+        };
+
+        let quality = match (
+            name == sought_after,
+            name.to_lowercase() == sought_after.to_lowercase(),
+            name.to_lowercase().contains(&sought_after.to_lowercase()),
+        ) {
+            (true, _, _) => Match::Perfect,
+            (false, true, _) => Match::CaseInsensitive,
+            (false, false, true) => Match::Partial,
+            // No match
+            (false, false, false) => continue,
+        };
+        candidates.push((quality, name, bus_name));
+    }
+
+    // A stable sort, so the most recently started of equally good matches comes first.
+    candidates.sort_by_key(|(quality, ..)| *quality);
+    let listed: Vec<(String, BusName<'static>)> = candidates
+        .iter()
+        .map(|(_, name, bus_name)| (name.clone(), bus_name.clone()))
+        .collect();
+    if let Some(app) = pick_numbered(&sought_after, &listed)? {
+        return Ok(vec![app]);
+    }
+
+    let matching = MATCHING.get().copied().unwrap_or(Matching::Ask);
+    let mut matching_apps: Vec<(String, BusName<'static>)> = Vec::new();
+
+    for (quality, name, bus_name) in candidates {
+        match (quality, matching) {
+            (Match::Perfect, _) | (_, Matching::All) => matching_apps.push((name, bus_name)),
+
+            (_, Matching::Exact) => {
+                return Err(Failure::not_found(format!(
+                    "Sought {sought_after}, which only loosely matches application {name}: \
+                     accept loose matches with --yes, --first or --index"
+                )));
+            }
+
+            // Case-insensitive match
+            (Match::CaseInsensitive, _) => {
+                println!("Sought {sought_after}, found application: {name}");
+
+                if get_user_yn_response("Would you like to add this application?")? {
+                    matching_apps.push((name, bus_name));
+                }
+            }
+
+            // Case-insensitive partial match
+            (Match::Partial, _) => {
+                println!("Sought {sought_after}, partially matches application: {name}");
+                if get_user_yn_response("Would you like to add this application?")? {
+                    matching_apps.push((name, bus_name));
+                }
+            }
+        };
+    }
+
+    if matching_apps.is_empty() {
+        return Err(Failure::not_found(format!(
+            "No application found with name: {sought_after}"
+        )));
+    }
+    Ok(matching_apps)
+}
+
+/// Whether the run is in machine mode, where standard output has only what was asked for.
+fn machine() -> bool {
+    MACHINE.get().copied().unwrap_or(false)
+}
+
+/// Whether commands only print what they would do to applications.
+fn dry_run() -> bool {
+    DRY_RUN.get().copied().unwrap_or(false)
+}
+
+/// Whether to color output: on a terminal, unless turned off or in machine mode.
+fn color(no_color: bool) -> bool {
+    !no_color && !machine() && config::get().color != Some(false) && std::io::stdout().is_terminal()
+}
+
+/// The application of `apps`, which match `sought`, that `--first` or `--index` pick.
+/// Without either, several applications are listed with their numbers for `--index`.
+fn pick_numbered(
+    sought: &str,
+    apps: &[(String, BusName<'static>)],
+) -> Result<Option<(String, BusName<'static>)>> {
+    match MATCHING.get().copied().unwrap_or(Matching::Ask) {
+        Matching::First => Ok(apps.first().cloned()),
+        Matching::Index(index) => apps.get(index - 1).cloned().map(Some).ok_or_else(|| {
+            Failure::not_found(format!(
+                "There is no application {index} matching {sought}, only {}",
+                apps.len()
+            ))
+        }),
+        _ => {
+            if apps.len() > 1 {
+                eprintln!("Applications matching {sought}, to select one with --index:");
+                for (number, (name, bus_name)) in apps.iter().enumerate() {
+                    eprintln!("  {}. {name} ({bus_name})", number + 1);
+                }
+            }
+            Ok(None)
+        }
+    }
+}
+
+/// The applications registered on the accessibility bus, by name and bus name.
+async fn registry_applications(conn: &Connection) -> Result<Vec<(String, BusName<'static>)>> {
+    let roots = registered_roots(conn).await?;
+    let names = join_all(roots.iter().map(|(_, acc_proxy)| acc_proxy.name())).await;
+    Ok(roots
+        .into_iter()
+        .zip(names)
+        .map(|((bus_name, _), name)| (name.unwrap_or_default(), bus_name))
+        .collect())
+}
+
+/// The bus names and root accessible objects of the applications registered on the
+/// accessibility bus, in the order they registered. Those that cannot be reached are left
+/// out with a warning.
+async fn registered_roots(
+    conn: &Connection,
+) -> Result<Vec<(BusName<'static>, AccessibleProxy<'_>)>> {
+    let registry = get_registry_accessible(conn).await?;
+    let roots = join_all(
+        registry
+            .get_children()
+            .await?
+            .into_iter()
+            .map(|app| async move {
+                let bus_name: BusName<'static> = app.name.to_owned().into();
+                match app.into_accessible_proxy(conn).await {
+                    Ok(acc_proxy) => Some((bus_name, acc_proxy)),
+                    Err(e) => {
+                        warn!("{bus_name} could not convert to accessible proxy: {e}");
+                        None
+                    }
+                }
+            }),
+    )
+    .await;
+    Ok(roots.into_iter().flatten().collect())
+}
+
+/// Print a section for each application, with its properties and, if `print_tree`, its
+/// tree, so those of one application are not lost among those of the others
+async fn print_application_sections(
+    conn: &Connection,
+    apps: &[(String, BusName<'static>)],
+    properties: &Properties,
+    print_tree: bool,
+) -> Result<()> {
+    for app in apps {
+        if interrupt::interrupted() {
+            break;
+        }
+        let (name, bus_name) = app;
+        let name = if name.is_empty() {
+            "--- No name ---"
+        } else {
+            name
+        };
+        banner!("==> {name} ({bus_name}) <==");
+        banner!();
+        print_properties_of_apps(conn, std::slice::from_ref(app), properties).await?;
+        if print_tree {
+            self::print_tree(conn, std::slice::from_ref(app)).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Print the accessible properties of the root objects of the applications
+async fn print_properties_of_apps(
+    conn: &Connection,
+    apps: &[(String, BusName<'static>)],
+    properties: &Properties,
+) -> Result<()> {
+    for (name, bus_name) in apps {
+        let acc_proxy = get_root_accessible(bus_name.clone(), conn).await?;
+        banner!("Application: {name} ({bus_name}) - Accessible Properties of its root object:");
+        table_of_accessible_properties(&acc_proxy, properties).await?;
+        println!();
+    }
+    Ok(())
+}
+
+/// Print the accessible properties of the given `AccessibleProxy`
+async fn table_of_accessible_properties(
+    acc_proxy: &AccessibleProxy<'_>,
+    properties: &Properties,
+) -> Result<()> {
+    let mut props_data = Vec::new();
+    for property in properties.iter() {
+        props_data.push((property.label(), property.fetch(acc_proxy).await));
+    }
+    print_properties(&props_data);
+
+    Ok(())
+}
+
+/// Print labels and values as a two-column table
+fn print_properties(props_data: &[(&str, String)]) {
+    // Determine maximum widths for each column
+    let max_label_width = props_data
+        .iter()
+        .map(|(label, _)| label.len())
+        .max()
+        .unwrap_or(0);
+    let max_value_width = props_data
+        .iter()
+        .map(|(_, value)| value.len())
+        .max()
+        .unwrap_or(0);
+
+    // Create the horizontal border string
+    let label_border_segment = "-".repeat(max_label_width + 2); // +2 for " " padding
+    let value_border_segment = "-".repeat(max_value_width + 2); // +2 for " " padding
+    let horizontal_border = format!("+{label_border_segment}+{value_border_segment}+");
+
+    // Print the top border
+    println!("{horizontal_border}");
+
+    // Print property rows
+    for (label, value) in props_data {
+        println!("| {label:<max_label_width$} | {value:<max_value_width$} |");
+    }
+
+    // Print the bottom border
+    println!("{horizontal_border}");
+}
+
+/// Print rows with a header row as a table, in the style of the properties table
+fn print_table(header: &[&str], rows: &[Vec<String>]) {
+    // Determine maximum widths for each column
+    let widths: Vec<usize> = header
+        .iter()
+        .enumerate()
+        .map(|(i, label)| {
+            rows.iter()
+                .filter_map(|row| row.get(i))
+                .map(String::len)
+                .chain(std::iter::once(label.len()))
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let segments: Vec<String> = widths.iter().map(|w| "-".repeat(w + 2)).collect();
+    let horizontal_border = format!("+{}+", segments.join("+"));
+
+    let print_row = |cells: Vec<&str>| {
+        let cells: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!(" {cell:<width$} "))
+            .collect();
+        println!("|{}|", cells.join("|"));
+    };
+
+    println!("{horizontal_border}");
+    print_row(header.to_vec());
+    println!("{horizontal_border}");
+    for row in rows {
+        print_row(row.iter().map(String::as_str).collect());
+    }
+    println!("{horizontal_border}");
+}
+
+/// Print the properties of the root of a snapshot, and with `print_tree` its tree
+fn print_snapshot(path: &Path, print_tree: bool) -> Result<()> {
+    let snapshot = snapshot::load(path)?;
+    print_snapshot_properties(path, &snapshot, &snapshot.root, &snapshot.path);
+    if print_tree {
+        print_snapshot_tree(path, &snapshot.root)?;
+    }
+    Ok(())
+}
+
+/// Print the properties of `node`, at index path `node_path`, of a snapshot read from `file`
+fn print_snapshot_properties(
+    file: &Path,
+    snapshot: &snapshot::Snapshot,
+    node: &snapshot::SnapshotNode,
+    node_path: &str,
+) {
+    let or_empty = |value: &str| {
+        if value.is_empty() {
+            "--- No value ---".to_string()
+        } else {
+            value.to_string()
+        }
+    };
+
+    if node_path == snapshot.path {
+        banner!(
+            "Snapshot: {} of {} - Accessible Properties of its root object:",
+            file.display(),
+            snapshot.app
+        );
+    } else {
+        banner!(
+            "Snapshot: {} of {} - Accessible Properties of {} at {}:",
+            file.display(),
+            snapshot.app,
+            node.description(),
+            audit::location(node_path)
+        );
+    }
+    print_properties(&[
+        ("Name:", or_empty(&node.name)),
+        ("Role:", node.role.clone()),
+        ("Description:", or_empty(&node.description)),
+        ("Locale:", or_empty(&node.locale)),
+        ("Accessible ID:", or_empty(&node.accessible_id)),
+        ("Child count:", node.children.len().to_string()),
+        ("Path:", or_empty(node_path)),
+    ]);
+    if snapshot.partial {
+        warn!(
+            "{} is partial: taking it was interrupted, so nodes are missing",
+            file.display()
+        );
+    }
+    if let Some(metadata) = &snapshot.metadata {
+        println!();
+        print_properties(&[
+            (
+                "Toolkit:",
+                or_empty(format!("{} {}", metadata.toolkit, metadata.toolkit_version).trim()),
+            ),
+            ("AT-SPI version:", or_empty(&metadata.atspi_version)),
+            ("Desktop:", or_empty(&metadata.desktop)),
+            ("Session type:", or_empty(&metadata.session_type)),
+            ("Captured at:", or_empty(&metadata.captured_at)),
+            (
+                "Captured by:",
+                format!(
+                    "a11y-app {}, atspi {}",
+                    metadata.a11y_app_version, metadata.atspi_crate_version
+                ),
+            ),
+            (
+                "Host name:",
+                or_empty(metadata.hostname.as_deref().unwrap_or_default()),
+            ),
+        ]);
+    }
+    println!();
+}
+
+/// Print the tree below `node` of a snapshot read from `file`
+fn print_snapshot_tree(file: &Path, node: &snapshot::SnapshotNode) -> Result<()> {
+    banner!("Snapshot: {} - Tree of Accessible Objects:", file.display());
+    render::print(&A11yNode::from_snapshot(node))?;
+    println!();
+    Ok(())
+}
+
+// Print application(s) tree
+async fn print_tree(conn: &Connection, apps: &[(String, BusName<'static>)]) -> Result<()> {
+    for app in apps {
+        if interrupt::interrupted() {
+            break;
+        }
+        let (name, bus_name) = app;
+        let acc_proxy = get_root_accessible(bus_name.clone(), conn).await?;
+        banner!("Application: {name} ({bus_name}) - Tree of Accessible Objects:");
+
+        let tree = tree::configured().build(acc_proxy).await?;
+
+        render::print(&tree)?;
+        println!();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn star_matches_any_text() {
+        assert!(glob_matches("gedit*", "gedit"));
+        assert!(glob_matches("gedit*", "gedit-preferences"));
+        assert!(glob_matches("*edit", "gedit"));
+        assert!(glob_matches("g*t", "gedit"));
+        assert!(glob_matches("*", ""));
+        assert!(glob_matches("**", "gedit"));
+        assert!(!glob_matches("gedit*", "xgedit"));
+        assert!(!glob_matches("*edit", "editor"));
+    }
+
+    #[test]
+    fn question_mark_matches_one_character() {
+        assert!(glob_matches("g?dit", "gedit"));
+        assert!(glob_matches("gedi?", "gedit"));
+        assert!(!glob_matches("gedit?", "gedit"));
+        assert!(!glob_matches("g?dit", "gdit"));
+        assert!(glob_matches("?ö*", "Zöe"));
+    }
+
+    #[test]
+    fn star_backtracks_to_later_occurrences() {
+        assert!(glob_matches("*ab", "aab"));
+        assert!(glob_matches("a*b*c", "abxbyc"));
+        assert!(!glob_matches("a*b*c", "abxbyd"));
+        assert!(!glob_matches("", "gedit"));
+        assert!(glob_matches("", ""));
+    }
+}
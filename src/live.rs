@@ -0,0 +1,228 @@
+//! A tree of accessible objects that is built once and then kept current
+//! by applying `object:children-changed` and `object:property-change` events.
+
+use crate::{
+    exit, interrupt, render,
+    retry::retry,
+    watch::{self, EventFilter},
+    A11yNode, Result,
+};
+use atspi::{
+    events::object::Property, AccessibilityConnection, Event, EventProperties, ObjectEvents,
+    ObjectRef, Operation, Role,
+};
+use futures::StreamExt;
+use std::{collections::HashMap, io::IsTerminal, time::Duration};
+use tracing::warn;
+use zbus::{fdo::DBusProxy, names::BusName, zvariant::ObjectPath, Connection};
+
+/// The events that change the shape or the roles of the tree.
+const CHANGE_EVENTS: &[&str] = &["object:children-changed", "object:property-change"];
+
+struct LiveNode {
+    role: Option<Role>,
+    children: Vec<ObjectRef>,
+}
+
+/// The in-memory model of one application's tree.
+struct LiveTree {
+    name: String,
+    root: ObjectRef,
+    nodes: HashMap<ObjectRef, LiveNode>,
+}
+
+impl LiveTree {
+    async fn new(conn: &Connection, name: String, root: ObjectRef) -> LiveTree {
+        let mut tree = LiveTree {
+            name,
+            root: root.clone(),
+            nodes: HashMap::new(),
+        };
+        tree.fetch(conn, root).await;
+        tree
+    }
+
+    /// Fetch the subtree rooted at `object` from the application.
+    async fn fetch(&mut self, conn: &Connection, object: ObjectRef) {
+        let mut stack = vec![object];
+
+        while let Some(object) = stack.pop() {
+            let proxy = match watch::accessible_from_object_ref(conn, object.clone()).await {
+                Ok(proxy) => proxy,
+                Err(e) => {
+                    warn!("could not reach {}: {e}", object.path.as_str());
+                    exit::unreached();
+                    continue;
+                }
+            };
+
+            let role = retry(|| proxy.get_role()).await.ok();
+            let children = retry(|| proxy.get_children()).await.unwrap_or_else(|e| {
+                warn!(
+                    "Error getting children of {}: {e} -- continuing with next node.",
+                    object.path.as_str()
+                );
+                exit::unreached();
+                Vec::new()
+            });
+
+            stack.extend(children.iter().cloned());
+            self.nodes.insert(object, LiveNode { role, children });
+        }
+    }
+
+    /// Drop the subtree rooted at `object` from the model.
+    fn forget(&mut self, object: &ObjectRef) {
+        let mut stack = vec![object.clone()];
+        while let Some(object) = stack.pop() {
+            if let Some(node) = self.nodes.remove(&object) {
+                stack.extend(node.children);
+            }
+        }
+    }
+
+    /// Apply an event to the model, returns whether the tree changed.
+    async fn apply(&mut self, conn: &Connection, event: &Event) -> bool {
+        match event {
+            Event::Object(ObjectEvents::ChildrenChanged(e)) => {
+                let Some(parent) = self.nodes.get_mut(&e.item) else {
+                    return false;
+                };
+
+                match e.operation {
+                    Operation::Insert => {
+                        if parent.children.contains(&e.child) {
+                            return false;
+                        }
+                        let index = usize::try_from(e.index_in_parent)
+                            .unwrap_or(usize::MAX)
+                            .min(parent.children.len());
+                        parent.children.insert(index, e.child.clone());
+                        self.fetch(conn, e.child.clone()).await;
+                    }
+                    Operation::Delete => {
+                        let before = parent.children.len();
+                        parent.children.retain(|child| *child != e.child);
+                        if parent.children.len() == before {
+                            return false;
+                        }
+                        self.forget(&e.child);
+                    }
+                }
+                true
+            }
+            Event::Object(ObjectEvents::PropertyChange(e)) => {
+                match (&e.value, self.nodes.get_mut(&e.item)) {
+                    (Property::Role(role), Some(node)) => {
+                        node.role = Some(*role);
+                        true
+                    }
+                    _ => false,
+                }
+            }
+            _ => false,
+        }
+    }
+
+    /// Convert the model into the tree we print.
+    fn to_a11y_node(&self, object: &ObjectRef) -> A11yNode {
+        match self.nodes.get(object) {
+            Some(node) => A11yNode {
+                object: Some(object.clone()),
+                role: node.role,
+                children: node
+                    .children
+                    .iter()
+                    .map(|child| self.to_a11y_node(child))
+                    .collect(),
+                ..A11yNode::default()
+            },
+            None => A11yNode::default(),
+        }
+    }
+
+    fn print(&self) -> Result<()> {
+        banner!(
+            "Application: {} ({}) - Live tree of Accessible Objects ({} nodes):",
+            self.name,
+            self.root.name.as_str(),
+            self.nodes.len()
+        );
+        render::print(&self.to_a11y_node(&self.root))?;
+        println!();
+        Ok(())
+    }
+}
+
+/// Print the trees of the applications, then keep them current and re-render
+/// at most once per `interval` when they change.
+pub async fn live_trees(
+    a11y: &AccessibilityConnection,
+    apps: &[(String, BusName<'static>)],
+    interval: Duration,
+) -> Result<()> {
+    let conn = a11y.connection();
+    let dbus = DBusProxy::new(conn).await?;
+
+    let filters = CHANGE_EVENTS
+        .iter()
+        .map(|pattern| EventFilter::parse(pattern))
+        .collect::<Result<Vec<_>>>()?;
+
+    // Subscribe before building the trees, so no change slips through in between.
+    watch::subscribe(a11y, &filters).await?;
+    let events = a11y.event_stream();
+    futures::pin_mut!(events);
+
+    let mut trees = Vec::with_capacity(apps.len());
+    for (name, bus_name) in apps {
+        let unique_name = watch::unique_name(&dbus, bus_name.clone()).await?;
+        let root = ObjectRef {
+            name: unique_name,
+            path: ObjectPath::from_static_str_unchecked(crate::ACCESSIBLE_ROOT).into(),
+        };
+        trees.push(LiveTree::new(conn, name.clone(), root).await);
+    }
+
+    let mut dirty = true;
+    let mut ticker = tokio::time::interval(interval);
+
+    loop {
+        tokio::select! {
+            _ = interrupt::cancelled() => break,
+            event = events.next() => {
+                let Some(event) = event else {
+                    break;
+                };
+                let event = match event {
+                    Ok(event) => event,
+                    Err(e) => {
+                        warn!("could not parse event: {e}");
+                        continue;
+                    }
+                };
+
+                for tree in &mut trees {
+                    if tree.root.name == event.sender() {
+                        dirty |= tree.apply(conn, &event).await;
+                    }
+                }
+            }
+            _ = ticker.tick() => {
+                if !dirty {
+                    continue;
+                }
+                // Redraw in place when a person is watching, append when piped.
+                if std::io::stdout().is_terminal() && !crate::machine() {
+                    print!("\x1b[2J\x1b[H");
+                }
+                for tree in &trees {
+                    tree.print()?;
+                }
+                dirty = false;
+            }
+        }
+    }
+
+    Ok(())
+}
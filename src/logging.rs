@@ -0,0 +1,55 @@
+//! Diagnostics on standard error: warnings by default, and with `-v` what a11y-app does,
+//! e.g. which nodes it could not reach while traversing a tree.
+
+use crate::ArgResult;
+use tracing::Level;
+
+/// How diagnostics are written.
+#[derive(Clone, Copy, Default)]
+pub(crate) enum LogFormat {
+    /// A line of text each.
+    #[default]
+    Text,
+    /// A JSON object each, for log collectors.
+    Json,
+}
+
+pub(crate) fn parse_log_format(value: &str) -> ArgResult<LogFormat> {
+    match value {
+        "text" => Ok(LogFormat::Text),
+        "json" => Ok(LogFormat::Json),
+        _ => Err(format!(
+            "Unknown log format: {value}, expected text or json"
+        )),
+    }
+}
+
+/// The most detailed level written: errors with `quiet`, else warnings, and a level more
+/// for each `-v`.
+fn level(verbose: u8, quiet: bool) -> Level {
+    if quiet {
+        return Level::ERROR;
+    }
+    match verbose {
+        0 => Level::WARN,
+        1 => Level::INFO,
+        2 => Level::DEBUG,
+        _ => Level::TRACE,
+    }
+}
+
+/// Write diagnostics to standard error for the rest of the run.
+pub(crate) fn init(verbose: u8, quiet: bool, format: LogFormat) -> ArgResult<()> {
+    if quiet && verbose > 0 {
+        return Err("Give -v or -q, not both".to_string());
+    }
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(level(verbose, quiet))
+        .with_writer(std::io::stderr)
+        .with_target(false);
+    match format {
+        LogFormat::Text => subscriber.without_time().try_init(),
+        LogFormat::Json => subscriber.json().try_init(),
+    }
+    .map_err(|e| format!("Could not set up diagnostics: {e}"))
+}
@@ -0,0 +1,191 @@
+//! Writing the manual page, generated from the command line definitions, so distributions
+//! can package it with the documentation of every command and option.
+
+use crate::Result;
+use argh::{ArgsInfo, CommandInfoWithArgs, FlagInfoKind, FromArgs, Optionality};
+use std::path::PathBuf;
+
+/// The section of the manual the page is in, for user commands.
+const SECTION: &str = "1";
+
+/// Write the manual page of a11y-app in roff, for `man`
+#[derive(FromArgs, ArgsInfo)]
+#[argh(
+    subcommand,
+    name = "man",
+    example = "a11y-app man --output a11y-app.1 && man ./a11y-app.1"
+)]
+pub struct ManArgs {
+    /// the file to write the page to (default: standard output)
+    #[argh(option)]
+    output: Option<PathBuf>,
+}
+
+/// Write the page of the command line interface `info`.
+pub fn man(info: &CommandInfoWithArgs, args: ManArgs) -> Result<()> {
+    let page = page(info);
+    match &args.output {
+        Some(path) => std::fs::write(path, page)
+            .map_err(|e| format!("Could not write {}: {e}", path.display()))?,
+        None => print!("{page}"),
+    }
+    Ok(())
+}
+
+/// The manual page of the command `info` and its subcommands.
+fn page(info: &CommandInfoWithArgs) -> String {
+    let name = env!("CARGO_PKG_NAME");
+    let mut page = vec![
+        format!(
+            ".TH {} {SECTION} \"\" \"{name} {}\" \"User Commands\"",
+            name.to_uppercase(),
+            env!("CARGO_PKG_VERSION")
+        ),
+        ".SH NAME".to_string(),
+        format!("{name} \\- {}", escape(info.description)),
+        ".SH SYNOPSIS".to_string(),
+        synopsis(name, info),
+        ".SH DESCRIPTION".to_string(),
+        escape(info.description),
+    ];
+    page.extend(notes(info));
+    page.extend(arguments(info));
+
+    if !info.commands.is_empty() {
+        page.push(".SH COMMANDS".to_string());
+        for subcommand in &info.commands {
+            let command = &subcommand.command;
+            page.push(format!(".SS \"{name} {}\"", subcommand.name));
+            page.push(synopsis(&format!("{name} {}", subcommand.name), command));
+            page.push(".PP".to_string());
+            page.push(escape(command.description));
+            page.extend(notes(command));
+            page.extend(arguments(command));
+        }
+    }
+
+    let examples: Vec<&str> = std::iter::once(info)
+        .chain(info.commands.iter().map(|subcommand| &subcommand.command))
+        .flat_map(|command| command.examples.iter().copied())
+        .collect();
+    if !examples.is_empty() {
+        page.push(".SH EXAMPLES".to_string());
+        for example in examples {
+            page.push(".PP".to_string());
+            page.push(".nf".to_string());
+            page.push(escape(example));
+            page.push(".fi".to_string());
+        }
+    }
+
+    page.push(".SH \"EXIT STATUS\"".to_string());
+    page.push(".TP".to_string());
+    page.push("\\fB0\\fR".to_string());
+    page.push("Success.".to_string());
+    for error_code in info.error_codes {
+        page.push(".TP".to_string());
+        page.push(format!("\\fB{}\\fR", error_code.code));
+        page.push(escape(error_code.description));
+    }
+
+    page.push(".SH FILES".to_string());
+    page.push(".TP".to_string());
+    page.push("\\fI~/.config/a11y\\-app/config.toml\\fR".to_string());
+    page.push("Defaults for options, see the README.".to_string());
+
+    let mut page = page.join("\n");
+    page.push('\n');
+    page
+}
+
+/// The usage line of `command`, invoked as `invocation`.
+fn synopsis(invocation: &str, command: &CommandInfoWithArgs) -> String {
+    let mut parts = vec![format!("\\fB{}\\fR", escape(invocation))];
+    for flag in command.flags.iter().filter(|flag| !flag.hidden) {
+        if flag.long == "--help" {
+            continue;
+        }
+        let flag_name = match flag.kind {
+            FlagInfoKind::Switch => format!("\\fB{}\\fR", escape(flag.long)),
+            FlagInfoKind::Option { arg_name } => {
+                format!("\\fB{}\\fR \\fI{}\\fR", escape(flag.long), escape(arg_name))
+            }
+        };
+        parts.push(match flag.optionality {
+            Optionality::Required => flag_name,
+            Optionality::Repeating | Optionality::Greedy => format!("[{flag_name}...]"),
+            Optionality::Optional => format!("[{flag_name}]"),
+        });
+    }
+    for positional in command
+        .positionals
+        .iter()
+        .filter(|positional| !positional.hidden)
+    {
+        let positional_name = format!("\\fI{}\\fR", escape(positional.name));
+        parts.push(match positional.optionality {
+            Optionality::Required => positional_name,
+            Optionality::Repeating | Optionality::Greedy => format!("[{positional_name}...]"),
+            Optionality::Optional => format!("[{positional_name}]"),
+        });
+    }
+    if !command.commands.is_empty() {
+        parts.push("[\\fIcommand\\fR]".to_string());
+    }
+    parts.join(" ")
+}
+
+/// The positional arguments and options of `command`, as tagged paragraphs.
+fn arguments(command: &CommandInfoWithArgs) -> Vec<String> {
+    let mut lines = Vec::new();
+    for positional in command
+        .positionals
+        .iter()
+        .filter(|positional| !positional.hidden)
+    {
+        lines.push(".TP".to_string());
+        lines.push(format!("\\fI{}\\fR", escape(positional.name)));
+        lines.push(escape(positional.description));
+    }
+    for flag in command.flags.iter().filter(|flag| !flag.hidden) {
+        lines.push(".TP".to_string());
+        let long = match flag.kind {
+            FlagInfoKind::Switch => format!("\\fB{}\\fR", escape(flag.long)),
+            FlagInfoKind::Option { arg_name } => {
+                format!("\\fB{}\\fR \\fI{}\\fR", escape(flag.long), escape(arg_name))
+            }
+        };
+        lines.push(match flag.short {
+            Some(short) => format!("\\fB\\-{short}\\fR, {long}"),
+            None => long,
+        });
+        lines.push(escape(flag.description));
+    }
+    lines
+}
+
+/// The notes of `command`, as paragraphs.
+fn notes(command: &CommandInfoWithArgs) -> Vec<String> {
+    command
+        .notes
+        .iter()
+        .flat_map(|note| [".PP".to_string(), escape(note)])
+        .collect()
+}
+
+/// `text` with the characters roff treats specially escaped, so it is printed as is.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\e")
+        .replace('-', "\\-")
+        .lines()
+        .map(|line| {
+            // A line starting with a period or an apostrophe would be a request.
+            if line.starts_with('.') || line.starts_with('\'') {
+                format!("\\&{line}")
+            } else {
+                line.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
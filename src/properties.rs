@@ -0,0 +1,202 @@
+//! The properties the table of a node's accessible properties shows, as `--props` selects
+//! them, and as other programs can fetch them. Each is fetched with its own call, so
+//! leaving some out saves their round trips to applications that are slow to answer.
+
+use crate::{retry::retry, ArgResult};
+use atspi::proxy::accessible::AccessibleProxy;
+
+/// A property of an accessible object, as shown in the table.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Property {
+    Name,
+    Description,
+    Locale,
+    AccessibleId,
+    ChildCount,
+    Parent,
+    HelpText,
+    Role,
+    States,
+    Interfaces,
+    Attributes,
+    IndexInParent,
+}
+
+impl Property {
+    fn name(self) -> &'static str {
+        match self {
+            Property::Name => "name",
+            Property::Description => "description",
+            Property::Locale => "locale",
+            Property::AccessibleId => "accessible-id",
+            Property::ChildCount => "child-count",
+            Property::Parent => "parent",
+            Property::HelpText => "help-text",
+            Property::Role => "role",
+            Property::States => "states",
+            Property::Interfaces => "interfaces",
+            Property::Attributes => "attributes",
+            Property::IndexInParent => "index-in-parent",
+        }
+    }
+
+    /// The label of the property in the table.
+    pub fn label(self) -> &'static str {
+        match self {
+            Property::Name => "Name:",
+            Property::Description => "Description:",
+            Property::Locale => "Locale:",
+            Property::AccessibleId => "Accessible ID:",
+            Property::ChildCount => "Child count:",
+            Property::Parent => "Parent:",
+            Property::HelpText => "Help text:",
+            Property::Role => "Role:",
+            Property::States => "States:",
+            Property::Interfaces => "Interfaces:",
+            Property::Attributes => "Attributes:",
+            Property::IndexInParent => "Index in parent:",
+        }
+    }
+
+    /// The value of the property of `proxy`, as shown in the table. Failures are shown
+    /// as the value, e.g. `Error: ...`, so one property does not hide the others.
+    pub async fn fetch(self, proxy: &AccessibleProxy<'_>) -> String {
+        let text = |res: zbus::Result<String>| match res {
+            Ok(text) if text.is_empty() => EMPTY.to_string(),
+            Ok(text) => text,
+            Err(e) => format!("Error: {e}"),
+        };
+        let list = |items: Vec<String>| {
+            if items.is_empty() {
+                EMPTY.to_string()
+            } else {
+                items.join(", ")
+            }
+        };
+
+        match self {
+            Property::Name => text(retry(|| proxy.name()).await),
+            Property::Description => text(retry(|| proxy.description()).await),
+            Property::Locale => text(retry(|| proxy.locale()).await),
+            Property::AccessibleId => text(retry(|| proxy.accessible_id()).await),
+            Property::HelpText => text(retry(|| proxy.help_text()).await),
+            Property::Role => text(retry(|| proxy.get_role_name()).await),
+            Property::ChildCount => match retry(|| proxy.child_count()).await {
+                Ok(child_count) => child_count.to_string(),
+                Err(e) => format!("Error: {e}"),
+            },
+            Property::Parent => match retry(|| proxy.parent()).await {
+                Ok(parent) => format!("{parent:?}"),
+                Err(e) => format!("Error: {e}"),
+            },
+            Property::IndexInParent => match retry(|| proxy.get_index_in_parent()).await {
+                Ok(index) => index.to_string(),
+                Err(e) => format!("Error: {e}"),
+            },
+            Property::States => match retry(|| proxy.get_state()).await {
+                Ok(states) => list(
+                    states
+                        .iter()
+                        .map(|state| state.to_static_str().to_string())
+                        .collect(),
+                ),
+                Err(e) => format!("Error: {e}"),
+            },
+            Property::Interfaces => match retry(|| proxy.get_interfaces()).await {
+                Ok(interfaces) => list(
+                    interfaces
+                        .iter()
+                        .map(|interface| format!("{interface:?}"))
+                        .collect(),
+                ),
+                Err(e) => format!("Error: {e}"),
+            },
+            Property::Attributes => match retry(|| proxy.get_attributes()).await {
+                Ok(attributes) => {
+                    let mut attributes: Vec<String> = attributes
+                        .into_iter()
+                        .map(|(key, value)| format!("{key}={value}"))
+                        .collect();
+                    attributes.sort();
+                    list(attributes)
+                }
+                Err(e) => format!("Error: {e}"),
+            },
+        }
+    }
+}
+
+/// What the table shows for a property without a value.
+const EMPTY: &str = "--- No value ---";
+
+/// All properties, in the order of the table.
+pub const PROPERTIES: &[Property] = &[
+    Property::Name,
+    Property::Description,
+    Property::Locale,
+    Property::AccessibleId,
+    Property::ChildCount,
+    Property::Parent,
+    Property::HelpText,
+    Property::Role,
+    Property::States,
+    Property::Interfaces,
+    Property::Attributes,
+    Property::IndexInParent,
+];
+
+/// The properties shown by default, those the table has always had.
+const DEFAULT_PROPERTIES: &[Property] = &[
+    Property::Name,
+    Property::Description,
+    Property::Locale,
+    Property::AccessibleId,
+    Property::ChildCount,
+    Property::Parent,
+    Property::HelpText,
+];
+
+/// The properties to show, in the order given.
+#[derive(Clone)]
+pub(crate) struct Properties(Vec<Property>);
+
+impl Default for Properties {
+    fn default() -> Self {
+        Properties(DEFAULT_PROPERTIES.to_vec())
+    }
+}
+
+impl Properties {
+    pub(crate) fn iter(&self) -> impl Iterator<Item = Property> + '_ {
+        self.0.iter().copied()
+    }
+}
+
+pub(crate) fn parse_properties(value: &str) -> ArgResult<Properties> {
+    let mut properties = Vec::new();
+    for name in value.split(',').map(str::trim) {
+        let named: &[Property] = match name {
+            "all" => PROPERTIES,
+            "default" => DEFAULT_PROPERTIES,
+            _ => match PROPERTIES.iter().find(|property| property.name() == name) {
+                Some(property) => std::slice::from_ref(property),
+                None => {
+                    return Err(format!(
+                        "Unknown property: {name}, expected {}, default or all",
+                        PROPERTIES
+                            .iter()
+                            .map(|property| property.name())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ))
+                }
+            },
+        };
+        for property in named {
+            if !properties.contains(property) {
+                properties.push(*property);
+            }
+        }
+    }
+    Ok(Properties(properties))
+}
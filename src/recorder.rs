@@ -0,0 +1,312 @@
+//! Recording a script for `run` while a human drives the application.
+//!
+//! Clicks become `find` (or `path`) and `action` steps on the node that reacted to
+//! them, keyboard input becomes `type` and `key` steps, focus moving to another node
+//! becomes a `find` and an `assert`, and windows becoming active become `wait-for` steps.
+
+use crate::{
+    keys::{self, KeyEvent},
+    target::{self, Selected},
+    watch::{self, EventFilter},
+    Result,
+};
+use argh::{ArgsInfo, FromArgs};
+use atspi::{
+    proxy::{accessible::AccessibleProxy, proxy_ext::ProxyExt},
+    AccessibilityConnection, Event, EventProperties, MouseEvents, ObjectEvents, State,
+    WindowEvents,
+};
+use futures::StreamExt;
+use std::{
+    fs::File,
+    io::{BufRead, Write},
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+use zbus::Connection;
+
+/// The actions a click is recorded as, in order of preference.
+const CLICK_ACTIONS: &[&str] = &["click", "press", "activate", "toggle", "jump"];
+
+/// State changes that show which node reacted to a click.
+const CLICK_STATES: &[State] = &[
+    State::Focused,
+    State::Checked,
+    State::Pressed,
+    State::Selected,
+    State::Expanded,
+];
+
+/// Record a script for `run` from what you do in an application, until Enter is pressed
+#[derive(FromArgs, ArgsInfo)]
+#[argh(subcommand, name = "record-script")]
+pub struct RecordScriptArgs {
+    /// the application, by bus name or application name
+    #[argh(positional)]
+    app: String,
+
+    /// the script to write
+    #[argh(positional)]
+    output: PathBuf,
+
+    /// milliseconds after a click in which a state change counts as caused by it (default: 500)
+    #[argh(option, default = "500")]
+    click_window: u64,
+}
+
+/// The script being written, with the keyboard input not yet written as a step.
+struct Recorder {
+    file: File,
+    root: Selected,
+    steps: usize,
+    /// Text typed since the last step.
+    typed: String,
+    /// Keys without a character pressed since the last step.
+    keys: Vec<String>,
+}
+
+impl Recorder {
+    /// Write a step, given as the YAML of a list item without the dash.
+    fn step(&mut self, step: &str) -> Result<()> {
+        self.steps += 1;
+        println!("[{}] {step}", self.steps);
+        writeln!(self.file, "  - {step}")?;
+        self.file.flush()?;
+        Ok(())
+    }
+
+    fn comment(&mut self, comment: &str) -> Result<()> {
+        println!("# {comment}");
+        writeln!(self.file, "  # {comment}")?;
+        Ok(())
+    }
+
+    /// Write the pending keyboard input as a `type` or `key` step.
+    fn flush_input(&mut self) -> Result<()> {
+        if !self.typed.is_empty() {
+            let typed = std::mem::take(&mut self.typed);
+            self.step(&format!("type: {}", scalar(&typed)?))?;
+        }
+        if !self.keys.is_empty() {
+            let keys = std::mem::take(&mut self.keys)
+                .iter()
+                .map(|key| scalar(key))
+                .collect::<Result<Vec<_>>>()?;
+            match keys.as_slice() {
+                [key] => self.step(&format!("key: {key}"))?,
+                keys => self.step(&format!("key: [{}]", keys.join(", ")))?,
+            }
+        }
+        Ok(())
+    }
+
+    fn key(&mut self, key: &KeyEvent) -> Result<()> {
+        if !key.pressed || key.is_modifier() {
+            return Ok(());
+        }
+
+        let is_text = key.string.chars().count() == 1 && !key.string.chars().any(char::is_control);
+        if is_text && !key.is_shortcut() {
+            if !self.keys.is_empty() {
+                self.flush_input()?;
+            }
+            self.typed.push_str(&key.string);
+            return Ok(());
+        }
+
+        match keys::key_name(key.keysym) {
+            Some(name) if !key.is_shortcut() => {
+                if !self.typed.is_empty() {
+                    self.flush_input()?;
+                }
+                self.keys.push(name.to_string());
+                Ok(())
+            }
+            // `key` steps press one key at a time, so shortcuts cannot be replayed.
+            _ => {
+                self.flush_input()?;
+                self.comment(&format!(
+                    "not recorded: shortcut {} (keysym {:#x}, modifiers {:#x})",
+                    key.string, key.keysym, key.modifiers
+                ))
+            }
+        }
+    }
+
+    /// The step selecting `node`: `find` if its role and name are unique, otherwise `path`.
+    async fn select_step(&self, conn: &Connection, node: &AccessibleProxy<'_>) -> Result<String> {
+        if let Some(query) = query_for(node).await {
+            let matches = target::search(conn, &self.root, &query, 2).await?;
+            if matches.len() == 1 {
+                return Ok(format!("find: {}", scalar(&query)?));
+            }
+        }
+        let path = target::index_path(conn, node).await?;
+        Ok(format!("path: {}", scalar(&path)?))
+    }
+
+    /// Record a click on `node` as the step selecting it and the fitting action.
+    async fn click(&mut self, conn: &Connection, node: AccessibleProxy<'static>) -> Result<()> {
+        let select = self.select_step(conn, &node).await?;
+        let action_names = match node.proxies().await?.action().await {
+            Ok(action) => action.get_actions().await?,
+            Err(_) => Vec::new(),
+        };
+        let action = CLICK_ACTIONS.iter().find(|name| {
+            action_names
+                .iter()
+                .any(|a| a.name.eq_ignore_ascii_case(name))
+        });
+
+        self.flush_input()?;
+        self.step(&select)?;
+        match action {
+            Some(action) => self.step(&format!("action: {action}")),
+            None => self.comment(&format!(
+                "not recorded: click on {}, it has none of the actions {}",
+                watch::describe_node(&node).await,
+                CLICK_ACTIONS.join(", ")
+            )),
+        }
+    }
+
+    /// Record focus moving to `node` without a click, e.g. by Tab.
+    async fn focus(&mut self, conn: &Connection, node: AccessibleProxy<'static>) -> Result<()> {
+        let select = self.select_step(conn, &node).await?;
+        self.flush_input()?;
+        self.step(&select)?;
+        self.step("assert: { state: focused }")
+    }
+}
+
+/// `value` as a YAML scalar, quoted if needed.
+/// The query for the role and name of `node`, e.g. `push button "Save"`, with the name
+/// as is: `Query::parse` takes everything between the first and the last quote, so quotes,
+/// backslashes and newlines in it need no escaping. `None` if the role is unknown.
+async fn query_for(node: &AccessibleProxy<'_>) -> Option<String> {
+    let role = node.get_role().await.ok()?;
+    match node.name().await {
+        Ok(name) if !name.is_empty() => Some(format!("{} \"{name}\"", role.name())),
+        _ => Some(role.name().to_string()),
+    }
+}
+
+fn scalar(value: &str) -> Result<String> {
+    Ok(serde_yaml::to_string(value)?.trim_end().to_string())
+}
+
+pub async fn record_script(a11y: &AccessibilityConnection, args: RecordScriptArgs) -> Result<()> {
+    let conn = a11y.connection();
+    let root = target::select(conn, &args.app, None, None).await?;
+    let sender = root.proxy.inner().destination().to_string();
+
+    let filters = [
+        "object:state-changed",
+        "window:activate",
+        "window:deactivate",
+        "mouse:button",
+    ]
+    .iter()
+    .map(|pattern| EventFilter::parse(pattern))
+    .collect::<Result<Vec<_>>>()?;
+    watch::subscribe(a11y, &filters).await?;
+    let mut keys = keys::listen(conn).await?;
+
+    let mut file = File::create(&args.output)
+        .map_err(|e| format!("Could not create {}: {e}", args.output.display()))?;
+    writeln!(file, "# Recorded by a11y-app record-script")?;
+    writeln!(file, "app: {}", scalar(&args.app)?)?;
+    writeln!(file, "steps:")?;
+    let mut recorder = Recorder {
+        file,
+        root,
+        steps: 0,
+        typed: String::new(),
+        keys: Vec::new(),
+    };
+
+    let (stop_sender, mut stop) = futures::channel::mpsc::unbounded();
+    // Reading stdin blocks, so it gets a thread of its own.
+    std::thread::spawn(move || {
+        let _ = std::io::stdin().lock().lines().next();
+        let _ = stop_sender.unbounded_send(());
+    });
+    println!(
+        "Recording {} to {}, switch to it and go; press Enter here to stop...",
+        args.app,
+        args.output.display()
+    );
+
+    // Key and mouse events do not say which application they went to,
+    // so they are only recorded while one of its windows is active.
+    let mut active = false;
+    let mut last_click: Option<Instant> = None;
+    let click_window = Duration::from_millis(args.click_window);
+
+    let events = a11y.event_stream();
+    futures::pin_mut!(events);
+
+    loop {
+        let event = tokio::select! {
+            event = events.next() => event,
+            Some(key) = keys.next() => {
+                if active {
+                    recorder.key(&key)?;
+                }
+                continue;
+            }
+            _ = stop.next() => break,
+        };
+        let Some(event) = event else {
+            return Err("The connection to the accessibility bus was closed".into());
+        };
+        let Ok(event) = event else {
+            continue;
+        };
+
+        if let Event::Mouse(MouseEvents::Button(e)) = &event {
+            if active && e.detail == "1p" {
+                last_click = Some(Instant::now());
+            }
+            continue;
+        }
+        if event.sender().as_str() != sender {
+            continue;
+        }
+
+        match &event {
+            Event::Window(WindowEvents::Activate(_)) => {
+                // The first activation is switching to the application to start recording.
+                if recorder.steps > 0 {
+                    recorder.flush_input()?;
+                    recorder.step("wait-for: window:activate")?;
+                }
+                active = true;
+            }
+            Event::Window(WindowEvents::Deactivate(_)) => active = false,
+            Event::Object(ObjectEvents::StateChanged(e)) if CLICK_STATES.contains(&e.state) => {
+                let clicked = last_click.is_some_and(|at| at.elapsed() <= click_window);
+                let node = watch::accessible_from_object_ref(conn, event.object_ref()).await?;
+                if clicked {
+                    last_click = None;
+                    recorder.click(conn, node).await?;
+                } else if e.state == State::Focused && e.enabled {
+                    recorder.focus(conn, node).await?;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    recorder.flush_input()?;
+    if recorder.steps == 0 {
+        writeln!(recorder.file, "  []")?;
+    }
+    println!(
+        "Recorded {} steps to {}, replay them with: a11y-app run {}",
+        recorder.steps,
+        args.output.display(),
+        args.output.display()
+    );
+    Ok(())
+}
@@ -0,0 +1,231 @@
+//! How trees of accessible objects are written out: as `tree` does in a terminal, or as
+//! JSON, DOT or HTML for other tools, as `--tree-format` selects. Programs embedding the
+//! command line can register renderers of their own, which `--tree-format` then offers too.
+
+use crate::{tree::A11yNode, ArgResult, Result};
+use display_tree::AsTree;
+use std::{
+    io::{self, Write},
+    sync::{Arc, Mutex, OnceLock},
+};
+
+/// Writes a tree of accessible objects in some format.
+pub trait Renderer {
+    /// Write `tree` to `out`.
+    fn render(&self, tree: &A11yNode, out: &mut dyn Write) -> io::Result<()>;
+}
+
+/// As `tree` prints directories, the default.
+pub struct TermTree;
+
+impl Renderer for TermTree {
+    fn render(&self, tree: &A11yNode, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "{}", AsTree::new(tree))
+    }
+}
+
+/// The tree as A11yNode serializes, pretty-printed.
+pub struct Json;
+
+impl Renderer for Json {
+    fn render(&self, tree: &A11yNode, out: &mut dyn Write) -> io::Result<()> {
+        serde_json::to_writer_pretty(&mut *out, tree)?;
+        writeln!(out)
+    }
+}
+
+/// A Graphviz graph, e.g. for `dot -Tsvg`, with a box for each node.
+pub struct Dot;
+
+impl Renderer for Dot {
+    fn render(&self, tree: &A11yNode, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "digraph tree {{")?;
+        writeln!(out, "    node [shape=box];")?;
+        // The nodes are numbered depth first, with the number of their parent.
+        let mut stack: Vec<(&A11yNode, Option<usize>)> = vec![(tree, None)];
+        let mut number = 0;
+        while let Some((node, parent)) = stack.pop() {
+            let label = label(node).replace('\\', "\\\\").replace('"', "\\\"");
+            writeln!(out, "    n{number} [label=\"{label}\"];")?;
+            if let Some(parent) = parent {
+                writeln!(out, "    n{parent} -> n{number};")?;
+            }
+            stack.extend(
+                node.children
+                    .iter()
+                    .rev()
+                    .map(|child| (child, Some(number))),
+            );
+            number += 1;
+        }
+        writeln!(out, "}}")
+    }
+}
+
+/// Nested lists, to include in a page.
+pub struct Html;
+
+impl Renderer for Html {
+    fn render(&self, tree: &A11yNode, out: &mut dyn Write) -> io::Result<()> {
+        /// A node to write, or the end of the children of one, at a depth.
+        enum Item<'a> {
+            Node(&'a A11yNode, usize),
+            End(usize),
+        }
+
+        writeln!(out, "<ul class=\"a11y-tree\">")?;
+        let mut stack = vec![Item::Node(tree, 1)];
+        while let Some(item) = stack.pop() {
+            match item {
+                Item::Node(node, depth) => {
+                    let indent = "  ".repeat(depth);
+                    let label = label(node)
+                        .replace('&', "&amp;")
+                        .replace('<', "&lt;")
+                        .replace('>', "&gt;");
+                    if node.children.is_empty() {
+                        writeln!(out, "{indent}<li>{label}</li>")?;
+                        continue;
+                    }
+                    writeln!(out, "{indent}<li>{label}")?;
+                    writeln!(out, "{indent}<ul>")?;
+                    stack.push(Item::End(depth));
+                    stack.extend(
+                        node.children
+                            .iter()
+                            .rev()
+                            .map(|child| Item::Node(child, depth + 1)),
+                    );
+                }
+                Item::End(depth) => {
+                    let indent = "  ".repeat(depth);
+                    writeln!(out, "{indent}</ul>")?;
+                    writeln!(out, "{indent}</li>")?;
+                }
+            }
+        }
+        writeln!(out, "</ul>")
+    }
+}
+
+/// The role of `node`, and its name if it has one, e.g. `push button "Save"`.
+fn label(node: &A11yNode) -> String {
+    let role = node.role.map_or("unknown role", |role| role.name());
+    match &node.name {
+        Some(name) => format!("{role} {name:?}"),
+        None => role.to_string(),
+    }
+}
+
+/// A renderer the command line can be given.
+pub type SharedRenderer = Arc<dyn Renderer + Send + Sync>;
+
+/// The renderers registered by the program embedding the command line, by name.
+static REGISTERED: Mutex<Vec<(String, SharedRenderer)>> = Mutex::new(Vec::new());
+
+/// Offer `renderer` as `--tree-format <name>`, before [`crate::run`] parses the command
+/// line. It takes the place of a built-in renderer of the same name.
+pub fn register(name: impl Into<String>, renderer: impl Renderer + Send + Sync + 'static) {
+    let name = name.into();
+    let mut registered = REGISTERED.lock().unwrap_or_else(|e| e.into_inner());
+    registered.retain(|(registered, _)| *registered != name);
+    registered.push((name, Arc::new(renderer)));
+}
+
+/// The renderer of the printed trees, for the whole run.
+static RENDERER: OnceLock<SharedRenderer> = OnceLock::new();
+
+/// Print the trees with `renderer`.
+pub(crate) fn set(renderer: SharedRenderer) {
+    let _ = RENDERER.set(renderer);
+}
+
+/// The renderer of `--tree-format` by default.
+pub(crate) fn term_tree() -> SharedRenderer {
+    Arc::new(TermTree)
+}
+
+/// Print `tree` to standard output as `--tree-format` says.
+pub(crate) fn print(tree: &A11yNode) -> Result<()> {
+    let renderer = RENDERER.get_or_init(term_tree);
+    renderer
+        .render(tree, &mut io::stdout().lock())
+        .map_err(|e| format!("Could not print the tree: {e}").into())
+}
+
+pub(crate) fn parse_renderer(value: &str) -> ArgResult<SharedRenderer> {
+    let registered = REGISTERED.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some((_, renderer)) = registered.iter().find(|(name, _)| name == value) {
+        return Ok(renderer.clone());
+    }
+    match value {
+        "tree" => Ok(Arc::new(TermTree)),
+        "json" => Ok(Arc::new(Json)),
+        "dot" => Ok(Arc::new(Dot)),
+        "html" => Ok(Arc::new(Html)),
+        _ => {
+            let mut names = vec!["tree", "json", "dot", "html"];
+            names.extend(registered.iter().map(|(name, _)| name.as_str()));
+            Err(format!(
+                "Unknown tree format: {value}, expected {}",
+                names.join(", ")
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use atspi::Role;
+
+    fn node(role: Role, name: &str, children: Vec<A11yNode>) -> A11yNode {
+        A11yNode {
+            role: Some(role),
+            name: Some(name.to_string()),
+            children,
+            ..A11yNode::default()
+        }
+    }
+
+    fn render(renderer: &dyn Renderer, tree: &A11yNode) -> String {
+        let mut out = Vec::new();
+        renderer.render(tree, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn dot_escapes_quotes_and_backslashes_in_labels() {
+        let tree = node(
+            Role::Frame,
+            "Say \"hi\"",
+            vec![node(Role::Label, "C:\\Users", Vec::new())],
+        );
+        let expected = r#"digraph tree {
+    node [shape=box];
+    n0 [label="frame \"Say \\\"hi\\\"\""];
+    n1 [label="label \"C:\\\\Users\""];
+    n0 -> n1;
+}
+"#;
+        assert_eq!(render(&Dot, &tree), expected);
+    }
+
+    #[test]
+    fn html_escapes_markup_in_labels() {
+        let tree = node(
+            Role::Frame,
+            "<b>Tom & Jerry</b>",
+            vec![node(Role::Label, "a < b", Vec::new())],
+        );
+        let expected = r#"<ul class="a11y-tree">
+  <li>frame "&lt;b&gt;Tom &amp; Jerry&lt;/b&gt;"
+  <ul>
+    <li>label "a &lt; b"</li>
+  </ul>
+  </li>
+</ul>
+"#;
+        assert_eq!(render(&Html, &tree), expected);
+    }
+}
@@ -0,0 +1,56 @@
+//! Retrying failed calls to applications, as `--retries` and `--backoff` say, for
+//! applications that answer erratically, e.g. right after they started.
+
+use std::{fmt::Display, future::Future, sync::OnceLock, time::Duration};
+use tracing::debug;
+
+/// The default of `--backoff`, in milliseconds.
+pub(crate) const DEFAULT_BACKOFF: u64 = 100;
+
+/// How failed calls are retried, for the whole run.
+static RETRY: OnceLock<Retry> = OnceLock::new();
+
+#[derive(Clone, Copy)]
+struct Retry {
+    /// How many times a call is retried after it failed.
+    retries: u32,
+    /// How long to wait before the first retry, doubled for each next one.
+    backoff: Duration,
+}
+
+/// Retry failed calls `retries` times, waiting `backoff` milliseconds before the first retry.
+pub(crate) fn set(retries: u32, backoff: u64) {
+    let _ = RETRY.set(Retry {
+        retries,
+        backoff: Duration::from_millis(backoff),
+    });
+}
+
+/// The result of `call`, made again after it failed as often as `--retries` says.
+pub(crate) async fn retry<T, E, F, Fut>(mut call: F) -> Result<T, E>
+where
+    E: Display,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let Retry { retries, backoff } = RETRY.get().copied().unwrap_or(Retry {
+        retries: 0,
+        backoff: Duration::from_millis(DEFAULT_BACKOFF),
+    });
+    let mut delay = backoff;
+    let mut attempt = 0;
+    loop {
+        match call().await {
+            Err(e) if attempt < retries => {
+                attempt += 1;
+                debug!(
+                    "call failed, retry {attempt} of {retries} in {} ms: {e}",
+                    delay.as_millis()
+                );
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+            }
+            result => return result,
+        }
+    }
+}
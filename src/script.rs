@@ -0,0 +1,311 @@
+//! Running a sequence of steps against an application, described in a YAML script.
+//!
+//! ```yaml
+//! app: gedit
+//! steps:
+//!   - find: 'push button "Open"'
+//!   - action: click
+//!   - wait-for: window:activate
+//!   - find: 'text "Search"'
+//!   - type: README
+//!   - key: Return
+//!   - assert: { state: focused }
+//! ```
+//!
+//! Each step acts on the node selected by the last `find` or `path` step,
+//! initially the application's root.
+
+use crate::{
+    actions, editable_text, keys,
+    target::{self, Selected},
+    watch::{self, EventFilter},
+    Result,
+};
+use argh::{ArgsInfo, FromArgs};
+use atspi::{proxy::registry::RegistryProxy, AccessibilityConnection, Event, State};
+use futures::{
+    channel::mpsc::{unbounded, UnboundedReceiver},
+    StreamExt,
+};
+use serde::Deserialize;
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use zbus::{message::Type as MessageType, MessageStream};
+
+/// How long `key` steps wait between keys.
+const KEY_DELAY: Duration = Duration::from_millis(50);
+
+/// Run the steps of a YAML script against an application
+#[derive(FromArgs, ArgsInfo)]
+#[argh(subcommand, name = "run")]
+pub struct RunArgs {
+    /// the script
+    #[argh(positional)]
+    script: PathBuf,
+
+    /// the application, by bus name or application name (default: the script's `app`)
+    #[argh(option)]
+    app: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Script {
+    /// The application, by bus name or application name.
+    pub(crate) app: Option<String>,
+    /// How long `wait-for` steps wait, in milliseconds.
+    #[serde(default = "default_timeout")]
+    pub(crate) timeout: u64,
+    /// Steps are written as `- find: ...` rather than with YAML tags.
+    #[serde(with = "serde_yaml::with::singleton_map_recursive")]
+    pub(crate) steps: Vec<Step>,
+}
+
+fn default_timeout() -> u64 {
+    5000
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Step {
+    /// Select the first node with this role and/or name, e.g. `push button "Save"`.
+    Find(String),
+    /// Select the node by its index path, e.g. `0/2/1`.
+    Path(String),
+    /// Perform an action of the node, by name or index.
+    Action(String),
+    /// Press and release one or more keys, by keysym name, number or character.
+    Key(Keys),
+    /// Type a string.
+    Type(String),
+    /// Replace the node's text through the EditableText interface.
+    SetText(String),
+    /// Wait for an event of this type since the previous `wait-for`, e.g. `window:activate`.
+    WaitFor(String),
+    /// Check the node's states, name or role.
+    Assert(Assertion),
+    /// Pause, in milliseconds.
+    Sleep(u64),
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub(crate) enum Keys {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Keys {
+    pub(crate) fn to_vec(&self) -> Vec<String> {
+        match self {
+            Keys::One(key) => vec![key.clone()],
+            Keys::Many(keys) => keys.clone(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub(crate) struct Assertion {
+    /// A state the node must have.
+    pub(crate) state: Option<String>,
+    /// A state the node must not have.
+    pub(crate) not_state: Option<String>,
+    pub(crate) name: Option<String>,
+    pub(crate) role: Option<String>,
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Step::Find(query) => write!(f, "find {query}"),
+            Step::Path(path) => write!(f, "path {path}"),
+            Step::Action(action) => write!(f, "action {action}"),
+            Step::Key(keys) => write!(f, "key {}", keys.to_vec().join(" ")),
+            Step::Type(string) => write!(f, "type {string:?}"),
+            Step::SetText(text) => write!(f, "set-text {text:?}"),
+            Step::WaitFor(event) => write!(f, "wait-for {event}"),
+            Step::Assert(assertion) => {
+                write!(f, "assert")?;
+                if let Some(state) = &assertion.state {
+                    write!(f, " state {state}")?;
+                }
+                if let Some(state) = &assertion.not_state {
+                    write!(f, " not-state {state}")?;
+                }
+                if let Some(name) = &assertion.name {
+                    write!(f, " name {name:?}")?;
+                }
+                if let Some(role) = &assertion.role {
+                    write!(f, " role {role}")?;
+                }
+                Ok(())
+            }
+            Step::Sleep(ms) => write!(f, "sleep {ms} ms"),
+        }
+    }
+}
+
+/// Read and parse the script at `path`.
+pub(crate) fn load(path: &Path) -> Result<Script> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("Could not read {}: {e}", path.display()))?;
+    let script = serde_yaml::from_str(&contents)
+        .map_err(|e| format!("Invalid script {}: {e}", path.display()))?;
+    Ok(script)
+}
+
+pub async fn run(a11y: &AccessibilityConnection, args: RunArgs) -> Result<()> {
+    let script = load(&args.script)?;
+    let app = args
+        .app
+        .or(script.app)
+        .ok_or("The script names no app, give one with --app")?;
+
+    // Subscribe before the first step, so events caused by earlier steps are not missed.
+    let wait_patterns: Vec<&str> = script
+        .steps
+        .iter()
+        .filter_map(|step| match step {
+            Step::WaitFor(pattern) => Some(pattern.as_str()),
+            _ => None,
+        })
+        .collect();
+    let mut events = if wait_patterns.is_empty() {
+        None
+    } else {
+        let filters = wait_patterns
+            .iter()
+            .map(|pattern| EventFilter::parse(pattern))
+            .collect::<Result<Vec<_>>>()?;
+        watch::subscribe(a11y, &filters).await?;
+        Some(events(a11y))
+    };
+
+    let conn = a11y.connection();
+    let mut node = target::select(conn, &app, None, None).await?;
+    let timeout = Duration::from_millis(script.timeout);
+    let count = script.steps.len();
+
+    for (number, step) in script.steps.iter().enumerate() {
+        let number = number + 1;
+        println!("[{number}/{count}] {step}");
+        // What these steps wait for or check follows from the steps a dry run leaves out.
+        if crate::dry_run() && matches!(step, Step::WaitFor(_) | Step::Assert(_) | Step::Sleep(_)) {
+            println!("    skipped in a dry run");
+            continue;
+        }
+        let result = match step {
+            Step::Find(query) => target::select(conn, &app, None, Some(query))
+                .await
+                .map(|found| node = found),
+            Step::Path(path) => target::select(conn, &app, Some(path), None)
+                .await
+                .map(|found| node = found),
+            Step::Action(action) => actions::perform(&node, action).await.map(|_| ()),
+            Step::Key(keys) => keys::press_keys(conn, &keys.to_vec(), KEY_DELAY).await,
+            Step::Type(string) => keys::type_string(conn, string).await,
+            Step::SetText(text) => editable_text::set_contents(&node, text).await,
+            Step::WaitFor(pattern) => {
+                // Only scripts with `wait-for` steps subscribe, so this is always set.
+                let events = events.as_mut().ok_or("Not subscribed to events")?;
+                wait_for(events, pattern, timeout).await
+            }
+            Step::Assert(assertion) => check(&node, assertion).await,
+            Step::Sleep(ms) => {
+                tokio::time::sleep(Duration::from_millis(*ms)).await;
+                Ok(())
+            }
+        };
+
+        if let Err(e) = result {
+            return Err(format!("Step {number} ({step}) failed: {e}").into());
+        }
+        if matches!(step, Step::Find(_) | Step::Path(_)) {
+            println!("    found {} at {}", node.description, node.location());
+        }
+    }
+
+    println!("All {count} steps passed");
+    Ok(())
+}
+
+/// The incoming events on the connection of `registry`, read in the background so events
+/// queue up between `wait-for` steps instead of stalling the connection.
+pub(crate) fn events(registry: &RegistryProxy<'_>) -> UnboundedReceiver<Event> {
+    let (sender, receiver) = unbounded();
+    // As `AccessibilityConnection::event_stream`, which scripts cannot keep.
+    let mut messages = MessageStream::from(registry.inner().connection());
+    tokio::spawn(async move {
+        while let Some(message) = messages.next().await {
+            let Ok(message) = message else {
+                continue;
+            };
+            if message.message_type() != MessageType::Signal {
+                continue;
+            }
+            let Ok(event) = Event::try_from(&message) else {
+                continue;
+            };
+            if sender.unbounded_send(event).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+/// Wait for an event matching `pattern`, skipping the events that do not match.
+async fn wait_for(
+    events: &mut UnboundedReceiver<Event>,
+    pattern: &str,
+    timeout: Duration,
+) -> Result<()> {
+    let filter = EventFilter::parse(pattern)?;
+    let wait = async {
+        while let Some(event) = events.next().await {
+            if filter.matches(&watch::event_type(&event)) {
+                return Ok(());
+            }
+        }
+        Err("the event stream ended".into())
+    };
+    tokio::time::timeout(timeout, wait)
+        .await
+        .map_err(|_| format!("no {pattern} event within {} ms", timeout.as_millis()))?
+}
+
+async fn check(node: &Selected, assertion: &Assertion) -> Result<()> {
+    let states = node.proxy.get_state().await?;
+    for (name, expected) in [(&assertion.state, true), (&assertion.not_state, false)] {
+        let Some(name) = name else {
+            continue;
+        };
+        let state = State::from(name.as_str());
+        if state == State::Invalid {
+            return Err(format!("Unknown state: {name}").into());
+        }
+        if states.contains(state) != expected {
+            let has = if expected { "does not have" } else { "has" };
+            return Err(format!("{} {has} the {name} state", node.description).into());
+        }
+    }
+
+    if let Some(expected) = &assertion.name {
+        let name = node.proxy.name().await?;
+        if name != *expected {
+            return Err(format!("expected name {expected:?}, found {name:?}").into());
+        }
+    }
+    if let Some(expected) = &assertion.role {
+        let role = node.proxy.get_role().await?;
+        if !role.name().eq_ignore_ascii_case(expected) {
+            return Err(format!("expected role {expected}, found {}", role.name()).into());
+        }
+    }
+
+    Ok(())
+}
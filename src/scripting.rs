@@ -0,0 +1,338 @@
+//! Running Rhai scripts, for ad-hoc inspection and automation beyond what `run` scripts can do.
+//!
+//! ```rhai
+//! let gedit = app("gedit");
+//! for button in gedit.find_all("push button") {
+//!     print(`${button.path}: ${button.description} ${button.states()}`);
+//! }
+//! subscribe("window:activate");
+//! gedit.find(`push button "Open"`).do_action("click");
+//! let event = wait_for("window:activate", 5000);
+//! print(event.target);
+//! ```
+//!
+//! Nodes are the `Node` type, with the `name`, `role`, `description` and `path`
+//! properties. Calls into the accessibility bus that fail raise a script error.
+
+use crate::{
+    actions, editable_text, keys, script,
+    target::{self, Selected},
+    watch::{self, EventFilter},
+    Result,
+};
+use argh::{ArgsInfo, FromArgs};
+use atspi::{
+    proxy::{proxy_ext::ProxyExt, registry::RegistryProxy},
+    AccessibilityConnection, Event, EventProperties, State,
+};
+use futures::{channel::mpsc::UnboundedReceiver, Future, StreamExt};
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Map, Scope};
+use std::{cell::RefCell, path::PathBuf, rc::Rc, time::Duration};
+use zbus::Connection;
+
+/// How long `key` waits between keys.
+const KEY_DELAY: Duration = Duration::from_millis(50);
+
+type ScriptResult<T> = std::result::Result<T, Box<EvalAltResult>>;
+
+/// Run a Rhai script with access to the accessibility tree, actions and events
+#[derive(FromArgs, ArgsInfo)]
+#[argh(subcommand, name = "rhai")]
+pub struct RhaiArgs {
+    /// the script
+    #[argh(positional)]
+    script: PathBuf,
+
+    /// arguments for the script, available to it as `ARGS`
+    #[argh(positional, greedy)]
+    args: Vec<String>,
+}
+
+/// Wait for `future` from within the script, which runs outside of the async context.
+fn block_on<T>(future: impl Future<Output = Result<T>>) -> ScriptResult<T> {
+    tokio::runtime::Handle::current()
+        .block_on(future)
+        .map_err(|e| e.to_string().into())
+}
+
+pub async fn rhai(a11y: &AccessibilityConnection, args: RhaiArgs) -> Result<()> {
+    let engine = engine(a11y);
+    let mut scope = Scope::new();
+    let script_args: Array = args.args.into_iter().map(Dynamic::from).collect();
+    scope.push_constant("ARGS", script_args);
+
+    // The engine is synchronous, its functions block on the bus calls.
+    tokio::task::block_in_place(|| engine.run_file_with_scope(&mut scope, args.script))
+        .map_err(|e| e.to_string().into())
+}
+
+fn engine(a11y: &AccessibilityConnection) -> Engine {
+    let mut engine = Engine::new();
+    let conn = a11y.connection().clone();
+
+    engine
+        .register_type_with_name::<Selected>("Node")
+        .register_get("name", |node: &mut Selected| {
+            block_on(async { Ok(node.proxy.name().await?) })
+        })
+        .register_get("role", |node: &mut Selected| {
+            block_on(async { Ok(node.proxy.get_role().await?.name().to_string()) })
+        })
+        .register_get("description", |node: &mut Selected| {
+            node.description.clone()
+        })
+        .register_get("path", |node: &mut Selected| node.path.clone())
+        .register_fn("to_string", |node: &mut Selected| {
+            format!("{} at {}", node.description, node.location())
+        })
+        .register_fn("to_debug", |node: &mut Selected| {
+            format!("{} at {}", node.description, node.location())
+        });
+
+    let c = conn.clone();
+    engine.register_fn("app", move |app: &str| {
+        block_on(target::select(&c, app, None, None))
+    });
+
+    let c = conn.clone();
+    engine.register_fn("children", move |node: &mut Selected| {
+        block_on(children(&c, node))
+    });
+
+    let c = conn.clone();
+    engine.register_fn("child", move |node: &mut Selected, index: i64| {
+        block_on(child(&c, node, index))
+    });
+
+    let c = conn.clone();
+    engine.register_fn("parent", move |node: &mut Selected| {
+        block_on(parent(&c, node))
+    });
+
+    let c = conn.clone();
+    engine.register_fn(
+        "find",
+        move |node: &mut Selected, query: &str| -> ScriptResult<Dynamic> {
+            let mut found = block_on(target::search(&c, node, query, 1))?;
+            Ok(found.pop().map_or(Dynamic::UNIT, Dynamic::from))
+        },
+    );
+
+    let c = conn.clone();
+    engine.register_fn(
+        "find_all",
+        move |node: &mut Selected, query: &str| -> ScriptResult<Array> {
+            let found = block_on(target::search(&c, node, query, usize::MAX))?;
+            Ok(found.into_iter().map(Dynamic::from).collect::<Array>())
+        },
+    );
+
+    engine
+        .register_fn("states", |node: &mut Selected| {
+            block_on(async {
+                let states = node.proxy.get_state().await?;
+                Ok(states
+                    .iter()
+                    .map(|state| Dynamic::from(state.to_static_str().to_string()))
+                    .collect::<Array>())
+            })
+        })
+        .register_fn(
+            "has_state",
+            |node: &mut Selected, name: &str| -> ScriptResult<bool> {
+                let state = State::from(name);
+                if state == State::Invalid {
+                    return Err(format!("Unknown state: {name}").into());
+                }
+                block_on(async { Ok(node.proxy.get_state().await?.contains(state)) })
+            },
+        )
+        .register_fn("actions", |node: &mut Selected| {
+            block_on(action_names(node))
+        })
+        .register_fn("do_action", |node: &mut Selected, action: &str| {
+            block_on(actions::perform(node, action))
+        })
+        .register_fn("set_text", |node: &mut Selected, text: &str| {
+            block_on(editable_text::set_contents(node, text))
+        });
+
+    let c = conn.clone();
+    engine.register_fn("key", move |key: &str| {
+        block_on(keys::press_keys(&c, &[key.to_string()], KEY_DELAY))
+    });
+
+    let c = conn.clone();
+    engine.register_fn("key", move |keys: Array| {
+        let keys: Vec<String> = keys.into_iter().map(|key| key.to_string()).collect();
+        block_on(keys::press_keys(&c, &keys, KEY_DELAY))
+    });
+
+    let c = conn.clone();
+    engine.register_fn("type_text", move |string: &str| {
+        block_on(keys::type_string(&c, string))
+    });
+
+    engine.register_fn("sleep", |ms: i64| {
+        std::thread::sleep(Duration::from_millis(ms.max(0) as u64));
+    });
+
+    register_events(&mut engine, a11y);
+    engine
+}
+
+/// `subscribe(pattern)`, `next_event(ms)` and `wait_for(pattern, ms)`.
+///
+/// Events are only read after the first `subscribe`, and queue up until the script asks for them.
+fn register_events(engine: &mut Engine, a11y: &AccessibilityConnection) {
+    let events: Rc<RefCell<Option<UnboundedReceiver<Event>>>> = Rc::default();
+
+    // The engine outlives the borrow of `a11y`, so it keeps a clone of the registry.
+    let registry: RegistryProxy<'static> = (**a11y).clone();
+    let e = events.clone();
+    engine.register_fn("subscribe", move |pattern: &str| -> ScriptResult<()> {
+        block_on(async {
+            let filter = EventFilter::parse(pattern)?;
+            watch::subscribe(&registry, &[filter]).await?;
+            Ok(())
+        })?;
+        e.borrow_mut()
+            .get_or_insert_with(|| script::events(&registry));
+        Ok(())
+    });
+
+    let conn = a11y.connection().clone();
+    let c = conn.clone();
+    let e = events.clone();
+    engine.register_fn("next_event", move |ms: i64| -> ScriptResult<Dynamic> {
+        let mut events = e.borrow_mut();
+        let events = events
+            .as_mut()
+            .ok_or("Call subscribe before waiting for events")?;
+        block_on(next_event(&c, events, None, ms))
+    });
+
+    let e = events;
+    engine.register_fn(
+        "wait_for",
+        move |pattern: &str, ms: i64| -> ScriptResult<Dynamic> {
+            let mut events = e.borrow_mut();
+            let events = events
+                .as_mut()
+                .ok_or("Call subscribe before waiting for events")?;
+            let filter = EventFilter::parse(pattern).map_err(|e| e.to_string())?;
+            let event = block_on(next_event(&conn, events, Some(&filter), ms))?;
+            if event.is_unit() {
+                return Err(format!("No {pattern} event within {ms} ms").into());
+            }
+            Ok(event)
+        },
+    );
+}
+
+/// The next event matching `filter`, as a map, or `()` if none arrived within `ms` milliseconds.
+async fn next_event(
+    conn: &Connection,
+    events: &mut UnboundedReceiver<Event>,
+    filter: Option<&EventFilter>,
+    ms: i64,
+) -> Result<Dynamic> {
+    let wait = async {
+        while let Some(event) = events.next().await {
+            let event_type = watch::event_type(&event);
+            if filter.is_none_or(|filter| filter.matches(&event_type)) {
+                return Ok(event);
+            }
+        }
+        Err("the event stream ended")
+    };
+    let timeout = Duration::from_millis(ms.max(0) as u64);
+    let Ok(event) = tokio::time::timeout(timeout, wait).await else {
+        return Ok(Dynamic::UNIT);
+    };
+    let event = event?;
+
+    let mut map = Map::new();
+    map.insert("type".into(), watch::event_type(&event).into());
+    map.insert("detail".into(), watch::event_detail(&event).into());
+    map.insert("sender".into(), event.sender().to_string().into());
+    map.insert(
+        "target".into(),
+        watch::describe_target(conn, event.object_ref())
+            .await
+            .into(),
+    );
+    Ok(map.into())
+}
+
+/// The node's child at `index`.
+async fn child(conn: &Connection, node: &Selected, index: i64) -> Result<Selected> {
+    let count = node.proxy.child_count().await?;
+    let index = i32::try_from(index)
+        .ok()
+        .filter(|index| (0..count).contains(index))
+        .ok_or_else(|| {
+            format!(
+                "{} has {count} children, there is no child {index}",
+                node.description
+            )
+        })?;
+    let child = node.proxy.get_child_at_index(index).await?;
+    let proxy = watch::accessible_from_object_ref(conn, child).await?;
+    Ok(Selected {
+        description: watch::describe_node(&proxy).await,
+        path: child_path(&node.path, index as usize),
+        proxy,
+    })
+}
+
+async fn children(conn: &Connection, node: &Selected) -> Result<Array> {
+    let mut children = Array::new();
+    for (index, child) in node.proxy.get_children().await?.into_iter().enumerate() {
+        let proxy = watch::accessible_from_object_ref(conn, child).await?;
+        children.push(Dynamic::from(Selected {
+            description: watch::describe_node(&proxy).await,
+            path: child_path(&node.path, index),
+            proxy,
+        }));
+    }
+    Ok(children)
+}
+
+/// The node's parent, or `()` for the application's root.
+async fn parent(conn: &Connection, node: &Selected) -> Result<Dynamic> {
+    if node.path.is_empty() {
+        return Ok(Dynamic::UNIT);
+    }
+    let path = node.path.rsplit_once('/').map_or("", |(path, _)| path);
+
+    let parent = node.proxy.parent().await?;
+    let proxy = watch::accessible_from_object_ref(conn, parent).await?;
+    Ok(Dynamic::from(Selected {
+        description: watch::describe_node(&proxy).await,
+        path: path.to_string(),
+        proxy,
+    }))
+}
+
+fn child_path(path: &str, index: usize) -> String {
+    if path.is_empty() {
+        index.to_string()
+    } else {
+        format!("{path}/{index}")
+    }
+}
+
+/// The names of the node's actions, empty if it does not implement the Action interface.
+async fn action_names(node: &Selected) -> Result<Array> {
+    let proxies = node.proxy.proxies().await?;
+    let Ok(action) = proxies.action().await else {
+        return Ok(Array::new());
+    };
+    Ok(action
+        .get_actions()
+        .await?
+        .into_iter()
+        .map(|a| Dynamic::from(a.name))
+        .collect())
+}
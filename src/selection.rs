@@ -0,0 +1,244 @@
+//! Inspecting and changing which children of a list, combo box or similar container are
+//! selected, through the Selection interface.
+
+use crate::{
+    target::{self, Selected},
+    watch, Result,
+};
+use argh::{ArgsInfo, FromArgs};
+use atspi::{
+    proxy::{proxy_ext::ProxyExt, selection::SelectionProxy},
+    AccessibilityConnection,
+};
+use zbus::Connection;
+
+/// List the selected children of a selection container
+#[derive(FromArgs, ArgsInfo)]
+#[argh(subcommand, name = "selection")]
+pub struct SelectionArgs {
+    /// the application, by bus name or application name
+    #[argh(positional)]
+    app: String,
+
+    /// the container, by the child indices leading to it from the application's root, e.g. `0/2/1`
+    #[argh(option)]
+    path: Option<String>,
+
+    /// the first container with this role and/or name, e.g. `list "Files"`
+    #[argh(option)]
+    query: Option<String>,
+}
+
+/// Select children of a selection container, by index or name
+#[derive(FromArgs, ArgsInfo)]
+#[argh(subcommand, name = "select")]
+pub struct SelectArgs {
+    /// the application, by bus name or application name
+    #[argh(positional)]
+    app: String,
+
+    /// the index or name of the children to select
+    #[argh(positional)]
+    children: Vec<String>,
+
+    /// select all children
+    #[argh(switch)]
+    all: bool,
+
+    /// the container, by the child indices leading to it from the application's root, e.g. `0/2/1`
+    #[argh(option)]
+    path: Option<String>,
+
+    /// the first container with this role and/or name, e.g. `list "Files"`
+    #[argh(option)]
+    query: Option<String>,
+}
+
+/// Deselect children of a selection container, by index or name
+#[derive(FromArgs, ArgsInfo)]
+#[argh(subcommand, name = "deselect")]
+pub struct DeselectArgs {
+    /// the application, by bus name or application name
+    #[argh(positional)]
+    app: String,
+
+    /// the index or name of the children to deselect
+    #[argh(positional)]
+    children: Vec<String>,
+
+    /// deselect all children
+    #[argh(switch)]
+    all: bool,
+
+    /// the container, by the child indices leading to it from the application's root, e.g. `0/2/1`
+    #[argh(option)]
+    path: Option<String>,
+
+    /// the first container with this role and/or name, e.g. `list "Files"`
+    #[argh(option)]
+    query: Option<String>,
+}
+
+/// Select the container and get its Selection interface.
+async fn container(
+    conn: &Connection,
+    app: &str,
+    path: Option<&str>,
+    query: Option<&str>,
+) -> Result<(Selected, SelectionProxy<'static>)> {
+    let node = target::select(conn, app, path, query).await?;
+    let proxies = node.proxy.proxies().await?;
+    match proxies.selection().await {
+        Ok(selection) => Ok((node, selection)),
+        Err(_) => Err(format!(
+            "{} at {} does not implement the Selection interface",
+            node.description,
+            node.location()
+        )
+        .into()),
+    }
+}
+
+/// The index of the container's child given by its index or its name.
+async fn child_index(conn: &Connection, node: &Selected, child: &str) -> Result<i32> {
+    let count = node.proxy.child_count().await?;
+    if let Ok(index) = child.parse::<i32>() {
+        if (0..count).contains(&index) {
+            return Ok(index);
+        }
+        return Err(format!(
+            "{} has {count} children, there is no child {index}",
+            node.description
+        )
+        .into());
+    }
+
+    for (index, object) in node.proxy.get_children().await?.into_iter().enumerate() {
+        let proxy = watch::accessible_from_object_ref(conn, object).await?;
+        if proxy.name().await.is_ok_and(|name| name == child) {
+            return Ok(index as i32);
+        }
+    }
+    Err(format!("{} has no child named {child:?}", node.description).into())
+}
+
+/// Print what selecting or deselecting, as `verb` says, `children` or `all` of them would
+/// do, for a dry run.
+async fn would_change_selection(
+    conn: &Connection,
+    node: &Selected,
+    verb: &str,
+    all: bool,
+    children: &[String],
+) -> Result<()> {
+    if all {
+        println!(
+            "Would {verb} all children of {} at {}",
+            node.description,
+            node.location()
+        );
+    } else if children.is_empty() {
+        return Err(format!("Give the children to {verb}, or --all").into());
+    }
+    for child in children {
+        let index = child_index(conn, node, child).await?;
+        println!(
+            "Would {verb} child {index} of {} at {}",
+            node.description,
+            node.location()
+        );
+    }
+    Ok(())
+}
+
+/// Print the selected children with their indices.
+async fn print_selection(
+    conn: &Connection,
+    node: &Selected,
+    selection: &SelectionProxy<'_>,
+) -> Result<()> {
+    let count = selection.nselected_children().await?;
+    println!(
+        "{} at {} has {count} selected children",
+        node.description,
+        node.location()
+    );
+    if count == 0 {
+        return Ok(());
+    }
+
+    let mut rows = Vec::with_capacity(count as usize);
+    for i in 0..count {
+        let object = selection.get_selected_child(i).await?;
+        let proxy = watch::accessible_from_object_ref(conn, object).await?;
+        let index = match proxy.get_index_in_parent().await {
+            Ok(index) => index.to_string(),
+            Err(e) => format!("Error: {e}"),
+        };
+        rows.push(vec![index, watch::describe_node(&proxy).await]);
+    }
+    crate::print_table(&["Index", "Child"], &rows);
+
+    Ok(())
+}
+
+pub async fn selection(a11y: &AccessibilityConnection, args: SelectionArgs) -> Result<()> {
+    let conn = a11y.connection();
+    let (node, selection) =
+        container(conn, &args.app, args.path.as_deref(), args.query.as_deref()).await?;
+    print_selection(conn, &node, &selection).await
+}
+
+pub async fn select(a11y: &AccessibilityConnection, args: SelectArgs) -> Result<()> {
+    let conn = a11y.connection();
+    let (node, selection) =
+        container(conn, &args.app, args.path.as_deref(), args.query.as_deref()).await?;
+
+    if crate::dry_run() {
+        return would_change_selection(conn, &node, "select", args.all, &args.children).await;
+    }
+
+    if args.all {
+        if !selection.select_all().await? {
+            return Err(format!("{} refused to select all children", node.description).into());
+        }
+    } else if args.children.is_empty() {
+        return Err("Give the children to select, or --all".into());
+    }
+
+    for child in &args.children {
+        let index = child_index(conn, &node, child).await?;
+        if !selection.select_child(index).await? {
+            return Err(format!("{} refused to select child {index}", node.description).into());
+        }
+    }
+
+    print_selection(conn, &node, &selection).await
+}
+
+pub async fn deselect(a11y: &AccessibilityConnection, args: DeselectArgs) -> Result<()> {
+    let conn = a11y.connection();
+    let (node, selection) =
+        container(conn, &args.app, args.path.as_deref(), args.query.as_deref()).await?;
+
+    if crate::dry_run() {
+        return would_change_selection(conn, &node, "deselect", args.all, &args.children).await;
+    }
+
+    if args.all {
+        if !selection.clear_selection().await? {
+            return Err(format!("{} refused to clear its selection", node.description).into());
+        }
+    } else if args.children.is_empty() {
+        return Err("Give the children to deselect, or --all".into());
+    }
+
+    for child in &args.children {
+        let index = child_index(conn, &node, child).await?;
+        if !selection.deselect_child(index).await? {
+            return Err(format!("{} refused to deselect child {index}", node.description).into());
+        }
+    }
+
+    print_selection(conn, &node, &selection).await
+}
@@ -0,0 +1,1126 @@
+//! Saving an application's tree to a file, for analysis, diffing and golden tests
+//! without the application running.
+//!
+//! Where an application can be given, read-only commands also take a snapshot file,
+//! which is turned back into the tree `audit` collects.
+//!
+//! Snapshot files carry the version of their format, and older versions are upgraded
+//! when loaded, so golden files kept in other repositories keep working.
+
+use crate::{
+    audit::{self, AuditNode, AuditTree, Extents, TableInfo},
+    diff,
+    exit::{self, Failure},
+    interrupt,
+    snapshot_metadata::Metadata,
+    target::{self, Query},
+    ArgResult, Result,
+};
+use argh::{ArgsInfo, FromArgs};
+use atspi::{
+    proxy::accessible::ObjectRefExt, AccessibilityConnection, ObjectRef, Role, State, StateSet,
+};
+use flate2::{read::GzDecoder, write::GzEncoder};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{
+    collections::{hash_map::RandomState, BTreeMap, HashMap},
+    hash::{BuildHasher, Hasher},
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+use tracing::warn;
+use zbus::{names::UniqueName, zvariant::ObjectPath};
+
+/// The bus name of the objects of a tree read from a snapshot, which are not on the bus.
+const SNAPSHOT_BUS_NAME: &str = ":0.0";
+
+/// The object path standing in for objects outside of a tree read from a snapshot.
+const OUTSIDE_PATH: &str = "/snapshot/outside";
+
+/// The version of the snapshot format written. Files without a version are version 1.
+const SCHEMA_VERSION: u64 = 1;
+
+/// An upgrade of a snapshot to the next version of the format.
+type Migration = fn(&mut Map<String, Value>) -> Result<()>;
+
+/// Upgrades of older snapshots to the current format: the first turns version 1 into
+/// version 2, and so on. A change to the format adds one and bumps `SCHEMA_VERSION`,
+/// so files written before keep loading.
+const MIGRATIONS: &[Migration] = &[];
+
+const _: () = assert!(MIGRATIONS.len() as u64 + 1 == SCHEMA_VERSION);
+
+/// The zstd compression level of `.zst` snapshots, zstd's default.
+const ZSTD_LEVEL: i32 = 3;
+
+/// The highest role number to look for when reading roles by name.
+const MAX_ROLE: u32 = 200;
+
+/// The roles whose names and descriptions are user content, hidden by `--redact text`:
+/// text that was typed or opened, and window titles, which name the open documents.
+const USER_CONTENT_ROLES: &[Role] = &[
+    Role::Text,
+    Role::Entry,
+    Role::PasswordText,
+    Role::Paragraph,
+    Role::Terminal,
+    Role::DocumentFrame,
+    Role::DocumentWeb,
+    Role::DocumentText,
+    Role::DocumentEmail,
+    Role::DocumentPresentation,
+    Role::DocumentSpreadsheet,
+    Role::Frame,
+    Role::Window,
+];
+
+/// Save the tree of an application, with the properties of its nodes, to a JSON file
+#[derive(FromArgs, ArgsInfo)]
+#[argh(subcommand, name = "snapshot")]
+pub struct SnapshotArgs {
+    /// the application, by bus name or application name; several are saved to an archive
+    #[argh(positional)]
+    apps: Vec<String>,
+
+    /// save all applications on the accessibility bus to an archive
+    #[argh(switch)]
+    all: bool,
+
+    /// write the snapshot to this file, e.g. `app.a11y.json`, instead of printing it
+    #[argh(option)]
+    output: Option<PathBuf>,
+
+    /// only save the tree below the node with these child indices from the application's root
+    #[argh(option)]
+    path: Option<String>,
+
+    /// only save the tree below the first node with this role and/or name
+    #[argh(option)]
+    query: Option<String>,
+
+    /// take a snapshot every this many seconds and print what changed since the last one
+    #[argh(option)]
+    every: Option<u64>,
+
+    /// what to save of each node besides its role, comma separated: `names`, `states`,
+    /// `attributes`, `geometry`, `relations` and `text`, or `roles` for nothing else or
+    /// `all` (default: names,states,attributes,relations)
+    #[argh(option, from_str_fn(parse_detail), default = "Detail::default()")]
+    detail: Detail,
+
+    /// record the host name of this machine in the snapshot
+    #[argh(switch)]
+    hostname: bool,
+
+    /// replace user content with hashes, which only stay equal within one run: `text` (of
+    /// text fields, documents and window titles), `names` (all names and descriptions) or
+    /// `all` (also attribute values)
+    #[argh(option, from_str_fn(parse_redact))]
+    redact: Option<Redact>,
+}
+
+impl SnapshotArgs {
+    /// Redact the snapshot and add the host name, as asked for.
+    fn finish(&self, snapshot: &mut Snapshot) {
+        if let Some(redact) = self.redact {
+            redact_node(&mut snapshot.root, redact);
+        }
+        if let (true, Some(metadata)) = (self.hostname, &mut snapshot.metadata) {
+            metadata.hostname = Metadata::hostname();
+        }
+    }
+}
+
+/// What to hide of a snapshot, from least to most.
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+enum Redact {
+    Text,
+    Names,
+    All,
+}
+
+fn parse_redact(value: &str) -> ArgResult<Redact> {
+    match value {
+        "text" => Ok(Redact::Text),
+        "names" => Ok(Redact::Names),
+        "all" => Ok(Redact::All),
+        _ => Err(format!(
+            "Unknown redaction: {value}, expected text, names or all"
+        )),
+    }
+}
+
+/// What a snapshot holds of its nodes besides their roles and children.
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Facet {
+    /// Name, description, accessible id and locale.
+    Names,
+    States,
+    /// Attributes and what the Table interface says.
+    Attributes,
+    /// Where nodes are on the screen.
+    Geometry,
+    /// Relations, and the Parent and index in parent nodes report.
+    Relations,
+    /// The text of nodes that implement Text.
+    Text,
+}
+
+impl Facet {
+    fn name(self) -> &'static str {
+        match self {
+            Facet::Names => "names",
+            Facet::States => "states",
+            Facet::Attributes => "attributes",
+            Facet::Geometry => "geometry",
+            Facet::Relations => "relations",
+            Facet::Text => "text",
+        }
+    }
+}
+
+const FACETS: &[Facet] = &[
+    Facet::Names,
+    Facet::States,
+    Facet::Attributes,
+    Facet::Geometry,
+    Facet::Relations,
+    Facet::Text,
+];
+
+/// The facets snapshots hold by default, which are those audits need, and those of
+/// snapshots from before there was a choice.
+const DEFAULT_FACETS: &[Facet] = &[
+    Facet::Names,
+    Facet::States,
+    Facet::Attributes,
+    Facet::Relations,
+];
+
+/// The facets of a snapshot.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub(crate) struct Detail(Vec<Facet>);
+
+impl Default for Detail {
+    fn default() -> Detail {
+        Detail(DEFAULT_FACETS.to_vec())
+    }
+}
+
+impl Detail {
+    pub(crate) fn has(&self, facet: Facet) -> bool {
+        self.0.contains(&facet)
+    }
+
+    /// The facets of `other` this one lacks.
+    pub(crate) fn missing(&self, other: &Detail) -> Vec<&'static str> {
+        other
+            .0
+            .iter()
+            .filter(|facet| !self.has(**facet))
+            .map(|facet| facet.name())
+            .collect()
+    }
+
+    /// The facets both have.
+    fn common(&self, other: &Detail) -> Detail {
+        Detail(
+            self.0
+                .iter()
+                .copied()
+                .filter(|facet| other.has(*facet))
+                .collect(),
+        )
+    }
+}
+
+fn parse_detail(value: &str) -> ArgResult<Detail> {
+    let mut facets = Vec::new();
+    for name in value.split(',').map(str::trim) {
+        match name {
+            "roles" => {}
+            "all" => facets.extend(FACETS),
+            _ => match FACETS.iter().find(|facet| facet.name() == name) {
+                Some(facet) => facets.push(*facet),
+                None => {
+                    return Err(format!(
+                        "Unknown detail: {name}, expected roles, names, states, attributes, \
+                         geometry, relations, text or all"
+                    ))
+                }
+            },
+        }
+    }
+    // In a fixed order, so snapshots of the same detail say it the same way.
+    Ok(Detail(
+        FACETS
+            .iter()
+            .copied()
+            .filter(|facet| facets.contains(facet))
+            .collect(),
+    ))
+}
+
+/// The snapshots of several applications in one file.
+#[derive(Serialize)]
+struct Archive {
+    version: u64,
+    /// What is in `members`, in the same order, to find a member without reading them all.
+    index: Vec<ArchiveEntry>,
+    members: Vec<Snapshot>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ArchiveEntry {
+    /// The name to select the member by, the application's name, made unique.
+    name: String,
+    /// The bus name the application had.
+    bus_name: String,
+    nodes: usize,
+}
+
+/// A saved tree.
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct Snapshot {
+    /// The version of the format, `SCHEMA_VERSION` once loaded.
+    pub(crate) version: u64,
+    /// The application, as it was selected.
+    pub(crate) app: String,
+    /// The index path of `root` in the application's tree, empty for the application's root.
+    #[serde(default)]
+    pub(crate) path: String,
+    /// The locale of the application, empty if it did not say.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub(crate) app_locale: String,
+    /// What the snapshot holds of each node.
+    #[serde(default)]
+    pub(crate) detail: Detail,
+    /// Where and with what the snapshot was taken, if it says.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) metadata: Option<Metadata>,
+    /// Whether taking the snapshot was interrupted, so nodes are missing.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) partial: bool,
+    pub(crate) root: SnapshotNode,
+}
+
+/// A saved node. Empty properties are left out of the file.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct SnapshotNode {
+    pub(crate) role: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub(crate) name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub(crate) description: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub(crate) accessible_id: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub(crate) locale: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) states: Vec<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) attributes: BTreeMap<String, String>,
+    /// The targets of each relation by path, or empty for targets outside the saved tree.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) relations: BTreeMap<String, Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) table: Option<TableInfo>,
+    /// What the Parent property points at, if it is not the node that lists this one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) reported_parent: Option<ParentLink>,
+    /// The index in parent, if it differs from where the parent lists this node.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) index_in_parent: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) extents: Option<Extents>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) text: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) children: Vec<SnapshotNode>,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ParentLink {
+    /// The Parent property is empty.
+    None,
+    /// A node of the saved tree, by path.
+    Node(String),
+    /// An object outside the saved tree, by bus name and object path.
+    Outside(String),
+}
+
+impl ParentLink {
+    /// What the Parent property points at, e.g. `path 0/2` or `none`.
+    pub(crate) fn description(&self) -> String {
+        match self {
+            ParentLink::None => "none".to_string(),
+            ParentLink::Node(path) => audit::location(path),
+            ParentLink::Outside(object) => format!("{object}, outside the snapshot"),
+        }
+    }
+}
+
+impl Snapshot {
+    /// The snapshot with only the facets of `detail` it has.
+    pub(crate) fn with_detail(&self, detail: &Detail) -> Snapshot {
+        let detail = self.detail.common(detail);
+        let mut snapshot = self.clone();
+        snapshot.root.strip(&detail);
+        snapshot.detail = detail;
+        snapshot
+    }
+
+    /// The node at `path` from the application's root, or the first matching `query`,
+    /// or the root of the snapshot, with its index path.
+    pub(crate) fn select(
+        &self,
+        path: Option<&str>,
+        query: Option<&str>,
+    ) -> Result<(&SnapshotNode, String)> {
+        match (path, query) {
+            (Some(_), Some(_)) => Err("Select a node by --path or by --query, not both".into()),
+            (Some(path), None) => {
+                let relative = if self.path.is_empty() {
+                    Some(path)
+                } else if path == self.path {
+                    Some("")
+                } else {
+                    path.strip_prefix(&format!("{}/", self.path))
+                };
+                let relative = relative.ok_or_else(|| {
+                    format!("The snapshot only has the nodes below path {}", self.path)
+                })?;
+
+                let mut node = &self.root;
+                for index in relative.split('/').filter(|index| !index.is_empty()) {
+                    node = index
+                        .parse::<usize>()
+                        .ok()
+                        .and_then(|index| node.children.get(index))
+                        .ok_or_else(|| format!("The snapshot has no node at path {path}"))?;
+                }
+                Ok((node, path.to_string()))
+            }
+            (None, Some(query)) => {
+                let parsed = Query::parse(query)?;
+                let mut stack = vec![(&self.root, self.path.clone())];
+                while let Some((node, path)) = stack.pop() {
+                    if parsed.matches_values(&node.role, &node.name) {
+                        return Ok((node, path));
+                    }
+                    for (index, child) in node.children.iter().enumerate().rev() {
+                        stack.push((child, child_path(&path, index)));
+                    }
+                }
+                Err(Failure::not_found(format!(
+                    "No node of the snapshot matches {query:?}"
+                )))
+            }
+            (None, None) => Ok((&self.root, self.path.clone())),
+        }
+    }
+}
+
+impl SnapshotNode {
+    /// Role and name, as given by `watch::describe_node`, e.g. `push button "Save"`.
+    pub(crate) fn description(&self) -> String {
+        if self.name.is_empty() {
+            self.role.clone()
+        } else {
+            format!("{} {:?}", self.role, self.name)
+        }
+    }
+
+    /// The number of nodes in the subtree.
+    pub(crate) fn count(&self) -> usize {
+        1 + self.children.iter().map(SnapshotNode::count).sum::<usize>()
+    }
+
+    /// Clear what is not in `detail`, here and below.
+    fn strip(&mut self, detail: &Detail) {
+        if !detail.has(Facet::Names) {
+            self.name.clear();
+            self.description.clear();
+            self.accessible_id.clear();
+            self.locale.clear();
+        }
+        if !detail.has(Facet::States) {
+            self.states.clear();
+        }
+        if !detail.has(Facet::Attributes) {
+            self.attributes.clear();
+            self.table = None;
+        }
+        if !detail.has(Facet::Geometry) {
+            self.extents = None;
+        }
+        if !detail.has(Facet::Relations) {
+            self.relations.clear();
+            self.reported_parent = None;
+            self.index_in_parent = None;
+        }
+        if !detail.has(Facet::Text) {
+            self.text = None;
+        }
+        for child in &mut self.children {
+            child.strip(detail);
+        }
+    }
+}
+
+pub async fn snapshot(a11y: &AccessibilityConnection, args: SnapshotArgs) -> Result<()> {
+    let app = match (args.apps.as_slice(), args.all) {
+        ([], false) => return Err("Give an application to take a snapshot of, or --all".into()),
+        ([app], false) => app,
+        _ if args.every.is_some() => return Err("--every takes a single application".into()),
+        _ => return archive(a11y, &args).await,
+    };
+
+    let mut snapshot = capture(
+        a11y,
+        app,
+        args.path.as_deref(),
+        args.query.as_deref(),
+        &args.detail,
+    )
+    .await?;
+    args.finish(&mut snapshot);
+    match args.every {
+        Some(seconds) => {
+            let interval = Duration::from_secs(seconds.max(1));
+            poll(a11y, &args, app, snapshot, interval).await
+        }
+        None => save(&snapshot, args.output.as_deref()),
+    }
+}
+
+/// Save the applications, or with `--all` all of them, to an archive.
+async fn archive(a11y: &AccessibilityConnection, args: &SnapshotArgs) -> Result<()> {
+    let conn = a11y.connection();
+    // Selected by unique bus name, so each is taken once however they are named.
+    let mut apps: Vec<(String, String)> = Vec::new();
+    if args.all {
+        let registry = crate::get_registry_accessible(conn).await?;
+        for app in registry.get_children().await? {
+            let bus_name = app.name.to_string();
+            let name = match app.into_accessible_proxy(conn).await {
+                Ok(proxy) => proxy.name().await.unwrap_or_default(),
+                Err(_) => String::new(),
+            };
+            apps.push((name, bus_name));
+        }
+    }
+    for app in &args.apps {
+        let found = crate::parse_bus_name(app.clone(), conn).await?;
+        apps.extend(
+            found
+                .into_iter()
+                .map(|(name, bus_name)| (name, bus_name.to_string())),
+        );
+    }
+
+    let mut archive = Archive {
+        version: SCHEMA_VERSION,
+        index: Vec::new(),
+        members: Vec::new(),
+    };
+    for (name, bus_name) in apps {
+        // The snapshots taken so far are written, the last of them partial.
+        if interrupt::interrupted() {
+            break;
+        }
+        if archive.index.iter().any(|entry| entry.bus_name == bus_name) {
+            continue;
+        }
+        let snapshot = capture(
+            a11y,
+            &bus_name,
+            args.path.as_deref(),
+            args.query.as_deref(),
+            &args.detail,
+        )
+        .await;
+        let mut snapshot = match snapshot {
+            Ok(snapshot) => snapshot,
+            Err(e) => {
+                warn!("could not take a snapshot of {name} ({bus_name}): {e}");
+                exit::unreached();
+                continue;
+            }
+        };
+        args.finish(&mut snapshot);
+
+        let name = if name.is_empty() {
+            bus_name.clone()
+        } else {
+            name
+        };
+        // Instances of the same application are told apart by number.
+        let taken = |name: &str| archive.index.iter().any(|entry| entry.name == name);
+        let mut unique = name.clone();
+        for n in 2.. {
+            if !taken(&unique) {
+                break;
+            }
+            unique = format!("{name} ({n})");
+        }
+        let name = unique;
+        snapshot.app = name.clone();
+        archive.index.push(ArchiveEntry {
+            name,
+            bus_name,
+            nodes: snapshot.root.count(),
+        });
+        archive.members.push(snapshot);
+    }
+
+    eprintln!("Archived {} applications", archive.members.len());
+    save(&archive, args.output.as_deref())
+}
+
+/// Write the snapshot or archive to `output`, or print it.
+pub(crate) fn save(snapshot: &impl Serialize, output: Option<&Path>) -> Result<()> {
+    let contents = serde_json::to_string_pretty(snapshot)? + "\n";
+    match output {
+        Some(path) => write_file(path, &contents)
+            .map_err(|e| format!("Could not write {}: {e}", path.display()))?,
+        None => print!("{contents}"),
+    }
+    Ok(())
+}
+
+/// Write `contents` to `path`, compressed with gzip for `.gz` and zstd for `.zst`.
+fn write_file(path: &Path, contents: &str) -> Result<()> {
+    let data = match compression(path) {
+        Some(Compression::Gzip) => {
+            let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(contents.as_bytes())?;
+            encoder.finish()?
+        }
+        Some(Compression::Zstd) => zstd::encode_all(contents.as_bytes(), ZSTD_LEVEL)?,
+        None => contents.as_bytes().to_vec(),
+    };
+    std::fs::write(path, data)?;
+    Ok(())
+}
+
+/// Read `path`, decompressed by its extension like `write_file` compressed it.
+fn read_file(path: &Path) -> Result<String> {
+    let data = std::fs::read(path)?;
+    let data = match compression(path) {
+        Some(Compression::Gzip) => {
+            let mut contents = Vec::new();
+            GzDecoder::new(data.as_slice()).read_to_end(&mut contents)?;
+            contents
+        }
+        Some(Compression::Zstd) => zstd::decode_all(data.as_slice())?,
+        None => data,
+    };
+    Ok(String::from_utf8(data)?)
+}
+
+enum Compression {
+    Gzip,
+    Zstd,
+}
+
+fn compression(path: &Path) -> Option<Compression> {
+    match path.extension()?.to_str()? {
+        "gz" => Some(Compression::Gzip),
+        "zst" => Some(Compression::Zstd),
+        _ => None,
+    }
+}
+
+/// Take a snapshot every `interval` and print the differences to the one before, until
+/// interrupted. With `--output`, the file always holds the latest snapshot.
+async fn poll(
+    a11y: &AccessibilityConnection,
+    args: &SnapshotArgs,
+    app: &str,
+    mut previous: Snapshot,
+    interval: Duration,
+) -> Result<()> {
+    if let Some(output) = &args.output {
+        save(&previous, Some(output))?;
+    }
+    let color = crate::color(false);
+    let start = Instant::now();
+    let mut ticker = tokio::time::interval(interval);
+    // The first tick is immediate, and the first snapshot was just taken.
+    ticker.tick().await;
+
+    loop {
+        let captured = tokio::select! {
+            _ = interrupt::cancelled() => return Ok(()),
+            captured = async {
+                ticker.tick().await;
+                capture(
+                    a11y,
+                    app,
+                    args.path.as_deref(),
+                    args.query.as_deref(),
+                    &args.detail,
+                )
+                .await
+            } => captured,
+        };
+        // The node may come and go in a dynamic interface; keep watching until it is back.
+        let mut snapshot = match captured {
+            Ok(snapshot) => snapshot,
+            Err(e) => {
+                warn!("could not take a snapshot: {e}");
+                continue;
+            }
+        };
+        // The output keeps the last whole snapshot.
+        if snapshot.partial {
+            return Ok(());
+        }
+        args.finish(&mut snapshot);
+
+        let changes = diff::diff_snapshots(&previous, &snapshot);
+        if !changes.is_empty() {
+            println!("After {}s:", start.elapsed().as_secs());
+            diff::print_changes(&changes, color);
+            println!();
+            if let Some(output) = &args.output {
+                save(&snapshot, Some(output))?;
+            }
+        }
+        previous = snapshot;
+    }
+}
+
+/// Take a snapshot of the tree of `app`, or of the node selected by `path` or `query`,
+/// with what `detail` asks for of each node.
+pub(crate) async fn capture(
+    a11y: &AccessibilityConnection,
+    app: &str,
+    path: Option<&str>,
+    query: Option<&str>,
+    detail: &Detail,
+) -> Result<Snapshot> {
+    let conn = a11y.connection();
+    let node = target::select(conn, app, path, query).await?;
+    let tree = audit::collect(conn, &node, detail).await?;
+    let sender = node.proxy.inner().destination().to_string();
+    let metadata = Metadata::collect(conn, &sender).await;
+    eprintln!(
+        "Captured {} nodes below {} at {}",
+        tree.nodes.len(),
+        node.description,
+        node.location()
+    );
+
+    Ok(Snapshot {
+        version: SCHEMA_VERSION,
+        app: app.to_string(),
+        path: node.path.clone(),
+        app_locale: tree.app_locale.clone(),
+        detail: detail.clone(),
+        metadata: Some(metadata),
+        partial: interrupt::interrupted(),
+        root: from_tree(&tree, 0, detail),
+    })
+}
+
+/// The node at `index` of the collected tree, with the nodes below it.
+pub(crate) fn from_tree(tree: &AuditTree, index: usize, detail: &Detail) -> SnapshotNode {
+    let node = &tree.nodes[index];
+    let path_of = |object| tree.index_of(object).map(|i| tree.nodes[i].path.clone());
+
+    let mut relations: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (relation, targets) in &node.relations {
+        relations
+            .entry(audit::relation_name(*relation))
+            .or_default()
+            .extend(
+                targets
+                    .iter()
+                    .map(|target| path_of(target).unwrap_or_default()),
+            );
+    }
+
+    // Without relations, there are no Parent and index in parent to compare.
+    let parent = node
+        .parent
+        .map(|parent| &tree.nodes[parent])
+        .filter(|_| detail.has(Facet::Relations));
+    let reported_parent = match (&node.reported_parent, parent) {
+        (Some(reported), Some(parent)) if *reported == parent.object => None,
+        // The Parent of the root points outside of the tree.
+        (_, None) => None,
+        (None, Some(_)) => Some(ParentLink::None),
+        (Some(reported), Some(_)) => Some(match path_of(reported) {
+            Some(path) => ParentLink::Node(path),
+            None => ParentLink::Outside(format!(
+                "{}{}",
+                reported.name.as_str(),
+                reported.path.as_str()
+            )),
+        }),
+    };
+    let position = parent.and_then(|parent| parent.children.iter().position(|c| *c == index));
+    let index_in_parent = match position {
+        Some(position) if position as i32 != node.index_in_parent => Some(node.index_in_parent),
+        _ => None,
+    };
+
+    SnapshotNode {
+        role: node.role.name().to_string(),
+        name: node.name.clone(),
+        description: node.accessible_description.clone(),
+        accessible_id: node.accessible_id.clone(),
+        locale: node.locale.clone(),
+        states: node
+            .states
+            .iter()
+            .map(|state| state.to_static_str().to_string())
+            .collect(),
+        attributes: node
+            .attributes
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect(),
+        relations,
+        table: node.table.clone(),
+        reported_parent,
+        index_in_parent,
+        extents: node.extents,
+        text: node.text.clone(),
+        children: node
+            .children
+            .iter()
+            .map(|child| from_tree(tree, *child, detail))
+            .collect(),
+    }
+}
+
+/// Whether `app` names a snapshot file, or a member of an archive, rather than an application.
+pub(crate) fn is_snapshot(app: &str) -> bool {
+    split_member(Path::new(app)).is_some()
+}
+
+/// The file and the member of `path`, which is a file or `<archive>#<member>`.
+fn split_member(path: &Path) -> Option<(PathBuf, Option<String>)> {
+    if path.is_file() {
+        return Some((path.to_path_buf(), None));
+    }
+    let (file, member) = path.to_str()?.rsplit_once('#')?;
+    Path::new(file)
+        .is_file()
+        .then(|| (PathBuf::from(file), Some(member.to_string())))
+}
+
+/// Load a snapshot file, or with `<archive>#<member>` a member of an archive.
+pub(crate) fn load(path: &Path) -> Result<Snapshot> {
+    load_with(path, MIGRATIONS)
+}
+
+/// Load a snapshot as `load` does, upgrading it with `migrations` to the version after
+/// them.
+fn load_with(path: &Path, migrations: &[Migration]) -> Result<Snapshot> {
+    let current = migrations.len() as u64 + 1;
+    let (file, member) = split_member(path)
+        .ok_or_else(|| format!("Could not read {}: no such file", path.display()))?;
+    let contents =
+        read_file(&file).map_err(|e| format!("Could not read {}: {e}", file.display()))?;
+    let invalid = |e: &dyn std::fmt::Display| format!("Invalid snapshot {}: {e}", path.display());
+    let Value::Object(mut snapshot) = serde_json::from_str(&contents).map_err(|e| invalid(&e))?
+    else {
+        return Err(invalid(&"expected an object").into());
+    };
+    // The version of a snapshot or an archive, which this a11y-app may be too old to read.
+    let version = |object: &Map<String, Value>| -> Result<u64> {
+        let version = match object.get("version") {
+            None => 1,
+            Some(version) => version
+                .as_u64()
+                .filter(|version| *version >= 1)
+                .ok_or_else(|| invalid(&format!("unknown version {version}")))?,
+        };
+        if version > current {
+            return Err(format!(
+                "{} is a snapshot of version {version}, this a11y-app reads up to version \
+                 {current}; update a11y-app to read it",
+                path.display()
+            )
+            .into());
+        }
+        Ok(version)
+    };
+
+    match (snapshot.contains_key("members"), member) {
+        (false, None) => {}
+        (false, Some(_)) => {
+            return Err(format!("{} is a snapshot, not an archive", file.display()).into())
+        }
+        (true, member) => {
+            version(&snapshot)?;
+            let index: Vec<ArchiveEntry> =
+                serde_json::from_value(snapshot.remove("index").unwrap_or_default())
+                    .map_err(|e| invalid(&e))?;
+            let names: Vec<&str> = index.iter().map(|entry| entry.name.as_str()).collect();
+            let position = member
+                .as_ref()
+                .and_then(|member| names.iter().position(|name| name == member));
+            let Some(position) = position else {
+                return Err(format!(
+                    "{} is an archive of {}; select one as {}#<name>",
+                    file.display(),
+                    names.join(", "),
+                    file.display()
+                )
+                .into());
+            };
+            // Members are migrated like snapshot files.
+            let Some(Value::Array(mut members)) = snapshot.remove("members") else {
+                return Err(invalid(&"not an archive").into());
+            };
+            let Value::Object(member) = members.swap_remove(position) else {
+                return Err(invalid(&"expected an object").into());
+            };
+            snapshot = member;
+        }
+    }
+
+    let version = version(&snapshot)?;
+    for migrate in &migrations[version as usize - 1..] {
+        migrate(&mut snapshot).map_err(|e| invalid(&e))?;
+    }
+    snapshot.insert("version".to_string(), current.into());
+
+    serde_json::from_value(Value::Object(snapshot)).map_err(|e| invalid(&e).into())
+}
+
+/// Replace the user content of the node and the nodes below it with hashes, which keep
+/// equal texts equal within the run, so the snapshots of `--every` can still be compared.
+fn redact_node(node: &mut SnapshotNode, redact: Redact) {
+    let user_content = USER_CONTENT_ROLES
+        .iter()
+        .any(|role| role.name() == node.role);
+    if redact >= Redact::Names || user_content {
+        node.name = hash(&node.name);
+        node.description = hash(&node.description);
+    }
+    if let Some(text) = &mut node.text {
+        *text = hash(text);
+    }
+    if redact == Redact::All {
+        for value in node.attributes.values_mut() {
+            *value = hash(value);
+        }
+    }
+    for child in &mut node.children {
+        redact_node(child, redact);
+    }
+}
+
+/// The key of the redaction hashes, random for each run, so short or guessable texts
+/// cannot be found by hashing candidates. The hashes of different runs differ.
+static REDACTION_KEY: OnceLock<RandomState> = OnceLock::new();
+
+/// A keyed hash of `text` (SipHash), or nothing for no text.
+fn hash(text: &str) -> String {
+    if text.is_empty() {
+        return String::new();
+    }
+    let mut hasher = REDACTION_KEY.get_or_init(RandomState::new).build_hasher();
+    hasher.write(text.as_bytes());
+    format!("redacted:{:016x}", hasher.finish())
+}
+
+pub(crate) fn child_path(path: &str, index: usize) -> String {
+    match path {
+        "" => index.to_string(),
+        path => format!("{path}/{index}"),
+    }
+}
+
+/// The role with this name, as given by `Role::name`.
+pub(crate) fn role_from_name(name: &str) -> Option<Role> {
+    (0..=MAX_ROLE)
+        .filter_map(|number| Role::try_from(number).ok())
+        .find(|role| role.name() == name)
+}
+
+/// The tree below `root`, at index path `root_path`, as `audit::collect` would have collected it.
+/// The nodes get made-up object references, as they are not on the bus.
+pub(crate) fn to_tree(root: &SnapshotNode, root_path: &str, app_locale: &str) -> Result<AuditTree> {
+    let object = |path: &str| -> Result<ObjectRef> {
+        Ok(ObjectRef {
+            name: UniqueName::try_from(SNAPSHOT_BUS_NAME)?.into(),
+            path: ObjectPath::try_from(path)?.into(),
+        })
+    };
+    let node_object = |index: usize| object(&format!("/snapshot/{index}"));
+
+    // First the nodes in depth-first order, as `collect` has them, then their links.
+    let mut flat: Vec<(&SnapshotNode, String, Option<usize>)> = Vec::new();
+    let mut stack = vec![(root, root_path.to_string(), None)];
+    while let Some((node, path, parent)) = stack.pop() {
+        let index = flat.len();
+        for (child_index, child) in node.children.iter().enumerate().rev() {
+            stack.push((child, child_path(&path, child_index), Some(index)));
+        }
+        flat.push((node, path, parent));
+    }
+    let by_path: HashMap<&str, usize> = flat
+        .iter()
+        .enumerate()
+        .map(|(index, (_, path, _))| (path.as_str(), index))
+        .collect();
+    let target_object = |path: &str| match by_path.get(path) {
+        Some(index) => node_object(*index),
+        None => object(OUTSIDE_PATH),
+    };
+
+    let mut nodes: Vec<AuditNode> = Vec::with_capacity(flat.len());
+    for (index, (node, path, parent)) in flat.iter().enumerate() {
+        if let Some(parent) = parent {
+            nodes[*parent].children.push(index);
+        }
+
+        let mut states = StateSet::empty();
+        for state in &node.states {
+            states.insert(State::from(state.as_str()));
+        }
+        let mut relations = Vec::new();
+        for (name, targets) in &node.relations {
+            let Some(relation) = audit::relation_from_name(name) else {
+                continue;
+            };
+            let targets = targets
+                .iter()
+                .map(|target| target_object(target))
+                .collect::<Result<Vec<_>>>()?;
+            relations.push((relation, targets));
+        }
+        let reported_parent = match (&node.reported_parent, parent) {
+            (None, Some(parent)) => Some(node_object(*parent)?),
+            (None, None) | (Some(ParentLink::None), _) => None,
+            (Some(ParentLink::Node(path)), _) => Some(target_object(path)?),
+            (Some(ParentLink::Outside(_)), _) => Some(object(OUTSIDE_PATH)?),
+        };
+        let position = match parent {
+            Some(parent) => nodes[*parent].children.len() as i32 - 1,
+            None => 0,
+        };
+
+        nodes.push(AuditNode {
+            object: node_object(index)?,
+            path: path.clone(),
+            parent: *parent,
+            children: Vec::new(),
+            role: role_from_name(&node.role).unwrap_or(Role::Invalid),
+            name: node.name.clone(),
+            accessible_description: node.description.clone(),
+            accessible_id: node.accessible_id.clone(),
+            attributes: node
+                .attributes
+                .iter()
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect(),
+            states,
+            relations,
+            locale: node.locale.clone(),
+            table: node.table.clone(),
+            reported_parent,
+            index_in_parent: node.index_in_parent.unwrap_or(position),
+            extents: node.extents,
+            text: node.text.clone(),
+        });
+    }
+
+    Ok(AuditTree {
+        nodes,
+        app_locale: app_locale.to_string(),
+        tab_order: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// A stub upgrade from version 1, where the application was `application`, to version 2.
+    fn rename_application(snapshot: &mut Map<String, Value>) -> Result<()> {
+        let app = snapshot
+            .remove("application")
+            .ok_or("version 1 has an application")?;
+        snapshot.insert("app".to_string(), app);
+        Ok(())
+    }
+
+    const STUB_MIGRATIONS: &[Migration] = &[rename_application];
+
+    /// Write `contents` to a file of its own for the test `name`.
+    fn write(name: &str, contents: &Value) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("a11y-app-{}-{name}.a11y.json", std::process::id()));
+        std::fs::write(&path, contents.to_string()).unwrap();
+        path
+    }
+
+    #[test]
+    fn older_snapshots_are_migrated() {
+        let path = write(
+            "migrated",
+            &json!({ "application": "gedit", "root": { "role": "application" } }),
+        );
+        let snapshot = load_with(&path, STUB_MIGRATIONS).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(snapshot.version, 2);
+        assert_eq!(snapshot.app, "gedit");
+    }
+
+    #[test]
+    fn current_snapshots_are_not_migrated() {
+        let path = write(
+            "current",
+            &json!({ "version": 2, "app": "gedit", "root": { "role": "application" } }),
+        );
+        let snapshot = load_with(&path, STUB_MIGRATIONS).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(snapshot.app, "gedit");
+    }
+
+    #[test]
+    fn archive_members_are_migrated() {
+        let member =
+            json!({ "version": 1, "application": "gedit", "root": { "role": "application" } });
+        let path = write(
+            "archive",
+            &json!({
+                "version": 1,
+                "index": [{ "name": "gedit", "bus_name": ":1.2", "nodes": 1 }],
+                "members": [member],
+            }),
+        );
+        let snapshot = load_with(
+            &PathBuf::from(format!("{}#gedit", path.display())),
+            STUB_MIGRATIONS,
+        );
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(snapshot.unwrap().app, "gedit");
+    }
+
+    #[test]
+    fn newer_archives_are_refused() {
+        let path = write(
+            "newer-archive",
+            &json!({ "version": 3, "index": [], "members": [] }),
+        );
+        let error = load_with(&path, STUB_MIGRATIONS).err().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(error.to_string().contains("version 3"), "{error}");
+    }
+}
@@ -0,0 +1,143 @@
+//! Where and with what a snapshot was taken, so differences between snapshots from
+//! different environments can be told from regressions.
+
+use crate::{version::ATSPI_CRATE_VERSION, watch, Result};
+use atspi::{proxy::application::ApplicationProxy, ObjectRef};
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+use zbus::{names::UniqueName, zvariant::ObjectPath, Connection};
+
+/// Where the kernel keeps the host name.
+const HOSTNAME_FILE: &str = "/proc/sys/kernel/hostname";
+
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct Metadata {
+    /// The application's toolkit and its version, e.g. `GTK` and `3.24.41`.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub(crate) toolkit: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub(crate) toolkit_version: String,
+    /// The version of AT-SPI the application implements.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub(crate) atspi_version: String,
+    /// `XDG_CURRENT_DESKTOP`, e.g. `GNOME`.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub(crate) desktop: String,
+    /// `XDG_SESSION_TYPE`, e.g. `wayland`.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub(crate) session_type: String,
+    pub(crate) a11y_app_version: String,
+    pub(crate) atspi_crate_version: String,
+    /// When, in UTC, e.g. `2024-05-01T12:00:00Z`.
+    pub(crate) captured_at: String,
+    /// Only when asked for, as it identifies the machine.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) hostname: Option<String>,
+}
+
+impl Metadata {
+    /// The metadata of a snapshot of the application `app`, a unique bus name, taken now.
+    pub(crate) async fn collect(conn: &Connection, app: &str) -> Metadata {
+        let mut metadata = Metadata {
+            desktop: std::env::var("XDG_CURRENT_DESKTOP").unwrap_or_default(),
+            session_type: std::env::var("XDG_SESSION_TYPE").unwrap_or_default(),
+            a11y_app_version: env!("CARGO_PKG_VERSION").to_string(),
+            atspi_crate_version: ATSPI_CRATE_VERSION.to_string(),
+            captured_at: utc_timestamp(SystemTime::now()),
+            ..Metadata::default()
+        };
+        if let Ok(application) = application(conn, app).await {
+            metadata.toolkit = application.toolkit_name().await.unwrap_or_default();
+            metadata.toolkit_version = application.version().await.unwrap_or_default();
+            metadata.atspi_version = application.atspi_version().await.unwrap_or_default();
+        }
+        metadata
+    }
+
+    /// The host name of this machine, if it can be read.
+    pub(crate) fn hostname() -> Option<String> {
+        let hostname = std::fs::read_to_string(HOSTNAME_FILE).ok()?;
+        Some(hostname.trim().to_string()).filter(|hostname| !hostname.is_empty())
+    }
+
+    /// How the environments of two snapshots differ, a line each, e.g.
+    /// `toolkit: GTK 3.24.41 -> GTK 4.14.2`. When and where they were taken are left out,
+    /// as those always differ.
+    pub(crate) fn differences(&self, other: &Metadata) -> Vec<String> {
+        let toolkit = |metadata: &Metadata| {
+            format!("{} {}", metadata.toolkit, metadata.toolkit_version)
+                .trim()
+                .to_string()
+        };
+        let fields = [
+            ("toolkit", toolkit(self), toolkit(other)),
+            (
+                "AT-SPI version",
+                self.atspi_version.clone(),
+                other.atspi_version.clone(),
+            ),
+            ("desktop", self.desktop.clone(), other.desktop.clone()),
+            (
+                "session type",
+                self.session_type.clone(),
+                other.session_type.clone(),
+            ),
+            (
+                "a11y-app version",
+                self.a11y_app_version.clone(),
+                other.a11y_app_version.clone(),
+            ),
+            (
+                "atspi crate version",
+                self.atspi_crate_version.clone(),
+                other.atspi_crate_version.clone(),
+            ),
+        ];
+        fields
+            .into_iter()
+            .filter(|(_, old, new)| old != new)
+            .map(|(field, old, new)| format!("{field}: {old:?} -> {new:?}"))
+            .collect()
+    }
+}
+
+async fn application(conn: &Connection, app: &str) -> Result<ApplicationProxy<'static>> {
+    let root = ObjectRef {
+        name: UniqueName::try_from(app.to_string())?.into(),
+        path: ObjectPath::from_static_str_unchecked(crate::ACCESSIBLE_ROOT).into(),
+    };
+    watch::application_from_object_ref(conn, root).await
+}
+
+/// `time` in UTC as in RFC 3339, e.g. `2024-05-01T12:00:00Z`.
+fn utc_timestamp(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or_default();
+    let (days, seconds) = ((seconds / 86_400) as i64, seconds % 86_400);
+
+    // The civil date of a day number, after Howard Hinnant's `civil_from_days`.
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        seconds / 3_600,
+        seconds % 3_600 / 60,
+        seconds % 60
+    )
+}
@@ -0,0 +1,93 @@
+//! Following the focus while pressing Tab, to find the keyboard traversal order of an application.
+
+use crate::{
+    keys, script,
+    watch::{self, EventFilter},
+    Result,
+};
+use atspi::{AccessibilityConnection, Event, EventProperties, ObjectEvents, ObjectRef, State};
+use futures::{channel::mpsc::UnboundedReceiver, StreamExt};
+use std::time::Duration;
+use tracing::warn;
+
+/// How long to wait for the focus to move after pressing Tab.
+const FOCUS_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// After this many presses without the focus moving, it is stuck.
+const STUCK_PRESSES: usize = 3;
+
+/// The objects that got the focus while pressing Tab.
+pub(crate) struct TabOrder {
+    /// Each object that got the focus, in order, until the focus returned to an object
+    /// that had it before.
+    pub(crate) visited: Vec<ObjectRef>,
+    /// The objects the focus cycled through without returning to the first, if it did.
+    pub(crate) trap: Option<Vec<ObjectRef>>,
+}
+
+/// Press Tab up to `presses` times in the application sending as `sender`,
+/// and follow the focus until it goes around or gets trapped.
+///
+/// The keys go to whatever window is active, so one of the application's
+/// windows has to be active while this runs.
+pub(crate) async fn follow(
+    a11y: &AccessibilityConnection,
+    sender: &str,
+    presses: usize,
+) -> Result<TabOrder> {
+    let conn = a11y.connection();
+    watch::subscribe(a11y, &[EventFilter::parse("object:state-changed:focused")?]).await?;
+    let mut events = script::events(a11y);
+
+    let mut visited: Vec<ObjectRef> = Vec::new();
+    let mut unchanged = 0;
+    for _ in 0..presses {
+        keys::press_keys(conn, &["Tab".to_string()], Duration::ZERO).await?;
+
+        let Some(focused) = next_focus(&mut events, sender).await else {
+            unchanged += 1;
+            if unchanged >= STUCK_PRESSES {
+                // With a single object there is nowhere else to go. Otherwise Tab is handled
+                // by the focused object itself, e.g. a text view inserting tabs.
+                let trap = (visited.len() > 1).then(|| visited[visited.len() - 1..].to_vec());
+                return Ok(TabOrder { visited, trap });
+            }
+            continue;
+        };
+        unchanged = 0;
+
+        if let Some(first) = visited.iter().position(|object| *object == focused) {
+            // Back at the start means we went all the way around.
+            let trap = (first > 0).then(|| visited[first..].to_vec());
+            return Ok(TabOrder { visited, trap });
+        }
+        visited.push(focused);
+    }
+
+    warn!("the focus did not go around within {presses} presses of Tab");
+    Ok(TabOrder {
+        visited,
+        trap: None,
+    })
+}
+
+/// The object that gets the focus next, or `None` if the focus does not move in time.
+async fn next_focus(events: &mut UnboundedReceiver<Event>, sender: &str) -> Option<ObjectRef> {
+    let wait = async {
+        while let Some(event) = events.next().await {
+            if event.sender().as_str() != sender {
+                continue;
+            }
+            if let Event::Object(ObjectEvents::StateChanged(e)) = &event {
+                if e.state == State::Focused && e.enabled {
+                    return Some(event.object_ref());
+                }
+            }
+        }
+        None
+    };
+    tokio::time::timeout(FOCUS_TIMEOUT, wait)
+        .await
+        .ok()
+        .flatten()
+}
@@ -0,0 +1,260 @@
+//! Exporting the cells of data grids and tables through the Table interface.
+
+use crate::{
+    target::{self, Selected},
+    watch, ArgResult, Result,
+};
+use argh::{ArgsInfo, FromArgs};
+use atspi::{
+    proxy::{proxy_ext::ProxyExt, table::TableProxy},
+    AccessibilityConnection, ObjectRef,
+};
+use serde::Serialize;
+use std::path::PathBuf;
+use zbus::Connection;
+
+/// Export the cells of a table, with its headers, spans and caption, as CSV or JSON
+#[derive(FromArgs, ArgsInfo)]
+#[argh(subcommand, name = "table-dump")]
+pub struct TableDumpArgs {
+    /// the application, by bus name or application name
+    #[argh(positional)]
+    app: String,
+
+    /// the output format: csv or json (default: csv)
+    #[argh(option, from_str_fn(parse_format), default = "Format::Csv")]
+    format: Format,
+
+    /// only export the first rows
+    #[argh(option)]
+    max_rows: Option<i32>,
+
+    /// write the table to this file instead of printing it
+    #[argh(option)]
+    output: Option<PathBuf>,
+
+    /// the node, by the child indices leading to it from the application's root, e.g. `0/2/1`
+    #[argh(option)]
+    path: Option<String>,
+
+    /// the first node with this role and/or name, e.g. `table "Files"`
+    #[argh(option)]
+    query: Option<String>,
+}
+
+#[derive(Clone, Copy)]
+enum Format {
+    Csv,
+    Json,
+}
+
+fn parse_format(value: &str) -> ArgResult<Format> {
+    match value {
+        "csv" => Ok(Format::Csv),
+        "json" => Ok(Format::Json),
+        _ => Err(format!("Unknown format: {value}, expected csv or json")),
+    }
+}
+
+#[derive(Serialize)]
+struct Table {
+    caption: Option<String>,
+    summary: Option<String>,
+    rows: i32,
+    columns: i32,
+    /// One per column, empty for columns without a header.
+    column_headers: Vec<String>,
+    /// One per row, empty for rows without a header.
+    row_headers: Vec<String>,
+    /// The cells, each once: cells spanning several rows or columns are
+    /// listed at the row and column where they start.
+    cells: Vec<Cell>,
+}
+
+#[derive(Serialize)]
+struct Cell {
+    row: i32,
+    column: i32,
+    row_span: i32,
+    column_span: i32,
+    text: String,
+}
+
+/// Select the node and get its Table interface.
+async fn tabular(
+    conn: &Connection,
+    app: &str,
+    path: Option<&str>,
+    query: Option<&str>,
+) -> Result<(Selected, TableProxy<'static>)> {
+    let node = target::select(conn, app, path, query).await?;
+    let proxies = node.proxy.proxies().await?;
+    match proxies.table().await {
+        Ok(table) => Ok((node, table)),
+        Err(_) => Err(format!(
+            "{} at {} does not implement the Table interface",
+            node.description,
+            node.location()
+        )
+        .into()),
+    }
+}
+
+pub async fn table_dump(a11y: &AccessibilityConnection, args: TableDumpArgs) -> Result<()> {
+    let conn = a11y.connection();
+    let (node, table) =
+        tabular(conn, &args.app, args.path.as_deref(), args.query.as_deref()).await?;
+    eprintln!("Table: {} at {}", node.description, node.location());
+
+    let table = read_table(conn, &table, args.max_rows).await?;
+    let contents = match args.format {
+        Format::Csv => to_csv(&table),
+        Format::Json => serde_json::to_string_pretty(&table)? + "\n",
+    };
+    match &args.output {
+        Some(path) => std::fs::write(path, contents)
+            .map_err(|e| format!("Could not write {}: {e}", path.display()))?,
+        None => print!("{contents}"),
+    }
+    Ok(())
+}
+
+async fn read_table(
+    conn: &Connection,
+    table: &TableProxy<'_>,
+    max_rows: Option<i32>,
+) -> Result<Table> {
+    let rows = table.nrows().await?;
+    let rows = max_rows.map_or(rows, |max| rows.min(max.max(0)));
+    let columns = table.ncolumns().await?;
+
+    let caption = match table.caption().await {
+        Ok(caption) => text_of(conn, caption).await,
+        Err(_) => None,
+    };
+    let summary = match table.summary().await {
+        Ok(summary) => text_of(conn, summary).await,
+        Err(_) => None,
+    };
+
+    let mut column_headers = Vec::with_capacity(columns as usize);
+    for column in 0..columns {
+        let header = match table.get_column_header(column).await {
+            Ok(header) => text_of(conn, header).await,
+            Err(_) => None,
+        };
+        let header = match header {
+            Some(header) => header,
+            None => table
+                .get_column_description(column)
+                .await
+                .unwrap_or_default(),
+        };
+        column_headers.push(header);
+    }
+
+    let mut row_headers = Vec::with_capacity(rows as usize);
+    let mut cells = Vec::new();
+    for row in 0..rows {
+        let header = match table.get_row_header(row).await {
+            Ok(header) => text_of(conn, header).await,
+            Err(_) => None,
+        };
+        let header = match header {
+            Some(header) => header,
+            None => table.get_row_description(row).await.unwrap_or_default(),
+        };
+        row_headers.push(header);
+
+        for column in 0..columns {
+            let index = table.get_index_at(row, column).await?;
+            let (_, start_row, start_column, row_span, column_span, _) =
+                table.get_row_column_extents_at_index(index).await?;
+            // Covered by a cell spanning from an earlier row or column.
+            if (start_row, start_column) != (row, column) {
+                continue;
+            }
+
+            let cell = table.get_accessible_at(row, column).await?;
+            cells.push(Cell {
+                row,
+                column,
+                row_span,
+                column_span,
+                text: text_of(conn, cell).await.unwrap_or_default(),
+            });
+        }
+    }
+
+    Ok(Table {
+        caption,
+        summary,
+        rows,
+        columns,
+        column_headers,
+        row_headers,
+        cells,
+    })
+}
+
+/// The name of an object, or its text if it has no name; `None` for no object.
+async fn text_of(conn: &Connection, object: ObjectRef) -> Option<String> {
+    if object.path.as_str() == watch::NULL_PATH {
+        return None;
+    }
+    let proxy = watch::accessible_from_object_ref(conn, object).await.ok()?;
+    match proxy.name().await {
+        Ok(name) if !name.is_empty() => Some(name),
+        _ => {
+            let text = proxy.proxies().await.ok()?.text().await.ok()?;
+            let count = text.character_count().await.ok()?;
+            text.get_text(0, count).await.ok()
+        }
+    }
+}
+
+/// The table as CSV: a header row of column headers, and a first column of row headers if
+/// any row has one. The caption and summary come first as `#` comments.
+fn to_csv(table: &Table) -> String {
+    let mut csv = String::new();
+    for comment in [&table.caption, &table.summary].into_iter().flatten() {
+        csv.push_str(&format!("# {}\n", comment.replace('\n', " ")));
+    }
+
+    let has_row_headers = table.row_headers.iter().any(|header| !header.is_empty());
+    let mut grid = vec![vec![String::new(); table.columns as usize]; table.rows as usize];
+    for cell in &table.cells {
+        grid[cell.row as usize][cell.column as usize] = cell.text.clone();
+    }
+
+    let mut header: Vec<&str> = Vec::new();
+    if has_row_headers {
+        header.push("");
+    }
+    header.extend(table.column_headers.iter().map(String::as_str));
+    csv.push_str(&csv_line(&header));
+
+    for (row, cells) in grid.iter().enumerate() {
+        let mut line: Vec<&str> = Vec::new();
+        if has_row_headers {
+            line.push(&table.row_headers[row]);
+        }
+        line.extend(cells.iter().map(String::as_str));
+        csv.push_str(&csv_line(&line));
+    }
+    csv
+}
+
+fn csv_line(fields: &[&str]) -> String {
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|field| {
+            if field.contains([',', '"', '\n', '\r']) || field.starts_with('#') {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.to_string()
+            }
+        })
+        .collect();
+    fields.join(",") + "\n"
+}
@@ -0,0 +1,268 @@
+//! Selecting a single node of an application's tree, for the commands that act on one node.
+//!
+//! A node is selected either by its index path, the child indices leading from the
+//! application's root to the node (e.g. `0/2/1`), or by a query on its role and name,
+//! in the notation the event monitor uses to describe nodes (e.g. `push button "Save"`).
+
+use crate::{config, exit::Failure, watch, Result};
+use atspi::{proxy::accessible::AccessibleProxy, ObjectRef};
+use tracing::debug;
+use zbus::{fdo::DBusProxy, zvariant::ObjectPath, Connection};
+
+/// A node selected by `select`.
+#[derive(Clone)]
+pub(crate) struct Selected {
+    pub(crate) proxy: AccessibleProxy<'static>,
+    /// The index path of the node, e.g. `0/2/1`, empty for the root.
+    pub(crate) path: String,
+    /// Role and name of the node, e.g. `push button "Save"`.
+    pub(crate) description: String,
+}
+
+impl Selected {
+    /// Where the node is, for messages: `path 0/2/1`, or `the root`.
+    pub(crate) fn location(&self) -> String {
+        if self.path.is_empty() {
+            "the root".to_string()
+        } else {
+            format!("path {}", self.path)
+        }
+    }
+}
+
+/// A role and/or name to look for, e.g. `push button "Save"`, `push button` or `"Save"`.
+pub(crate) struct Query {
+    /// The role, in lower case.
+    pub(crate) role: Option<String>,
+    pub(crate) name: Option<String>,
+}
+
+impl Query {
+    /// Parse a query, or with `@<name>` the query saved as `name` in the configuration.
+    pub(crate) fn parse(query: &str) -> Result<Query> {
+        let query = match query.trim().strip_prefix('@') {
+            Some(name) => config::query(name)?.trim(),
+            None => query.trim(),
+        };
+        let (role, name) = match query.find('"') {
+            Some(start) => {
+                let end = query.rfind('"').filter(|end| *end > start).ok_or_else(|| {
+                    format!("Invalid query {query:?}: the name is missing its closing quote")
+                })?;
+                if !query[end + 1..].trim().is_empty() {
+                    return Err(format!("Invalid query {query:?}: text after the name").into());
+                }
+                (&query[..start], Some(query[start + 1..end].to_string()))
+            }
+            None => (query, None),
+        };
+
+        let role = role.trim();
+        let role = (!role.is_empty()).then(|| role.to_lowercase());
+        if role.is_none() && name.is_none() {
+            return Err("Invalid query: expected a role, a quoted name, or both".into());
+        }
+
+        Ok(Query { role, name })
+    }
+
+    /// Whether a node with this role name and name matches, for nodes that are not on the bus.
+    pub(crate) fn matches_values(&self, role: &str, name: &str) -> bool {
+        self.role
+            .as_ref()
+            .is_none_or(|query| role.eq_ignore_ascii_case(query))
+            && self.name.as_ref().is_none_or(|query| query == name)
+    }
+
+    async fn matches(&self, proxy: &AccessibleProxy<'_>) -> bool {
+        if let Some(role) = &self.role {
+            match proxy.get_role().await {
+                Ok(actual) if actual.name().eq_ignore_ascii_case(role) => {}
+                _ => return false,
+            }
+        }
+        if let Some(name) = &self.name {
+            match proxy.name().await {
+                Ok(actual) if actual == *name => {}
+                _ => return false,
+            }
+        }
+        true
+    }
+}
+
+/// Select a node of the application `app` (a bus name or application name) by its
+/// index `path`, by `query`, or the application's root if neither is given.
+pub(crate) async fn select(
+    conn: &Connection,
+    app: &str,
+    path: Option<&str>,
+    query: Option<&str>,
+) -> Result<Selected> {
+    let mut apps = crate::parse_bus_name(app.to_string(), conn).await?;
+    let (name, bus_name) = match apps.len() {
+        0 => return Err(Failure::not_found(format!("No application found: {app}"))),
+        1 => apps.remove(0),
+        n => {
+            return Err(format!(
+                "{app} matches {n} applications, select one by its bus name or with --index"
+            )
+            .into())
+        }
+    };
+
+    let dbus = DBusProxy::new(conn).await?;
+    let root = ObjectRef {
+        name: watch::unique_name(&dbus, bus_name).await?,
+        path: ObjectPath::from_static_str_unchecked(crate::ACCESSIBLE_ROOT).into(),
+    };
+    let root = watch::accessible_from_object_ref(conn, root).await?;
+
+    let (proxy, path) = match (path, query) {
+        (Some(_), Some(_)) => return Err("Select a node by --path or by --query, not both".into()),
+        (Some(path), None) => (follow_path(conn, root, path).await?, path.to_string()),
+        (None, Some(query)) => find(conn, root, "", &Query::parse(query)?, 1)
+            .await?
+            .pop()
+            .ok_or_else(|| Failure::not_found(format!("No node of {name} matches {query:?}")))?,
+        (None, None) => (root, String::new()),
+    };
+
+    let description = watch::describe_node(&proxy).await;
+    debug!("Selected {description} at path {path:?} of {name}");
+    Ok(Selected {
+        proxy,
+        path,
+        description,
+    })
+}
+
+/// Walk down from `root` along the child indices of `path`, e.g. `0/2/1`.
+pub(crate) async fn follow_path(
+    conn: &Connection,
+    root: AccessibleProxy<'static>,
+    path: &str,
+) -> Result<AccessibleProxy<'static>> {
+    let mut node = root;
+    for segment in path.split('/').filter(|segment| !segment.is_empty()) {
+        let index: i32 = segment
+            .parse()
+            .map_err(|_| format!("Invalid path {path:?}: {segment:?} is not a child index"))?;
+        let count = node.child_count().await?;
+        if index < 0 || index >= count {
+            return Err(format!(
+                "Invalid path {path:?}: {} has {count} children, there is no child {index}",
+                watch::describe_node(&node).await
+            )
+            .into());
+        }
+        let child = node.get_child_at_index(index).await?;
+        node = watch::accessible_from_object_ref(conn, child).await?;
+    }
+    Ok(node)
+}
+
+/// The nodes below `node`, in depth-first order, that match `query`, at most `limit` of them.
+pub(crate) async fn search(
+    conn: &Connection,
+    node: &Selected,
+    query: &str,
+    limit: usize,
+) -> Result<Vec<Selected>> {
+    let query = Query::parse(query)?;
+    let found = find(conn, node.proxy.clone(), &node.path, &query, limit).await?;
+
+    let mut selected = Vec::with_capacity(found.len());
+    for (proxy, path) in found {
+        let description = watch::describe_node(&proxy).await;
+        selected.push(Selected {
+            proxy,
+            path,
+            description,
+        });
+    }
+    Ok(selected)
+}
+
+/// `node` and the nodes below it, in depth-first order.
+pub(crate) async fn descendants(conn: &Connection, node: &Selected) -> Result<Vec<Selected>> {
+    let mut stack = vec![node.clone()];
+    let mut nodes = Vec::new();
+
+    while let Some(node) = stack.pop() {
+        let children = node.proxy.get_children().await.unwrap_or_default();
+        // Pushed in reverse, so the first child is visited first.
+        for (index, child) in children.into_iter().enumerate().rev() {
+            let proxy = watch::accessible_from_object_ref(conn, child).await?;
+            let path = match node.path.as_str() {
+                "" => index.to_string(),
+                path => format!("{path}/{index}"),
+            };
+            stack.push(Selected {
+                description: watch::describe_node(&proxy).await,
+                path,
+                proxy,
+            });
+        }
+        nodes.push(node);
+    }
+
+    Ok(nodes)
+}
+
+/// The nodes below `root`, in depth-first order, that match `query`, at most `limit` of them,
+/// with their index paths. `root_path` is the index path of `root` itself.
+async fn find(
+    conn: &Connection,
+    root: AccessibleProxy<'static>,
+    root_path: &str,
+    query: &Query,
+    limit: usize,
+) -> Result<Vec<(AccessibleProxy<'static>, String)>> {
+    let root_path: Vec<String> = root_path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(str::to_string)
+        .collect();
+    let mut stack = vec![(root, root_path)];
+    let mut found = Vec::new();
+
+    while let Some((node, path)) = stack.pop() {
+        if query.matches(&node).await {
+            found.push((node.clone(), path.join("/")));
+            if found.len() >= limit {
+                break;
+            }
+        }
+
+        let children = node.get_children().await.unwrap_or_default();
+        // Pushed in reverse, so the first child is visited first.
+        for (index, child) in children.into_iter().enumerate().rev() {
+            let child = watch::accessible_from_object_ref(conn, child).await?;
+            let mut child_path = path.clone();
+            child_path.push(index.to_string());
+            stack.push((child, child_path));
+        }
+    }
+
+    Ok(found)
+}
+
+/// The index path of `node`, found by walking up to the application's root.
+pub(crate) async fn index_path(conn: &Connection, node: &AccessibleProxy<'_>) -> Result<String> {
+    let mut indices = Vec::new();
+    let mut current = node.clone();
+    while current.inner().path().as_str() != crate::ACCESSIBLE_ROOT {
+        if indices.len() >= watch::MAX_ANCESTRY_DEPTH {
+            return Err("The node is nested too deep, is its hierarchy cyclic?".into());
+        }
+        indices.push(current.get_index_in_parent().await?.to_string());
+        let parent = current.parent().await?;
+        if parent.path.as_str() == watch::NULL_PATH {
+            return Err("The node is not part of the application's tree".into());
+        }
+        current = watch::accessible_from_object_ref(conn, parent).await?;
+    }
+    indices.reverse();
+    Ok(indices.join("/"))
+}
@@ -0,0 +1,752 @@
+//! The tree of accessible objects of an application, as `a11y-app -p` prints it, for other
+//! programs to build and print too: a [`TreeBuilder`] builds it from the root of an
+//! application, and `display_tree::AsTree` prints it.
+
+use crate::{exit, interrupt, retry::retry, snapshot, ArgResult, Result};
+use atspi::{proxy::accessible::AccessibleProxy, ObjectRef, Role};
+use display_tree::{DisplayTree, Style};
+use futures::{stream, Stream, StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, future::Future, sync::OnceLock, time::Duration};
+pub use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+use zbus::{names::BusName, proxy::CacheProperties, Connection};
+
+/// A node of the tree of accessible objects of an application: what it is, what
+/// [`Detail`] was fetched of it, and the nodes below it. Printed with
+/// [`display_tree::AsTree`], it looks like the output of `tree`.
+///
+/// It serializes with serde as snapshots do, roles by name and without what was not
+/// fetched, so trees can be saved and read back.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct A11yNode {
+    /// The object on the bus, if the tree was built from an application and the node was
+    /// reached.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub object: Option<ObjectRef>,
+    /// The role, if the application reported one.
+    #[serde(default, with = "role_name")]
+    pub role: Option<Role>,
+    /// The name, if fetched, see [`Detail::Names`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// The states, e.g. `focusable`, if fetched, see [`Detail::Full`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub states: Vec<String>,
+    /// The attributes, if fetched, see [`Detail::Full`].
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub attributes: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<A11yNode>,
+}
+
+/// Roles by their names, e.g. `push button`, as in snapshots, rather than by number.
+mod role_name {
+    use crate::snapshot;
+    use atspi::Role;
+    use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+    pub(super) fn serialize<S: Serializer>(
+        role: &Option<Role>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        role.map(|role| role.name()).serialize(serializer)
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Role>, D::Error> {
+        match Option::<String>::deserialize(deserializer)? {
+            Some(name) => snapshot::role_from_name(&name)
+                .map(Some)
+                .ok_or_else(|| D::Error::custom(format!("unknown role: {name}"))),
+            None => Ok(None),
+        }
+    }
+}
+
+impl DisplayTree for A11yNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter, style: Style) -> std::fmt::Result {
+        self.fmt_with(f, style, &mut vec![])
+    }
+}
+
+impl A11yNode {
+    fn fmt_with(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        style: Style,
+        prefix: &mut Vec<bool>,
+    ) -> std::fmt::Result {
+        for (i, is_last_at_i) in prefix.iter().enumerate() {
+            // if it is the last portion of the line
+            let is_last = i == prefix.len() - 1;
+            match (is_last, *is_last_at_i) {
+                (true, true) => write!(f, "{}", style.char_set.end_connector)?,
+                (true, false) => write!(f, "{}", style.char_set.connector)?,
+                // four spaces to emulate `tree`
+                (false, true) => write!(f, "    ")?,
+                // three spaces and vertical char
+                (false, false) => write!(f, "{}   ", style.char_set.vertical)?,
+            }
+        }
+
+        // two horizontal chars to mimic `tree`
+        write!(
+            f,
+            "{}{} {:?}",
+            style.char_set.horizontal, style.char_set.horizontal, self.role
+        )?;
+        match &self.name {
+            Some(name) => writeln!(f, " {name:?}")?,
+            None => writeln!(f)?,
+        }
+
+        for (i, child) in self.children.iter().enumerate() {
+            prefix.push(i == self.children.len() - 1);
+            child.fmt_with(f, style, prefix)?;
+            prefix.pop();
+        }
+
+        Ok(())
+    }
+}
+
+impl A11yNode {
+    pub(crate) fn from_snapshot(node: &snapshot::SnapshotNode) -> A11yNode {
+        A11yNode {
+            role: snapshot::role_from_name(&node.role),
+            children: node.children.iter().map(A11yNode::from_snapshot).collect(),
+            ..A11yNode::default()
+        }
+    }
+
+    /// The tree below `ap`, as [`TreeBuilder::default`] builds it.
+    pub async fn from_accessible_proxy_iterative(ap: AccessibleProxy<'_>) -> Result<A11yNode> {
+        TreeBuilder::default().build(ap).await
+    }
+
+    /// The events of the tree below `proxy`, traversed as `options` say, see
+    /// [`TreeBuilder::stream`].
+    pub fn stream<'a>(
+        proxy: AccessibleProxy<'a>,
+        options: &TreeBuilder,
+    ) -> impl Stream<Item = Result<NodeEvent>> + 'a {
+        options.stream(proxy)
+    }
+}
+
+/// How much of each node a [`TreeBuilder`] fetches, from least to most.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Detail {
+    /// Only the shape of the tree, without roles: the fewest calls to the application.
+    Shape,
+    /// The role of each node.
+    #[default]
+    Roles,
+    /// The role and name of each node.
+    Names,
+    /// The role, name, states and attributes of each node.
+    Full,
+}
+
+/// How a tree of accessible objects is traversed: how deep, which nodes are left out, how
+/// many calls are made at once and how long each may take, what is fetched of each node,
+/// whether proxies cache properties and what cancels the traversal.
+///
+/// The default traverses the whole tree, fetching the roles of all children of a node at
+/// once, without caching and waiting for each call as long as D-Bus does.
+#[derive(Clone, Debug)]
+pub struct TreeBuilder {
+    max_depth: Option<usize>,
+    skip_roles: Vec<Role>,
+    concurrency: Option<usize>,
+    timeout: Option<Duration>,
+    detail: Detail,
+    cache: CacheProperties,
+    cancellation: Option<CancellationToken>,
+}
+
+impl Default for TreeBuilder {
+    fn default() -> Self {
+        TreeBuilder {
+            max_depth: None,
+            skip_roles: Vec::new(),
+            concurrency: None,
+            timeout: None,
+            detail: Detail::default(),
+            cache: CacheProperties::No,
+            cancellation: None,
+        }
+    }
+}
+
+impl TreeBuilder {
+    pub fn new() -> Self {
+        TreeBuilder::default()
+    }
+
+    /// Leave nodes `depth` levels below the root as leaves, 0 being the root itself.
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Leave out nodes with any of `roles`, and everything below them.
+    pub fn skip_roles(mut self, roles: impl IntoIterator<Item = Role>) -> Self {
+        self.skip_roles.extend(roles);
+        self
+    }
+
+    /// Make at most `calls` calls at once for the children of a node, rather than one for
+    /// each child, for applications that choke on many at once.
+    pub fn concurrency(mut self, calls: usize) -> Self {
+        self.concurrency = Some(calls.max(1));
+        self
+    }
+
+    /// Give up on a call after `timeout`, as if it failed, so an application that hangs
+    /// does not hold up the traversal.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// What to fetch of each node.
+    pub fn detail(mut self, detail: Detail) -> Self {
+        self.detail = detail;
+        self
+    }
+
+    /// Whether the proxies of the nodes cache their properties, see [`CacheProperties`].
+    pub fn cache(mut self, cache: CacheProperties) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// Stop traversing as soon as `token` is cancelled, dropping the calls in flight, with
+    /// a [`Cancelled`] error, so an application embedding the traversal can abort it.
+    pub fn cancel_on(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// The tree below `root`, traversed without recursion, so deep trees do not overflow
+    /// the stack. Nodes whose children cannot be fetched are left as leaves, with a warning.
+    pub async fn build(&self, root: AccessibleProxy<'_>) -> Result<A11yNode> {
+        match &self.cancellation {
+            Some(token) => tokio::select! {
+                tree = self.traverse(root) => tree,
+                _ = token.cancelled() => Err(Box::new(Cancelled)),
+            },
+            None => self.traverse(root).await,
+        }
+    }
+
+    async fn traverse(&self, root: AccessibleProxy<'_>) -> Result<A11yNode> {
+        let connection = root.inner().connection().clone();
+        // Contains the processed `A11yNode`'s.
+        let mut nodes: Vec<A11yNode> = Vec::new();
+
+        // Contains the `AccessibleProxy` yet to be processed, with its depth below the root
+        // and its role, if fetched already.
+        let mut stack: Vec<(AccessibleProxy, usize, Option<Option<Role>>)> = vec![(root, 0, None)];
+        let _traversal = interrupt::Traversal::begin();
+
+        // If the stack has an `AccessibleProxy`, we take the last.
+        while let Some((ap, depth, role)) = stack.pop() {
+            if interrupt::interrupted() {
+                // The nodes not reached yet stay leaves without a role, so the tree still
+                // folds together.
+                warn!(
+                    "interrupted, the tree of {} is partial: {} nodes were not reached",
+                    ap.inner().destination(),
+                    stack.len() + 1
+                );
+                nodes.push(A11yNode::default());
+                while stack.pop().is_some() {
+                    nodes.push(A11yNode::default());
+                }
+                break;
+            }
+            let mut node = self.node(&ap, role).await;
+            if self.is_deepest(depth) {
+                nodes.push(node);
+                continue;
+            }
+
+            let children = self.children(&connection, &ap).await?;
+            // Placeholders, for the folding below to know how many children to take.
+            node.children = vec![A11yNode::default(); children.len()];
+            nodes.push(node);
+            stack.extend(
+                children
+                    .into_iter()
+                    .map(|(_, child, role)| (child, depth + 1, role)),
+            );
+        }
+
+        let mut fold_stack: Vec<A11yNode> = Vec::with_capacity(nodes.len());
+
+        while let Some(mut node) = nodes.pop() {
+            if node.children.is_empty() {
+                fold_stack.push(node);
+                continue;
+            }
+
+            // If the node has children, we fold in the children from 'fold_stack'.
+            // There may be more on 'fold_stack' than the node requires.
+            let begin = fold_stack.len().saturating_sub(node.children.len());
+            node.children = fold_stack.split_off(begin);
+            fold_stack.push(node);
+        }
+
+        fold_stack.pop().ok_or("No root node built".into())
+    }
+
+    /// The events of the tree below `root`, depth first, as it is traversed: the nodes are
+    /// not kept, so trees of any size can be processed as they come in.
+    pub fn stream<'a>(
+        &self,
+        root: AccessibleProxy<'a>,
+    ) -> impl Stream<Item = Result<NodeEvent>> + 'a {
+        let walk = Walk {
+            builder: self.clone(),
+            connection: root.inner().connection().clone(),
+            root: Some(root),
+            pending: Vec::new(),
+            _traversal: interrupt::Traversal::begin(),
+        };
+        stream::unfold(walk, |mut walk| async move {
+            let event = walk.next().await?;
+            Some((event, walk))
+        })
+    }
+
+    /// Traverse the tree below `root`, depth first, telling `visitor` about each node as it
+    /// is reached. Like [`TreeBuilder::stream`], the nodes are not kept.
+    pub async fn visit(
+        &self,
+        root: AccessibleProxy<'_>,
+        visitor: &mut impl TreeVisitor,
+    ) -> Result<()> {
+        drive(self.stream(root), visitor).await
+    }
+
+    /// Whether the nodes at `depth` are left as leaves.
+    pub(crate) fn is_deepest(&self, depth: usize) -> bool {
+        self.max_depth.is_some_and(|max_depth| depth >= max_depth)
+    }
+
+    /// The node for `ap`, without children, with what the detail asks for. Its `role` is
+    /// only fetched if not fetched already.
+    async fn node(&self, ap: &AccessibleProxy<'_>, role: Option<Option<Role>>) -> A11yNode {
+        let role = match role {
+            Some(role) => role,
+            None if self.detail >= Detail::Roles => self.call(|| ap.get_role()).await.ok(),
+            None => None,
+        };
+        let mut node = A11yNode {
+            object: object_of(ap),
+            role: role.filter(|_| self.detail >= Detail::Roles),
+            ..A11yNode::default()
+        };
+        if self.detail >= Detail::Names {
+            node.name = self.call(|| ap.name()).await.ok();
+        }
+        if self.detail >= Detail::Full {
+            if let Ok(states) = self.call(|| ap.get_state()).await {
+                node.states = states
+                    .iter()
+                    .map(|state| state.to_static_str().to_string())
+                    .collect();
+            }
+            if let Ok(attributes) = self.call(|| ap.get_attributes()).await {
+                node.attributes = attributes.into_iter().collect();
+            }
+        }
+        node
+    }
+
+    /// Proxies for the children of `ap`, by their index among all its children, with their
+    /// roles if those are needed, without the children to skip. A node whose children
+    /// cannot be fetched has none, with a warning.
+    pub(crate) async fn children(
+        &self,
+        connection: &Connection,
+        ap: &AccessibleProxy<'_>,
+    ) -> Result<Vec<(usize, AccessibleProxy<'static>, Option<Option<Role>>)>> {
+        let node_name = format!(
+            "node: {} on {}",
+            ap.inner().path(),
+            ap.inner().destination()
+        );
+        let objects = match self.call(|| ap.get_children()).await {
+            // Ok can also be an empty vector, which is fine.
+            Ok(children) => children,
+            Err(e) => {
+                warn!("Error getting children of {node_name}: {e} -- continuing with next node.");
+                exit::unreached();
+                return Ok(Vec::new());
+            }
+        };
+        debug!("{node_name} has {} children", objects.len());
+
+        let concurrency = self.concurrency.unwrap_or(usize::MAX);
+        let proxies: Vec<AccessibleProxy<'static>> = stream::iter(objects)
+            .map(|object| self.proxy(connection, object))
+            .buffered(concurrency)
+            .try_collect()
+            .await?;
+
+        if self.detail < Detail::Roles && self.skip_roles.is_empty() {
+            return Ok(proxies
+                .into_iter()
+                .enumerate()
+                .map(|(index, child)| (index, child, None))
+                .collect());
+        }
+        let roles: Vec<Option<Role>> = stream::iter(&proxies)
+            .map(|child| async move { self.call(|| child.get_role()).await.ok() })
+            .buffered(concurrency)
+            .collect()
+            .await;
+        Ok(proxies
+            .into_iter()
+            .zip(roles)
+            .enumerate()
+            .filter(|(_, (_, role))| !role.is_some_and(|role| self.skip_roles.contains(&role)))
+            .map(|(index, (child, role))| (index, child, Some(role)))
+            .collect())
+    }
+
+    async fn proxy(
+        &self,
+        connection: &Connection,
+        object: ObjectRef,
+    ) -> Result<AccessibleProxy<'static>> {
+        Ok(AccessibleProxy::builder(connection)
+            .destination(object.name)?
+            .path(object.path)?
+            .cache_properties(self.cache)
+            .build()
+            .await?)
+    }
+
+    /// The result of `call`, retried as `--retries` says and given up on after the timeout.
+    pub(crate) async fn call<T, F, Fut>(&self, mut call: F) -> std::result::Result<T, String>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = zbus::Result<T>>,
+    {
+        retry(|| {
+            let call = call();
+            async move {
+                match self.timeout {
+                    Some(timeout) => tokio::time::timeout(timeout, call)
+                        .await
+                        .map_err(|_| format!("no answer within {} ms", timeout.as_millis()))?
+                        .map_err(|e| e.to_string()),
+                    None => call.await.map_err(|e| e.to_string()),
+                }
+            }
+        })
+        .await
+    }
+}
+
+/// Something done with each node of a tree as [`TreeBuilder::visit`] reaches it, e.g. an
+/// audit, statistics or an exporter, without the tree being kept.
+pub trait TreeVisitor {
+    /// `node`, without its children, `depth` levels below the root.
+    fn visit_node(&mut self, node: &A11yNode, depth: usize);
+
+    /// The children of the node visited last are visited next, at `depth`.
+    fn enter_children(&mut self, _depth: usize) {}
+
+    /// All children at `depth` of a node were visited.
+    fn leave_children(&mut self, _depth: usize) {}
+
+    /// A node could not be reached. The traversal stops with the error this returns, by
+    /// default `error` itself, and goes on without the node if it returns `Ok`.
+    fn on_error(&mut self, error: Box<dyn std::error::Error>) -> Result<()> {
+        Err(error)
+    }
+}
+
+/// The error of a traversal cancelled with the token given to [`TreeBuilder::cancel_on`].
+#[derive(Debug)]
+pub struct Cancelled;
+
+impl std::fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("the traversal was cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// What a stream of a tree yields, depth first: each node is entered, then the nodes below
+/// it are, and then it is left.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum NodeEvent {
+    /// A node, without its children, and how many levels it is below the root.
+    Enter { node: A11yNode, depth: usize },
+    /// The node entered last at `depth` is done, with everything below it.
+    Leave { depth: usize },
+}
+
+/// The state of a stream of a tree.
+struct Walk<'a> {
+    builder: TreeBuilder,
+    connection: Connection,
+    /// The root, until it is entered.
+    root: Option<AccessibleProxy<'a>>,
+    /// For each node entered and not yet left, its children not yet entered, the next one
+    /// last.
+    pending: Vec<Vec<(AccessibleProxy<'a>, Option<Option<Role>>)>>,
+    _traversal: interrupt::Traversal,
+}
+
+impl Walk<'_> {
+    async fn next(&mut self) -> Option<Result<NodeEvent>> {
+        if self.root.is_none() && self.pending.is_empty() {
+            return None;
+        }
+        let event = match self.builder.cancellation.clone() {
+            Some(token) => tokio::select! {
+                event = self.step() => Some(event),
+                _ = token.cancelled() => None,
+            },
+            None => Some(self.step().await),
+        };
+        match event {
+            Some(event) => event,
+            None => {
+                // Drop the proxies of the nodes not entered yet, ending the stream.
+                self.root = None;
+                self.pending.clear();
+                Some(Err(Box::new(Cancelled)))
+            }
+        }
+    }
+
+    async fn step(&mut self) -> Option<Result<NodeEvent>> {
+        if interrupt::interrupted() {
+            if self.root.is_some() || !self.pending.is_empty() {
+                warn!("interrupted, the stream of the tree ends before all nodes were left");
+                self.root = None;
+                self.pending.clear();
+            }
+            return None;
+        }
+
+        let (ap, role) = match self.root.take() {
+            Some(root) => (root, None),
+            None => match self.pending.last_mut()?.pop() {
+                Some(child) => child,
+                None => {
+                    self.pending.pop();
+                    return Some(Ok(NodeEvent::Leave {
+                        depth: self.pending.len(),
+                    }));
+                }
+            },
+        };
+
+        let depth = self.pending.len();
+        let node = self.builder.node(&ap, role).await;
+        let mut children = Vec::new();
+        if !self.builder.is_deepest(depth) {
+            // The node is not entered, so it is not left either.
+            match self.builder.children(&self.connection, &ap).await {
+                Ok(found) => {
+                    children = found
+                        .into_iter()
+                        .map(|(_, child, role)| (child, role))
+                        .collect();
+                }
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        children.reverse();
+        self.pending.push(children);
+        Some(Ok(NodeEvent::Enter { node, depth }))
+    }
+}
+
+/// Tell `visitor` about the nodes of `events`, and when their children begin and end.
+async fn drive(
+    events: impl Stream<Item = Result<NodeEvent>>,
+    visitor: &mut impl TreeVisitor,
+) -> Result<()> {
+    futures::pin_mut!(events);
+    // For each node entered and not yet left, whether its children were entered.
+    let mut entered_children: Vec<bool> = Vec::new();
+    while let Some(event) = events.next().await {
+        match event {
+            Ok(NodeEvent::Enter { node, depth }) => {
+                if let Some(entered) = entered_children.last_mut() {
+                    if !*entered {
+                        *entered = true;
+                        visitor.enter_children(depth);
+                    }
+                }
+                entered_children.push(false);
+                visitor.visit_node(&node, depth);
+            }
+            Ok(NodeEvent::Leave { depth }) => {
+                if entered_children.pop() == Some(true) {
+                    visitor.leave_children(depth + 1);
+                }
+            }
+            Err(e) => visitor.on_error(e)?,
+        }
+    }
+    Ok(())
+}
+
+/// The object `proxy` is for, if it is addressed by the unique name of its application.
+fn object_of(proxy: &AccessibleProxy<'_>) -> Option<ObjectRef> {
+    match proxy.inner().destination() {
+        BusName::Unique(name) => Some(ObjectRef {
+            name: name.to_owned().into(),
+            path: proxy.inner().path().to_owned().into(),
+        }),
+        BusName::WellKnown(_) => None,
+    }
+}
+
+/// How the trees the command line prints are traversed, for the whole run.
+static TRAVERSAL: OnceLock<TreeBuilder> = OnceLock::new();
+
+/// Traverse the trees the command line prints as `builder` says.
+pub(crate) fn set(builder: TreeBuilder) {
+    let _ = TRAVERSAL.set(builder);
+}
+
+/// How to traverse the trees the command line prints.
+pub(crate) fn configured() -> &'static TreeBuilder {
+    TRAVERSAL.get_or_init(TreeBuilder::default)
+}
+
+pub(crate) fn parse_detail(value: &str) -> ArgResult<Detail> {
+    match value {
+        "shape" => Ok(Detail::Shape),
+        "roles" => Ok(Detail::Roles),
+        "names" => Ok(Detail::Names),
+        "full" => Ok(Detail::Full),
+        _ => Err(format!(
+            "Unknown tree detail: {value}, expected shape, roles, names or full"
+        )),
+    }
+}
+
+pub(crate) fn parse_role(value: &str) -> ArgResult<Role> {
+    snapshot::role_from_name(value).ok_or_else(|| format!("Unknown role: {value}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes down what it is told, one line for each call.
+    #[derive(Default)]
+    struct Log {
+        lines: Vec<String>,
+        /// Whether errors are written down and skipped, rather than ending the traversal.
+        skip_errors: bool,
+    }
+
+    impl TreeVisitor for Log {
+        fn visit_node(&mut self, node: &A11yNode, depth: usize) {
+            let name = node.name.as_deref().unwrap_or_default();
+            self.lines.push(format!("node {name} {depth}"));
+        }
+
+        fn enter_children(&mut self, depth: usize) {
+            self.lines.push(format!("enter {depth}"));
+        }
+
+        fn leave_children(&mut self, depth: usize) {
+            self.lines.push(format!("leave {depth}"));
+        }
+
+        fn on_error(&mut self, error: Box<dyn std::error::Error>) -> Result<()> {
+            if !self.skip_errors {
+                return Err(error);
+            }
+            self.lines.push(format!("error {error}"));
+            Ok(())
+        }
+    }
+
+    fn enter(name: &str, depth: usize) -> Result<NodeEvent> {
+        let node = A11yNode {
+            name: Some(name.to_string()),
+            ..A11yNode::default()
+        };
+        Ok(NodeEvent::Enter { node, depth })
+    }
+
+    fn leave(depth: usize) -> Result<NodeEvent> {
+        Ok(NodeEvent::Leave { depth })
+    }
+
+    /// A root with children `a`, which has a child `x`, and `b`.
+    fn events() -> Vec<Result<NodeEvent>> {
+        vec![
+            enter("root", 0),
+            enter("a", 1),
+            enter("x", 2),
+            leave(2),
+            leave(1),
+            enter("b", 1),
+            leave(1),
+            leave(0),
+        ]
+    }
+
+    #[tokio::test]
+    async fn visitor_is_told_where_children_begin_and_end() {
+        let mut log = Log::default();
+        drive(stream::iter(events()), &mut log).await.unwrap();
+        assert_eq!(
+            log.lines,
+            [
+                "node root 0",
+                "enter 1",
+                "node a 1",
+                "enter 2",
+                "node x 2",
+                "leave 2",
+                "node b 1",
+                "leave 1",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn errors_end_the_traversal_unless_the_visitor_skips_them() {
+        let failing = || {
+            let mut events = events();
+            events.insert(3, Err("no answer".into()));
+            events
+        };
+
+        let mut log = Log::default();
+        let error = drive(stream::iter(failing()), &mut log).await.unwrap_err();
+        assert_eq!(error.to_string(), "no answer");
+        assert_eq!(log.lines.last().unwrap(), "node x 2");
+
+        let mut log = Log {
+            skip_errors: true,
+            ..Log::default()
+        };
+        drive(stream::iter(failing()), &mut log).await.unwrap();
+        assert_eq!(log.lines[5], "error no answer");
+        assert_eq!(log.lines.last().unwrap(), "leave 1");
+    }
+}
@@ -0,0 +1,155 @@
+//! Reading and adjusting sliders, spin buttons and scrollbars through the Value interface.
+
+use crate::{
+    target::{self, Selected},
+    Result,
+};
+use argh::{ArgsInfo, FromArgs};
+use atspi::{
+    proxy::{proxy_ext::ProxyExt, value::ValueProxy},
+    AccessibilityConnection,
+};
+use tracing::warn;
+use zbus::Connection;
+
+/// Print the current, minimum and maximum value of a node
+#[derive(FromArgs, ArgsInfo)]
+#[argh(subcommand, name = "get-value")]
+pub struct GetValueArgs {
+    /// the application, by bus name or application name
+    #[argh(positional)]
+    app: String,
+
+    /// the node, by the child indices leading to it from the application's root, e.g. `0/2/1`
+    #[argh(option)]
+    path: Option<String>,
+
+    /// the first node with this role and/or name, e.g. `slider "Volume"`
+    #[argh(option)]
+    query: Option<String>,
+}
+
+/// Set the value of a node, within its minimum and maximum
+#[derive(FromArgs, ArgsInfo)]
+#[argh(subcommand, name = "set-value")]
+pub struct SetValueArgs {
+    /// the application, by bus name or application name
+    #[argh(positional)]
+    app: String,
+
+    /// the new value (put `--` before negative values)
+    #[argh(positional)]
+    value: f64,
+
+    /// the node, by the child indices leading to it from the application's root, e.g. `0/2/1`
+    #[argh(option)]
+    path: Option<String>,
+
+    /// the first node with this role and/or name, e.g. `slider "Volume"`
+    #[argh(option)]
+    query: Option<String>,
+}
+
+/// Select the node and get its Value interface.
+async fn adjustable(
+    conn: &Connection,
+    app: &str,
+    path: Option<&str>,
+    query: Option<&str>,
+) -> Result<(Selected, ValueProxy<'static>)> {
+    let node = target::select(conn, app, path, query).await?;
+    let proxies = node.proxy.proxies().await?;
+    match proxies.value().await {
+        Ok(value) => Ok((node, value)),
+        Err(_) => Err(format!(
+            "{} at {} does not implement the Value interface",
+            node.description,
+            node.location()
+        )
+        .into()),
+    }
+}
+
+pub async fn get_value(a11y: &AccessibilityConnection, args: GetValueArgs) -> Result<()> {
+    let conn = a11y.connection();
+    let (node, value) =
+        adjustable(conn, &args.app, args.path.as_deref(), args.query.as_deref()).await?;
+    banner!("Node: {} at {}", node.description, node.location());
+
+    let fmt = |v: zbus::Result<f64>| match v {
+        Ok(v) => v.to_string(),
+        Err(e) => format!("Error: {e}"),
+    };
+    let text = match value.text().await {
+        Ok(text) if text.is_empty() => "--- No value ---".to_string(),
+        Ok(text) => text,
+        Err(e) => format!("Error: {e}"),
+    };
+
+    let rows = vec![
+        vec![
+            "Current value:".to_string(),
+            fmt(value.current_value().await),
+        ],
+        vec![
+            "Minimum value:".to_string(),
+            fmt(value.minimum_value().await),
+        ],
+        vec![
+            "Maximum value:".to_string(),
+            fmt(value.maximum_value().await),
+        ],
+        vec![
+            "Minimum increment:".to_string(),
+            fmt(value.minimum_increment().await),
+        ],
+        vec!["Text:".to_string(), text],
+    ];
+    crate::print_table(&["Property", "Value"], &rows);
+
+    Ok(())
+}
+
+pub async fn set_value(a11y: &AccessibilityConnection, args: SetValueArgs) -> Result<()> {
+    let conn = a11y.connection();
+    let (node, value) =
+        adjustable(conn, &args.app, args.path.as_deref(), args.query.as_deref()).await?;
+
+    let minimum = value.minimum_value().await?;
+    let maximum = value.maximum_value().await?;
+    if args.value < minimum || args.value > maximum {
+        return Err(format!(
+            "{} is out of range for {}: the value must lie between {minimum} and {maximum}",
+            args.value, node.description
+        )
+        .into());
+    }
+
+    if crate::dry_run() {
+        println!(
+            "Would set the value of {} at {} from {} to {}",
+            node.description,
+            node.location(),
+            value.current_value().await?,
+            args.value
+        );
+        return Ok(());
+    }
+    value.set_current_value(args.value).await?;
+
+    // Toolkits may round to their increment, or ignore the change altogether.
+    let current = value.current_value().await?;
+    println!(
+        "Value of {} at {} is now: {current}",
+        node.description,
+        node.location()
+    );
+    if current != args.value {
+        warn!(
+            "the value was set to {}, but reads back as {current}",
+            args.value
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,72 @@
+//! Checking a running application against a golden snapshot, for CI.
+//!
+//! Names that change from run to run and subtrees with volatile content can be left out
+//! of the comparison, see `diff_ignore`.
+
+use crate::{diff, diff_ignore::Ignore, exit, snapshot, Result};
+use argh::{ArgsInfo, FromArgs};
+use atspi::AccessibilityConnection;
+use std::path::PathBuf;
+
+/// Compare a running application to a golden snapshot and exit with 7 if its tree deviates
+#[derive(FromArgs, ArgsInfo)]
+#[argh(subcommand, name = "verify")]
+pub struct VerifyArgs {
+    /// the application, by bus name or application name
+    #[argh(positional)]
+    app: String,
+
+    /// the golden snapshot
+    #[argh(positional)]
+    golden: PathBuf,
+
+    /// do not compare the names of nodes matching this query, e.g. `label` (repeatable)
+    #[argh(option)]
+    ignore_name: Vec<String>,
+
+    /// do not compare nodes matching this query and the nodes below them,
+    /// e.g. `panel "Notifications"` (repeatable)
+    #[argh(option)]
+    ignore_subtree: Vec<String>,
+
+    /// do not compare this property of any node, e.g. `geometry` or `attribute tooltip`
+    /// (repeatable)
+    #[argh(option)]
+    ignore_property: Vec<String>,
+
+    /// read more ignore rules from this TOML file
+    #[argh(option)]
+    ignore_file: Option<PathBuf>,
+
+    /// do not color the output, which is colored when printing to a terminal
+    #[argh(switch)]
+    no_color: bool,
+}
+
+pub async fn verify(a11y: &AccessibilityConnection, args: VerifyArgs) -> Result<()> {
+    let ignore = Ignore::from_options(
+        &args.ignore_name,
+        &args.ignore_subtree,
+        &args.ignore_property,
+        args.ignore_file.as_deref(),
+    )?;
+
+    let golden = snapshot::load(&args.golden)?;
+    let live = snapshot::capture(a11y, &args.app, Some(&golden.path), None, &golden.detail).await?;
+    let description = live.root.description();
+    let changes = ignore.diff(&golden, &live);
+
+    if changes.is_empty() {
+        println!("{description} matches {}", args.golden.display());
+        return Ok(());
+    }
+    let color = crate::color(args.no_color);
+    diff::print_changes(&changes, color);
+    Err(exit::Failure::boxed(
+        exit::ASSERTION_FAILED,
+        format!(
+            "verify failed: {description} deviates from {}",
+            args.golden.display()
+        ),
+    ))
+}
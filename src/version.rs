@@ -0,0 +1,79 @@
+//! The versions of a11y-app and of the accessibility stack it runs on, for `--version`, so
+//! a bug report has them all.
+
+use crate::Result;
+use atspi::{
+    proxy::{application::ApplicationProxy, bus::StatusProxy},
+    zbus::proxy::CacheProperties,
+    AccessibilityConnection,
+};
+
+/// The version of the atspi crate a11y-app is built with, as Cargo.lock resolved it.
+pub(crate) const ATSPI_CRATE_VERSION: &str = env!("A11Y_APP_ATSPI_VERSION");
+
+/// The version of the zbus crate a11y-app is built with, as Cargo.lock resolved it.
+const ZBUS_CRATE_VERSION: &str = env!("A11Y_APP_ZBUS_VERSION");
+
+/// The features of the atspi crate a11y-app is built with, as in Cargo.toml.
+const ATSPI_FEATURES: &str = "proxies-tokio, tokio, zbus";
+
+/// Print the versions, and what the session bus and the accessibility bus report. Neither
+/// has to be there, and accessibility is not turned on to ask.
+pub async fn version() -> Result<()> {
+    let unknown = |e: Box<dyn std::error::Error>| format!("--- Unknown: {e} ---");
+    let (enabled, screen_reader) = match status().await {
+        Ok((enabled, screen_reader)) => (enabled.to_string(), screen_reader.to_string()),
+        Err(e) => {
+            let unknown = unknown(e);
+            (unknown.clone(), unknown)
+        }
+    };
+    let registry = registry_version().await.unwrap_or_else(unknown);
+    let variable = |name: &str| {
+        std::env::var(name)
+            .ok()
+            .filter(|value| !value.is_empty())
+            .unwrap_or_else(|| "--- No value ---".to_string())
+    };
+
+    println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+    crate::print_properties(&[
+        ("atspi crate", ATSPI_CRATE_VERSION.to_string()),
+        ("atspi features", ATSPI_FEATURES.to_string()),
+        ("zbus crate", ZBUS_CRATE_VERSION.to_string()),
+        ("AT-SPI registry", registry),
+        ("Accessibility enabled", enabled),
+        ("Screen reader enabled", screen_reader),
+        ("Desktop", variable("XDG_CURRENT_DESKTOP")),
+        ("Session type", variable("XDG_SESSION_TYPE")),
+    ]);
+    Ok(())
+}
+
+/// Whether the session claims accessibility and a screen reader are enabled.
+async fn status() -> Result<(bool, bool)> {
+    let session = zbus::Connection::session().await?;
+    let status = StatusProxy::new(&session).await?;
+    Ok((
+        status.is_enabled().await?,
+        status.screen_reader_enabled().await?,
+    ))
+}
+
+/// The toolkit, version and AT-SPI version of the registry, e.g.
+/// `at-spi-registry 2.52.0 (AT-SPI 2.1)`.
+async fn registry_version() -> Result<String> {
+    let a11y = AccessibilityConnection::new().await?;
+    let registry = ApplicationProxy::builder(a11y.connection())
+        .destination(crate::REGISTRY_DEST)?
+        .path(crate::ACCESSIBLE_ROOT)?
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await?;
+    Ok(format!(
+        "{} {} (AT-SPI {})",
+        registry.toolkit_name().await?,
+        registry.version().await?,
+        registry.atspi_version().await?
+    ))
+}
@@ -0,0 +1,303 @@
+//! Waiting for a condition on an application, so scripts can synchronize with slow applications.
+
+use crate::{
+    config, script,
+    target::{self, Selected},
+    watch::{self, EventFilter},
+    ArgResult, Result,
+};
+use argh::{ArgsInfo, FromArgs};
+use atspi::{AccessibilityConnection, EventProperties, State};
+use futures::StreamExt;
+use std::time::{Duration, Instant};
+use zbus::Connection;
+
+/// Milliseconds to wait before failing, without `--timeout` or a configured timeout.
+const DEFAULT_TIMEOUT: u64 = 5000;
+
+/// Wait until a node appears (or is gone), has a state, or an event arrives, failing after a timeout
+#[derive(FromArgs, ArgsInfo)]
+#[argh(subcommand, name = "wait-for")]
+pub struct WaitForArgs {
+    /// the application, by bus name or application name
+    #[argh(positional)]
+    app: String,
+
+    /// the node, by the child indices leading to it from the application's root, e.g. `0/2/1`
+    #[argh(option)]
+    path: Option<String>,
+
+    /// the first node with this role and/or name, e.g. `dialog "Preferences"`
+    #[argh(option)]
+    query: Option<String>,
+
+    /// wait until the node has this state, e.g. `focused` or `showing`
+    #[argh(option)]
+    state: Option<String>,
+
+    /// with --state, wait until the node does not have the state
+    #[argh(switch)]
+    not: bool,
+
+    /// wait until the node is gone
+    #[argh(switch)]
+    gone: bool,
+
+    /// wait for an event of this type from the application, e.g. `window:activate`
+    #[argh(option)]
+    event: Option<String>,
+
+    /// milliseconds to wait before failing (default: 5000)
+    #[argh(option)]
+    timeout: Option<u64>,
+
+    /// how to notice changes: `events`, re-checking when the application sends a
+    /// relevant event, or `poll`, re-checking every --interval (default: events)
+    #[argh(option, from_str_fn(parse_strategy), default = "Strategy::Events")]
+    strategy: Strategy,
+
+    /// milliseconds between checks with `--strategy poll` (default: 100)
+    #[argh(option, default = "100")]
+    interval: u64,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Strategy {
+    Events,
+    Poll,
+}
+
+fn parse_strategy(value: &str) -> ArgResult<Strategy> {
+    match value {
+        "events" => Ok(Strategy::Events),
+        "poll" => Ok(Strategy::Poll),
+        _ => Err(format!(
+            "Unknown strategy: {value}, expected events or poll"
+        )),
+    }
+}
+
+/// What to wait for on a node.
+struct Condition {
+    path: Option<String>,
+    query: Option<String>,
+    state: Option<(State, String)>,
+    /// Whether the node must have the state, or be present.
+    expected: bool,
+}
+
+impl Condition {
+    fn describe(&self) -> String {
+        let node = match (&self.path, &self.query) {
+            (Some(path), _) => format!("the node at path {path}"),
+            (None, Some(query)) => query.clone(),
+            (None, None) => "the root".to_string(),
+        };
+        match (&self.state, self.expected) {
+            (Some((_, name)), true) => format!("{node} to have the {name} state"),
+            (Some((_, name)), false) => format!("{node} to lose the {name} state"),
+            (None, true) => format!("{node} to appear"),
+            (None, false) => format!("{node} to be gone"),
+        }
+    }
+
+    /// The node, if the condition holds for it; `Some(None)` if it holds because the node is gone.
+    async fn check(&self, conn: &Connection, root: &Selected) -> Result<Option<Option<Selected>>> {
+        let node = self.find(conn, root).await;
+        let Some((state, _)) = &self.state else {
+            return Ok(match (node, self.expected) {
+                (Some(node), true) => Some(Some(node)),
+                (None, false) => Some(None),
+                _ => None,
+            });
+        };
+
+        // A node that is not (yet) there has no states.
+        let Some(node) = node else {
+            return Ok(None);
+        };
+        let has_state = node
+            .proxy
+            .get_state()
+            .await
+            .map(|states| states.contains(*state))
+            .unwrap_or(false);
+        Ok((has_state == self.expected).then_some(Some(node)))
+    }
+
+    async fn find(&self, conn: &Connection, root: &Selected) -> Option<Selected> {
+        match (&self.path, &self.query) {
+            (Some(path), _) => {
+                let proxy = target::follow_path(conn, root.proxy.clone(), path)
+                    .await
+                    .ok()?;
+                Some(Selected {
+                    description: watch::describe_node(&proxy).await,
+                    path: path.clone(),
+                    proxy,
+                })
+            }
+            (None, Some(query)) => target::search(conn, root, query, 1).await.ok()?.pop(),
+            (None, None) => Some(root.clone()),
+        }
+    }
+
+    /// The events after which the condition may have changed.
+    fn events(&self) -> Vec<String> {
+        let mut events = vec!["object:children-changed".to_string()];
+        if self.query.is_some() {
+            events.push("object:property-change:accessible-name".to_string());
+        }
+        if let Some((_, name)) = &self.state {
+            events.push(format!("object:state-changed:{name}"));
+        }
+        events
+    }
+}
+
+pub async fn wait_for(a11y: &AccessibilityConnection, args: WaitForArgs) -> Result<()> {
+    let conn = a11y.connection();
+    let timeout = args
+        .timeout
+        .or(config::get().wait_timeout)
+        .unwrap_or(DEFAULT_TIMEOUT);
+    let timeout = Duration::from_millis(timeout);
+
+    if let Some(pattern) = &args.event {
+        if args.path.is_some() || args.query.is_some() || args.state.is_some() || args.gone {
+            return Err("Wait for either an --event or a node, not both".into());
+        }
+        return wait_for_event(a11y, &args.app, pattern, timeout).await;
+    }
+
+    if args.gone && args.state.is_some() {
+        return Err("Wait for either a --state or the node to be --gone, not both".into());
+    }
+    let state = match &args.state {
+        Some(name) => match State::from(name.as_str()) {
+            State::Invalid => return Err(format!("Unknown state: {name}").into()),
+            state => Some((state, name.clone())),
+        },
+        None => None,
+    };
+    let condition = Condition {
+        path: args.path,
+        query: args.query,
+        expected: if state.is_some() {
+            !args.not
+        } else {
+            !args.gone
+        },
+        state,
+    };
+    if condition.path.is_some() && condition.query.is_some() {
+        return Err("Select a node by --path or by --query, not both".into());
+    }
+
+    let root = target::select(conn, &args.app, None, None).await?;
+    let sender = root.proxy.inner().destination().to_string();
+
+    // Subscribe before the first check, so a change right after it is not missed.
+    let mut events = None;
+    if args.strategy == Strategy::Events {
+        let filters = condition
+            .events()
+            .iter()
+            .map(|pattern| EventFilter::parse(pattern))
+            .collect::<Result<Vec<_>>>()?;
+        watch::subscribe(a11y, &filters).await?;
+        events = Some((script::events(a11y), filters));
+    }
+
+    let start = Instant::now();
+    let interval = Duration::from_millis(args.interval.max(1));
+    loop {
+        if let Some(node) = condition.check(conn, &root).await? {
+            let elapsed = start.elapsed().as_millis();
+            match node {
+                Some(node) => println!(
+                    "{} at {} after {elapsed} ms",
+                    node.description,
+                    node.location()
+                ),
+                None => println!(
+                    "Done waiting for {} after {elapsed} ms",
+                    condition.describe()
+                ),
+            }
+            return Ok(());
+        }
+
+        let Some(remaining) = timeout.checked_sub(start.elapsed()) else {
+            return Err(format!(
+                "Timed out after {} ms waiting for {}",
+                timeout.as_millis(),
+                condition.describe()
+            )
+            .into());
+        };
+
+        match &mut events {
+            None => tokio::time::sleep(interval.min(remaining)).await,
+            Some((events, filters)) => {
+                let relevant = async {
+                    while let Some(event) = events.next().await {
+                        let event_type = watch::event_type(&event);
+                        if event.sender().as_str() == sender
+                            && filters.iter().any(|filter| filter.matches(&event_type))
+                        {
+                            return;
+                        }
+                    }
+                };
+                // On timeout, the next round checks one last time and fails.
+                let _ = tokio::time::timeout(remaining, relevant).await;
+                // A burst of events, e.g. a dialog being populated, needs one check only.
+                while let Ok(Some(_)) = events.try_next() {}
+            }
+        }
+    }
+}
+
+/// Wait for an event matching `pattern` from the application.
+async fn wait_for_event(
+    a11y: &AccessibilityConnection,
+    app: &str,
+    pattern: &str,
+    timeout: Duration,
+) -> Result<()> {
+    let conn = a11y.connection();
+    let root = target::select(conn, app, None, None).await?;
+    let sender = root.proxy.inner().destination().to_string();
+
+    let filter = EventFilter::parse(pattern)?;
+    watch::subscribe(a11y, std::slice::from_ref(&filter)).await?;
+    let mut events = script::events(a11y);
+
+    let start = Instant::now();
+    let wait = async {
+        while let Some(event) = events.next().await {
+            let event_type = watch::event_type(&event);
+            if event.sender().as_str() == sender && filter.matches(&event_type) {
+                let target = watch::describe_target(conn, event.object_ref()).await;
+                return Some((event_type, target));
+            }
+        }
+        None
+    };
+    match tokio::time::timeout(timeout, wait).await {
+        Ok(Some((event_type, target))) => {
+            println!(
+                "{event_type} on {target} after {} ms",
+                start.elapsed().as_millis()
+            );
+            Ok(())
+        }
+        Ok(None) => Err("The connection to the accessibility bus was closed".into()),
+        Err(_) => Err(format!(
+            "Timed out after {} ms waiting for {pattern} from {app}",
+            timeout.as_millis()
+        )
+        .into()),
+    }
+}
@@ -0,0 +1,1490 @@
+//! Live monitoring of AT-SPI events, in the spirit of the `at-spi2-core` event monitor.
+
+use crate::Result;
+use argh::{ArgsInfo, FromArgs};
+use atspi::{
+    events::{DBusMatchRule, ObjectEvents},
+    proxy::{
+        accessible::AccessibleProxy, application::ApplicationProxy, registry::RegistryProxy,
+        text::TextProxy,
+    },
+    zbus::proxy::CacheProperties,
+    AccessibilityConnection, DocumentEvents, Event, EventProperties, EventTypeProperties,
+    FocusEvents, KeyboardEvents, MouseEvents, ObjectRef, Operation, Role, State, TerminalEvents,
+    WindowEvents,
+};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, IsTerminal, Write},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tracing::warn;
+use zbus::{
+    fdo::DBusProxy,
+    names::{BusName, OwnedUniqueName},
+    Connection, MatchRule,
+};
+
+/// Path used by AT-SPI to refer to 'no object', e.g. as the parent of the desktop.
+pub(crate) const NULL_PATH: &str = "/org/a11y/atspi/null";
+
+/// Event classes subscribed to by default: the registry event string,
+/// paired with the D-Bus match rule that lets the signals reach us.
+const EVENT_CLASSES: &[(&str, &str)] = &[
+    ("object:", ObjectEvents::MATCH_RULE_STRING),
+    ("window:", WindowEvents::MATCH_RULE_STRING),
+    ("focus:", FocusEvents::MATCH_RULE_STRING),
+    ("document:", DocumentEvents::MATCH_RULE_STRING),
+    ("terminal:", TerminalEvents::MATCH_RULE_STRING),
+    ("keyboard:", KeyboardEvents::MATCH_RULE_STRING),
+    ("mouse:", MouseEvents::MATCH_RULE_STRING),
+];
+
+/// The events that signal focus moving to another object.
+const FOCUS_EVENTS: &[&str] = &["object:state-changed:focused", "focus:"];
+
+/// Roles of the objects that give an event its context in `--resolve` output.
+const WINDOW_ROLES: &[Role] = &[
+    Role::Alert,
+    Role::ColorChooser,
+    Role::Dialog,
+    Role::FileChooser,
+    Role::FontChooser,
+    Role::Frame,
+    Role::Window,
+];
+
+/// The events that signal the caret moving or text changing.
+const TEXT_EVENTS: &[&str] = &["object:text-caret-moved", "object:text-changed"];
+
+/// Number of characters shown on either side of the caret or change by `--text`.
+const TEXT_CONTEXT: i32 = 20;
+
+/// A round trip to the bus taking longer than this means we were not reading
+/// from it, because our queue of incoming events was full.
+const STALL_THRESHOLD: Duration = Duration::from_millis(250);
+
+/// Upper bound on the ancestors walked for a breadcrumb, in case an application
+/// reports a cyclic hierarchy.
+pub(crate) const MAX_ANCESTRY_DEPTH: usize = 64;
+
+/// Print accessibility events as they arrive
+#[derive(FromArgs, ArgsInfo)]
+#[argh(subcommand, name = "watch")]
+pub struct WatchArgs {
+    /// only show events of this type, e.g. `object:state-changed:focused` or `window:*`
+    /// (may be repeated, default: all events)
+    #[argh(option)]
+    event: Vec<String>,
+
+    /// only show events from this application, by bus name or application name
+    /// (may be repeated, default: all applications)
+    #[argh(option)]
+    app: Vec<String>,
+
+    /// only follow focus changes, printing the focused object and its ancestors
+    #[argh(switch)]
+    follow_focus: bool,
+
+    /// only follow changes of this state, e.g. `checked` or `expanded`, printing
+    /// the path to each node that changed (may be repeated)
+    #[argh(option)]
+    state: Vec<String>,
+
+    /// only follow caret movement and text changes, printing the text around them
+    #[argh(switch)]
+    text: bool,
+
+    /// show the object an event is about in the context of its window,
+    /// e.g. `push button "Save" in dialog "Preferences"`
+    #[argh(switch)]
+    resolve: bool,
+
+    /// append a timestamped record of every shown event to this file, as JSON lines
+    #[argh(option)]
+    log: Option<PathBuf>,
+
+    /// run this shell command for every shown event, with the event's fields in
+    /// the environment: A11Y_EVENT_TYPE, A11Y_EVENT_DETAIL, A11Y_EVENT_SENDER,
+    /// A11Y_EVENT_APPLICATION, A11Y_EVENT_PATH, A11Y_EVENT_TARGET, A11Y_EVENT_ANCESTRY and A11Y_EVENT_TIME
+    #[argh(option)]
+    exec: Option<String>,
+
+    /// record every shown event to this file, for later use with `replay`
+    #[argh(option)]
+    record: Option<PathBuf>,
+
+    /// instead of printing events, show a periodically refreshed table of
+    /// event counts per event type and per application
+    #[argh(switch)]
+    stats: bool,
+
+    /// instead of printing events, print a line whenever an application
+    /// joins or leaves the accessibility bus
+    #[argh(switch)]
+    registrations: bool,
+
+    /// also show key presses and releases, as reported to the registry's device event controller
+    #[argh(switch)]
+    keys: bool,
+
+    /// instead of printing events, print the tree of every window that becomes active
+    #[argh(switch)]
+    windows: bool,
+
+    /// instead of printing events, keep the last N shown events and print them
+    /// when Enter is pressed or a `--dump-on` event arrives
+    #[argh(option)]
+    buffer: Option<usize>,
+
+    /// dump the `--buffer` when an event of this type arrives, e.g.
+    /// `object:state-changed:defunct` (may be repeated)
+    #[argh(option)]
+    dump_on: Vec<String>,
+
+    /// append dumps of the `--buffer` to this file, as JSON lines, instead of printing them
+    #[argh(option)]
+    dump_file: Option<PathBuf>,
+
+    /// show at most this many events per second from any one application,
+    /// summarizing the number of suppressed events instead
+    #[argh(option)]
+    rate_limit: Option<u64>,
+
+    /// the number of incoming events that may wait to be handled before reading
+    /// from the bus stalls (default: 64)
+    #[argh(option)]
+    queue: Option<usize>,
+
+    /// the sampling window of `--stats` in seconds (default: 2)
+    #[argh(option, default = "2")]
+    interval: u64,
+}
+
+#[derive(Default)]
+struct Counter {
+    window: u64,
+    total: u64,
+}
+
+/// Event counts per event type and per application, for `--stats`.
+struct EventStats {
+    start: Instant,
+    window_start: Instant,
+    by_type: HashMap<String, Counter>,
+    by_app: HashMap<String, Counter>,
+}
+
+impl EventStats {
+    fn new() -> EventStats {
+        EventStats {
+            start: Instant::now(),
+            window_start: Instant::now(),
+            by_type: HashMap::new(),
+            by_app: HashMap::new(),
+        }
+    }
+
+    fn count(&mut self, sender: &str, event_type: &str) {
+        for counter in [
+            self.by_type.entry(event_type.to_string()).or_default(),
+            self.by_app.entry(sender.to_string()).or_default(),
+        ] {
+            counter.window += 1;
+            counter.total += 1;
+        }
+    }
+
+    /// Print the tables for the window that just ended and start a new window.
+    fn print_and_reset(&mut self) {
+        let window = self.window_start.elapsed().as_secs_f64();
+
+        // Redraw in place when a person is watching, append when piped.
+        if std::io::stdout().is_terminal() && !crate::machine() {
+            print!("\x1b[2J\x1b[H");
+        }
+        println!(
+            "Event statistics, {:.0}s since start, rates over the last {window:.1}s:",
+            self.start.elapsed().as_secs_f64()
+        );
+
+        for (header, counters) in [("Event type", &self.by_type), ("Application", &self.by_app)] {
+            let mut counts: Vec<(&String, &Counter)> = counters.iter().collect();
+            counts.sort_by(|a, b| b.1.total.cmp(&a.1.total).then_with(|| a.0.cmp(b.0)));
+
+            let rows: Vec<Vec<String>> = counts
+                .into_iter()
+                .map(|(key, counter)| {
+                    vec![
+                        key.clone(),
+                        format!("{:.1}", counter.window as f64 / window),
+                        counter.total.to_string(),
+                    ]
+                })
+                .collect();
+
+            crate::print_table(&[header, "Events/s", "Total"], &rows);
+        }
+
+        for counter in self.by_type.values_mut().chain(self.by_app.values_mut()) {
+            counter.window = 0;
+        }
+        self.window_start = Instant::now();
+    }
+}
+
+/// Per-application limit on the number of events shown each second, for `--rate-limit`.
+struct RateLimiter {
+    limit: u64,
+    shown: HashMap<String, u64>,
+    suppressed: HashMap<String, u64>,
+}
+
+impl RateLimiter {
+    fn new(limit: u64) -> RateLimiter {
+        RateLimiter {
+            limit,
+            shown: HashMap::new(),
+            suppressed: HashMap::new(),
+        }
+    }
+
+    /// Whether an event from `sender` may be shown in the current second.
+    fn allow(&mut self, sender: &str) -> bool {
+        let shown = self.shown.entry(sender.to_string()).or_default();
+        if *shown < self.limit {
+            *shown += 1;
+            return true;
+        }
+        *self.suppressed.entry(sender.to_string()).or_default() += 1;
+        false
+    }
+
+    /// Print what was suppressed in the second that just ended and start a new one.
+    fn summarize_and_reset(&mut self) {
+        let mut suppressed: Vec<(String, u64)> = self.suppressed.drain().collect();
+        suppressed.sort();
+        for (sender, count) in suppressed {
+            println!(
+                "{sender:<8} suppressed {count} events over the limit of {}/s",
+                self.limit
+            );
+        }
+        self.shown.clear();
+    }
+}
+
+/// Counters of the events we may have missed, for the warnings about overruns.
+#[derive(Default)]
+struct Overruns {
+    received: u64,
+    unparsable: u64,
+    stalls: u64,
+    longest_stall: Duration,
+}
+
+impl Overruns {
+    fn stalled(&mut self, duration: Duration) {
+        self.stalls += 1;
+        self.longest_stall = self.longest_stall.max(duration);
+        warn!(
+            "events arrive faster than they are handled, reading from the bus stalled for {} ms; \
+             the bus drops signals it cannot queue ({})",
+            duration.as_millis(),
+            self.counters()
+        );
+    }
+
+    fn counters(&self) -> String {
+        format!(
+            "{} events received, {} unparsable, {} stalls, longest {} ms",
+            self.received,
+            self.unparsable,
+            self.stalls,
+            self.longest_stall.as_millis()
+        )
+    }
+}
+
+/// Ping the bus every second in the background, warning about round trips that take
+/// so long that the connection must have stopped reading.
+///
+/// zbus stops reading from the socket while the queue of incoming messages is full,
+/// so a stall delays the reply to the ping just as it delays events. The warnings are
+/// printed from here, because the watch itself may be waiting on a reply stuck behind the stall.
+fn monitor_stalls(conn: Connection, overruns: Arc<Mutex<Overruns>>) {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            let sent = Instant::now();
+            let ping = conn.call_method(
+                Some("org.freedesktop.DBus"),
+                "/org/freedesktop/DBus",
+                Some("org.freedesktop.DBus.Peer"),
+                "Ping",
+                &(),
+            );
+            futures::pin_mut!(ping);
+
+            let mut wait = STALL_THRESHOLD;
+            let reply = loop {
+                match tokio::time::timeout(wait, &mut ping).await {
+                    Ok(reply) => break reply,
+                    Err(_) => {
+                        warn!(
+                            "reading from the bus has stalled for {} ms so far, \
+                             a larger --queue may help",
+                            sent.elapsed().as_millis()
+                        );
+                        wait = Duration::from_secs(1);
+                    }
+                }
+            };
+            let round_trip = sent.elapsed();
+
+            if reply.is_err() {
+                break;
+            }
+            if round_trip > STALL_THRESHOLD {
+                if let Ok(mut overruns) = overruns.lock() {
+                    overruns.stalled(round_trip);
+                }
+            }
+        }
+    });
+}
+
+/// Replay events recorded with `watch --record`
+#[derive(FromArgs, ArgsInfo)]
+#[argh(subcommand, name = "replay")]
+pub struct ReplayArgs {
+    /// the recording to replay
+    #[argh(positional)]
+    file: PathBuf,
+
+    /// playback speed relative to the recorded timing, 0 replays without delay (default: 1)
+    #[argh(option, default = "1.0")]
+    speed: f64,
+
+    /// only show events of this type, e.g. `object:state-changed:focused` or `window:*`
+    /// (may be repeated, default: all events)
+    #[argh(option)]
+    event: Vec<String>,
+}
+
+/// An event as stored in a recording.
+///
+/// Besides the event itself, we keep what we learned about its target at the time,
+/// so a recording can be examined without the application running.
+#[derive(Serialize, Deserialize)]
+struct RecordedEvent {
+    wall_clock: f64,
+    monotonic: f64,
+    target: String,
+    ancestry: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    application: Option<String>,
+    event: Event,
+}
+
+/// A single shown event, as printed and as written to the log.
+#[derive(Clone, Serialize)]
+struct EventRecord {
+    /// Seconds since the Unix epoch.
+    wall_clock: f64,
+    /// Seconds since the start of the watch.
+    monotonic: f64,
+    sender: String,
+    /// The name of the sending application, if it is registered.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    application: Option<String>,
+    event_type: String,
+    detail: String,
+    target: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ancestry: Option<String>,
+}
+
+impl EventRecord {
+    fn new(event: &Event, wall_clock: f64, monotonic: f64, target: String) -> EventRecord {
+        EventRecord {
+            wall_clock,
+            monotonic,
+            sender: event.sender().to_string(),
+            application: None,
+            event_type: event_type(event),
+            detail: event_detail(event),
+            target,
+            ancestry: None,
+        }
+    }
+
+    fn print(&self) {
+        let sender = self.application.as_deref().unwrap_or(&self.sender);
+        match &self.ancestry {
+            Some(ancestry) => {
+                println!("Focus: [{}] ({sender})", self.target);
+                println!("    {ancestry}");
+            }
+            None => println!(
+                "{sender:<16} {:<40} {:<24} [{}]",
+                self.event_type, self.detail, self.target
+            ),
+        }
+    }
+}
+
+/// An event type pattern in the registry notation: `class:member:kind`.
+///
+/// Missing trailing segments, empty segments and `*` match anything.
+pub(crate) struct EventFilter {
+    segments: Vec<String>,
+}
+
+impl EventFilter {
+    pub(crate) fn parse(pattern: &str) -> Result<EventFilter> {
+        let segments: Vec<String> = pattern.split(':').map(str::to_string).collect();
+        let class = format!("{}:", segments[0]);
+
+        if !EVENT_CLASSES.iter().any(|(c, _)| *c == class) {
+            let known: Vec<&str> = EVENT_CLASSES.iter().map(|(c, _)| *c).collect();
+            return Err(format!(
+                "Unknown event class in {pattern:?}, expected one of: {}",
+                known.join(" ")
+            )
+            .into());
+        }
+
+        Ok(EventFilter { segments })
+    }
+
+    fn class(&self) -> String {
+        format!("{}:", self.segments[0])
+    }
+
+    /// The event string to register with the registry.
+    ///
+    /// The registry does not know about wildcards, so we register the part of the
+    /// pattern up to the first wildcard and let `matches` do the rest.
+    pub(crate) fn registry_event(&self) -> String {
+        let concrete: Vec<&str> = self
+            .segments
+            .iter()
+            .map(String::as_str)
+            .take_while(|segment| !segment.is_empty() && *segment != "*")
+            .collect();
+
+        match concrete.len() {
+            0 | 1 => self.class(),
+            _ => concrete.join(":"),
+        }
+    }
+
+    pub(crate) fn matches(&self, event_type: &str) -> bool {
+        let mut event_segments = event_type.split(':');
+        self.segments.iter().all(|segment| {
+            let event_segment = event_segments.next().unwrap_or_default();
+            segment.is_empty() || segment == "*" || segment == event_segment
+        })
+    }
+}
+
+/// Subscribe to the events matching any of the filters, or to all events if there are none.
+/// Takes the registry, which an `AccessibilityConnection` derefs to, so scripts can keep a
+/// clone of it.
+pub(crate) async fn subscribe(registry: &RegistryProxy<'_>, filters: &[EventFilter]) -> Result<()> {
+    let dbus = DBusProxy::new(registry.inner().connection()).await?;
+
+    // `AccessibilityConnection::register_event` wants a type per event class.
+    // Registering by string on the registry keeps the set of classes a plain table.
+    for (class, match_rule) in EVENT_CLASSES {
+        let registry_events: Vec<String> = if filters.is_empty() {
+            vec![class.to_string()]
+        } else {
+            filters
+                .iter()
+                .filter(|filter| filter.class() == *class)
+                .map(EventFilter::registry_event)
+                .collect()
+        };
+
+        if registry_events.is_empty() {
+            continue;
+        }
+
+        for registry_event in &registry_events {
+            registry.register_event(registry_event).await?;
+        }
+        dbus.add_match_rule(MatchRule::try_from(*match_rule)?)
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// The unique name of the connection owning `bus_name`, as found in the events it sends.
+pub(crate) async fn unique_name(
+    dbus: &DBusProxy<'_>,
+    bus_name: BusName<'_>,
+) -> Result<OwnedUniqueName> {
+    let unique_name = match bus_name {
+        BusName::Unique(unique_name) => unique_name.into(),
+        well_known => dbus.get_name_owner(well_known).await?,
+    };
+    Ok(unique_name)
+}
+
+/// Subscribe to the AT-SPI event classes and print every event until interrupted.
+pub async fn watch(a11y: &AccessibilityConnection, args: WatchArgs) -> Result<()> {
+    if args.registrations {
+        return watch_registrations(a11y).await;
+    }
+
+    let conn = a11y.connection();
+    let dbus = DBusProxy::new(conn).await?;
+
+    let patterns = if args.follow_focus {
+        FOCUS_EVENTS
+            .iter()
+            .map(|pattern| pattern.to_string())
+            .collect()
+    } else if args.text {
+        TEXT_EVENTS
+            .iter()
+            .map(|pattern| pattern.to_string())
+            .collect()
+    } else if !args.state.is_empty() {
+        let mut patterns = Vec::new();
+        for name in &args.state {
+            if State::from(name.as_str()) == State::Invalid {
+                return Err(format!("Unknown state: {name}").into());
+            }
+            patterns.push(format!("object:state-changed:{name}"));
+        }
+        patterns
+    } else {
+        args.event
+    };
+
+    let mut filters = patterns
+        .iter()
+        .map(|pattern| EventFilter::parse(pattern))
+        .collect::<Result<Vec<_>>>()?;
+    // Key records pass the filters like events, and are all shown unless the patterns pick
+    // some of them, e.g. `keyboard:press`.
+    if args.keys
+        && !filters.is_empty()
+        && !patterns
+            .iter()
+            .any(|pattern| pattern.starts_with("keyboard"))
+    {
+        filters.push(EventFilter::parse("keyboard")?);
+    }
+
+    if args.buffer.is_none() && (!args.dump_on.is_empty() || args.dump_file.is_some()) {
+        return Err("--dump-on and --dump-file need --buffer".into());
+    }
+    let triggers = args
+        .dump_on
+        .iter()
+        .map(|pattern| EventFilter::parse(pattern))
+        .collect::<Result<Vec<_>>>()?;
+
+    // Events carry the unique name of their sender, so well-known names are resolved up front.
+    let mut senders: Vec<OwnedUniqueName> = Vec::new();
+    for app in &args.app {
+        for (name, bus_name) in crate::parse_bus_name(app.clone(), conn).await? {
+            let unique_name = unique_name(&dbus, bus_name).await?;
+            banner!("Watching application: {name} ({unique_name})");
+            senders.push(unique_name);
+        }
+    }
+
+    if args.windows {
+        return watch_windows(a11y, &senders).await;
+    }
+
+    // Triggers must reach us even when they are not among the shown events.
+    // So are the registrations that keep the application names current.
+    let mut subscription = patterns.clone();
+    if !filters.is_empty() {
+        subscription.extend(args.dump_on.iter().cloned());
+        subscription.push("object:children-changed".to_string());
+    }
+    let subscription = subscription
+        .iter()
+        .map(|pattern| EventFilter::parse(pattern))
+        .collect::<Result<Vec<_>>>()?;
+    subscribe(a11y, &subscription).await?;
+
+    let (_, mut keys) = futures::channel::mpsc::unbounded();
+    if args.keys {
+        keys = crate::keys::listen(conn).await?;
+    }
+
+    let mut log = match &args.log {
+        Some(path) => Some(OpenOptions::new().create(true).append(true).open(path)?),
+        None => None,
+    };
+
+    let mut recording = match &args.record {
+        Some(path) => Some(File::create(path)?),
+        None => None,
+    };
+
+    let mut dump_file = match &args.dump_file {
+        Some(path) => Some(OpenOptions::new().create(true).append(true).open(path)?),
+        None => None,
+    };
+
+    let buffer_size = args.buffer.unwrap_or_default().max(1);
+    let mut buffer = args.buffer.map(|_| VecDeque::with_capacity(buffer_size));
+    let (dump_requests, mut dumps) = futures::channel::mpsc::unbounded();
+    if let Some(n) = args.buffer {
+        println!("Keeping the last {n} events, press Enter to dump them or Ctrl-C to stop...");
+        // Reading stdin blocks, so it gets a thread of its own.
+        std::thread::spawn(move || {
+            for _ in std::io::stdin().lock().lines() {
+                if dump_requests.unbounded_send(()).is_err() {
+                    break;
+                }
+            }
+        });
+    } else {
+        banner!("Watching accessibility events, press Ctrl-C to stop...");
+    }
+    let mut stdin_open = args.buffer.is_some();
+    let start = Instant::now();
+
+    let mut stats = args.stats.then(EventStats::new);
+    let mut cache = NodeCache::default();
+    let mut names = AppNames::new(conn).await?;
+    let period = Duration::from_secs(args.interval.max(1));
+    let mut ticker = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+
+    let mut limiter = args.rate_limit.map(RateLimiter::new);
+    let second = Duration::from_secs(1);
+    let mut limiter_ticker = tokio::time::interval_at(tokio::time::Instant::now() + second, second);
+
+    if let Some(queue) = args.queue {
+        conn.clone().set_max_queued(queue);
+    }
+    let overruns = Arc::new(Mutex::new(Overruns::default()));
+    monitor_stalls(conn.clone(), overruns.clone());
+
+    let events = a11y.event_stream();
+    futures::pin_mut!(events);
+
+    loop {
+        let event = tokio::select! {
+            event = events.next() => event,
+            _ = crate::interrupt::cancelled() => return Ok(()),
+            _ = ticker.tick(), if stats.is_some() => {
+                if let Some(stats) = &mut stats {
+                    stats.print_and_reset();
+                }
+                continue;
+            }
+            _ = limiter_ticker.tick(), if limiter.is_some() => {
+                if let Some(limiter) = &mut limiter {
+                    limiter.summarize_and_reset();
+                }
+                continue;
+            }
+            Some(key) = keys.next(), if args.keys => {
+                let is_trigger = triggers.iter().any(|f| f.matches(key.event_type()));
+                if !is_trigger
+                    && !filters.is_empty()
+                    && !filters.iter().any(|f| f.matches(key.event_type()))
+                {
+                    continue;
+                }
+                if let Some(stats) = &mut stats {
+                    stats.count(&names.label(&key.sender), key.event_type());
+                    continue;
+                }
+                if let Some(limiter) = &mut limiter {
+                    if !is_trigger && !limiter.allow(&key.sender) {
+                        continue;
+                    }
+                }
+
+                let record = EventRecord {
+                    wall_clock: wall_clock(),
+                    monotonic: start.elapsed().as_secs_f64(),
+                    application: names.get(&key.sender).cloned(),
+                    sender: key.sender.clone(),
+                    event_type: key.event_type().to_string(),
+                    detail: key.detail(),
+                    target: "keyboard".to_string(),
+                    ancestry: None,
+                };
+                show(&record, &mut buffer, buffer_size, is_trigger, &mut dump_file)?;
+
+                if let Some(command) = &args.exec {
+                    run_command(command, &record, "");
+                }
+                if let Some(log) = &mut log {
+                    write_json_line(log, &record)?;
+                }
+                continue;
+            }
+            request = dumps.next(), if stdin_open => {
+                match (request, &mut buffer) {
+                    (Some(()), Some(buffer)) => dump_buffer(buffer, &mut dump_file)?,
+                    _ => stdin_open = false,
+                }
+                continue;
+            }
+        };
+
+        let event = {
+            let mut counts = overruns.lock().map_err(|e| e.to_string())?;
+            let Some(event) = event else {
+                return Err(format!(
+                    "The connection to the accessibility bus was closed ({})",
+                    counts.counters()
+                )
+                .into());
+            };
+            counts.received += 1;
+
+            match event {
+                Ok(event) => event,
+                Err(e) => {
+                    counts.unparsable += 1;
+                    warn!("could not parse event: {e}");
+                    continue;
+                }
+            }
+        };
+
+        cache.invalidate(&event);
+        names.update(conn, &event).await;
+
+        if !senders.is_empty() && !senders.iter().any(|sender| *sender == event.sender()) {
+            continue;
+        }
+
+        let event_type = event_type(&event);
+        let is_trigger = triggers.iter().any(|f| f.matches(&event_type));
+        if !is_trigger && !filters.is_empty() && !filters.iter().any(|f| f.matches(&event_type)) {
+            continue;
+        }
+
+        // Statistics only need the counts, not the (costly) description of the target.
+        if let Some(stats) = &mut stats {
+            stats.count(&names.label(event.sender().as_str()), &event_type);
+            continue;
+        }
+
+        // Losing focus is implied by the next object gaining it.
+        if args.follow_focus {
+            if let Event::Object(ObjectEvents::StateChanged(e)) = &event {
+                if !e.enabled {
+                    continue;
+                }
+            }
+        }
+
+        if let Some(limiter) = &mut limiter {
+            if !is_trigger && !limiter.allow(event.sender().as_str()) {
+                continue;
+            }
+        }
+
+        let mut ancestry = None;
+        let target = if args.follow_focus || args.resolve || !args.state.is_empty() {
+            let nodes = cache.ancestry(conn, event.object_ref()).await;
+            if args.follow_focus {
+                ancestry = Some(describe_ancestry(&nodes));
+            }
+            // For state changes, knowing exactly which node changed is the point.
+            if !args.state.is_empty() {
+                describe_ancestry(&nodes)
+            } else if args.resolve {
+                describe_in_context(&nodes)
+            } else {
+                nodes[0].description.clone()
+            }
+        } else {
+            describe_target(conn, event.object_ref()).await
+        };
+
+        let mut record =
+            EventRecord::new(&event, wall_clock(), start.elapsed().as_secs_f64(), target);
+        record.ancestry = ancestry;
+        record.application = names.get(event.sender().as_str()).cloned();
+
+        if args.text {
+            if let Some(context) = text_context(conn, &event).await {
+                record.detail = format!("{} {context}", record.detail);
+            }
+        }
+
+        show(
+            &record,
+            &mut buffer,
+            buffer_size,
+            is_trigger,
+            &mut dump_file,
+        )?;
+
+        if let Some(command) = &args.exec {
+            run_command(command, &record, event.path().as_str());
+        }
+
+        if let Some(log) = &mut log {
+            write_json_line(log, &record)?;
+        }
+
+        if let Some(recording) = &mut recording {
+            let recorded = RecordedEvent {
+                wall_clock: record.wall_clock,
+                monotonic: record.monotonic,
+                target: record.target,
+                ancestry: record.ancestry,
+                application: record.application,
+                event,
+            };
+            write_json_line(recording, &recorded)?;
+        }
+    }
+}
+
+/// Seconds since the Unix epoch.
+fn wall_clock() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64()
+}
+
+/// Print the record, or keep it in the `--buffer` and dump that when the record is a trigger.
+fn show(
+    record: &EventRecord,
+    buffer: &mut Option<VecDeque<EventRecord>>,
+    buffer_size: usize,
+    is_trigger: bool,
+    dump_file: &mut Option<File>,
+) -> Result<()> {
+    match buffer {
+        Some(buffer) => {
+            if buffer.len() == buffer_size {
+                buffer.pop_front();
+            }
+            buffer.push_back(record.clone());
+            if is_trigger {
+                dump_buffer(buffer, dump_file)?;
+            }
+        }
+        None => record.print(),
+    }
+    Ok(())
+}
+
+/// Print the buffered events, or append them to the dump file, and empty the buffer.
+fn dump_buffer(buffer: &mut VecDeque<EventRecord>, dump_file: &mut Option<File>) -> Result<()> {
+    match dump_file {
+        Some(file) => {
+            for record in buffer.drain(..) {
+                write_json_line(file, &record)?;
+            }
+        }
+        None => {
+            println!("--- Last {} events ---", buffer.len());
+            for record in buffer.drain(..) {
+                record.print();
+            }
+            println!("--- End of dump ---");
+        }
+    }
+    Ok(())
+}
+
+/// Print a line for every application that registers with, or deregisters from, the registry.
+async fn watch_registrations(a11y: &AccessibilityConnection) -> Result<()> {
+    let conn = a11y.connection();
+    let dbus = DBusProxy::new(conn).await?;
+    let registry = unique_name(&dbus, BusName::try_from(crate::REGISTRY_DEST)?).await?;
+
+    subscribe(a11y, &[EventFilter::parse("object:children-changed")?]).await?;
+    let events = a11y
+        .event_stream()
+        .take_until(crate::interrupt::cancelled());
+    futures::pin_mut!(events);
+
+    // Once an application has left, it can no longer tell us who it was.
+    let mut known: HashMap<ObjectRef, String> = HashMap::new();
+    let registry_accessible = crate::get_registry_accessible(conn).await?;
+    for app in registry_accessible.get_children().await? {
+        let description = describe_application(conn, &app).await;
+        known.insert(app, description);
+    }
+
+    banner!(
+        "Watching {} registered applications join and leave, press Ctrl-C to stop...",
+        known.len()
+    );
+
+    while let Some(event) = events.next().await {
+        let event = match event {
+            Ok(event) => event,
+            Err(e) => {
+                warn!("could not parse event: {e}");
+                continue;
+            }
+        };
+
+        let Event::Object(ObjectEvents::ChildrenChanged(e)) = event else {
+            continue;
+        };
+        if e.item.name != registry || e.item.path.as_str() != crate::ACCESSIBLE_ROOT {
+            continue;
+        }
+
+        match e.operation {
+            Operation::Insert => {
+                let description = describe_application(conn, &e.child).await;
+                println!("join:  {description}");
+                known.insert(e.child, description);
+            }
+            Operation::Delete => {
+                let description = known
+                    .remove(&e.child)
+                    .unwrap_or_else(|| format!("unknown application ({})", e.child.name.as_str()));
+                println!("leave: {description}");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Print the tree of every window that becomes active, as soon as it does,
+/// so windows that are only briefly shown can still be examined.
+async fn watch_windows(a11y: &AccessibilityConnection, senders: &[OwnedUniqueName]) -> Result<()> {
+    let conn = a11y.connection();
+
+    subscribe(a11y, &[EventFilter::parse("window:activate")?]).await?;
+    let events = a11y
+        .event_stream()
+        .take_until(crate::interrupt::cancelled());
+    futures::pin_mut!(events);
+
+    banner!("Watching windows become active, press Ctrl-C to stop...");
+
+    while let Some(event) = events.next().await {
+        let event = match event {
+            Ok(event) => event,
+            Err(e) => {
+                warn!("could not parse event: {e}");
+                continue;
+            }
+        };
+
+        let Event::Window(WindowEvents::Activate(e)) = event else {
+            continue;
+        };
+        if !senders.is_empty() && !senders.contains(&e.item.name) {
+            continue;
+        }
+
+        let description = describe_target(conn, e.item.clone()).await;
+        let tree = match accessible_from_object_ref(conn, e.item.clone()).await {
+            Ok(window) => crate::tree::configured().build(window).await,
+            Err(e) => Err(e),
+        };
+
+        println!("Activated: [{description}] ({})", e.item.name.as_str());
+        match tree {
+            Ok(tree) => crate::render::print(&tree)?,
+            Err(err) => warn!("could not get the tree of {description}: {err}"),
+        }
+        println!();
+    }
+
+    Ok(())
+}
+
+/// The text around the offset of a caret or text change event, with the offset marked by `|`,
+/// e.g. `"Hello wo|rld"`.
+async fn text_context(conn: &Connection, event: &Event) -> Option<String> {
+    let (object, offset) = match event {
+        Event::Object(ObjectEvents::TextCaretMoved(e)) => (&e.item, e.position),
+        Event::Object(ObjectEvents::TextChanged(e)) => (&e.item, e.start_pos),
+        _ => return None,
+    };
+
+    let text = match TextProxy::builder(conn)
+        .destination(object.name.clone())
+        .ok()?
+        .path(object.path.clone())
+        .ok()?
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await
+    {
+        Ok(text) => text,
+        Err(e) => return Some(format!("(Error: {e})")),
+    };
+
+    let count = match text.character_count().await {
+        Ok(count) => count,
+        Err(e) => return Some(format!("(Error: {e})")),
+    };
+    let start = (offset - TEXT_CONTEXT).max(0);
+    let end = (offset + TEXT_CONTEXT).min(count);
+    let context = match text.get_text(start, end).await {
+        Ok(context) => context,
+        Err(e) => return Some(format!("(Error: {e})")),
+    };
+
+    // Offsets count characters, not bytes.
+    let marker = usize::try_from(offset - start).unwrap_or_default();
+    let before: String = context.chars().take(marker).collect();
+    let after: String = context.chars().skip(marker).collect();
+    Some(format!("{:?}", format!("{before}|{after}")))
+}
+
+/// Name, bus name, toolkit and toolkit version of an application,
+/// e.g. `gedit (:1.57) GTK 3.24.41`.
+async fn describe_application(conn: &Connection, app: &ObjectRef) -> String {
+    let name = application_name(conn, app)
+        .await
+        .unwrap_or_else(|| "--- No name ---".to_string());
+
+    let toolkit = match application_from_object_ref(conn, app.clone()).await {
+        Ok(application) => {
+            let toolkit = application.toolkit_name().await.unwrap_or_default();
+            let version = application.version().await.unwrap_or_default();
+            format!("{toolkit} {version}").trim().to_string()
+        }
+        Err(_) => String::new(),
+    };
+
+    format!("{name} ({}) {toolkit}", app.name.as_str())
+        .trim_end()
+        .to_string()
+}
+
+/// The names of the registered applications by their unique bus name, so events
+/// can show `gedit` instead of `:1.57`.
+struct AppNames {
+    registry: OwnedUniqueName,
+    names: HashMap<String, String>,
+}
+
+impl AppNames {
+    async fn new(conn: &Connection) -> Result<AppNames> {
+        let dbus = DBusProxy::new(conn).await?;
+        let registry = unique_name(&dbus, BusName::try_from(crate::REGISTRY_DEST)?).await?;
+
+        let mut names = HashMap::new();
+        let registry_accessible = crate::get_registry_accessible(conn).await?;
+        for app in registry_accessible.get_children().await? {
+            if let Some(name) = application_name(conn, &app).await {
+                names.insert(app.name.to_string(), name);
+            }
+        }
+
+        Ok(AppNames { registry, names })
+    }
+
+    /// Keep the names current as applications join and leave the registry.
+    async fn update(&mut self, conn: &Connection, event: &Event) {
+        let Event::Object(ObjectEvents::ChildrenChanged(e)) = event else {
+            return;
+        };
+        if e.item.name != self.registry || e.item.path.as_str() != crate::ACCESSIBLE_ROOT {
+            return;
+        }
+
+        match e.operation {
+            Operation::Insert => {
+                if let Some(name) = application_name(conn, &e.child).await {
+                    self.names.insert(e.child.name.to_string(), name);
+                }
+            }
+            Operation::Delete => {
+                self.names.remove(e.child.name.as_str());
+            }
+        }
+    }
+
+    fn get(&self, sender: &str) -> Option<&String> {
+        self.names.get(sender)
+    }
+
+    /// The application name together with its bus name, e.g. `gedit (:1.57)`.
+    fn label(&self, sender: &str) -> String {
+        match self.get(sender) {
+            Some(name) => format!("{name} ({sender})"),
+            None => sender.to_string(),
+        }
+    }
+}
+
+/// The name of an application's root object, if it has one.
+async fn application_name(conn: &Connection, app: &ObjectRef) -> Option<String> {
+    let proxy = accessible_from_object_ref(conn, app.clone()).await.ok()?;
+    let name = proxy.name().await.ok()?;
+    (!name.is_empty()).then_some(name)
+}
+
+/// Print the events of a recording, with their original relative timing scaled by `speed`.
+pub async fn replay(args: ReplayArgs) -> Result<()> {
+    let filters = args
+        .event
+        .iter()
+        .map(|pattern| EventFilter::parse(pattern))
+        .collect::<Result<Vec<_>>>()?;
+
+    let recording = BufReader::new(File::open(&args.file)?);
+    let mut previous: Option<f64> = None;
+
+    for (i, line) in recording.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let recorded: RecordedEvent = serde_json::from_str(&line)
+            .map_err(|e| format!("{}:{}: invalid recording: {e}", args.file.display(), i + 1))?;
+
+        if args.speed > 0.0 {
+            if let Some(previous) = previous {
+                let delay = (recorded.monotonic - previous).max(0.0) / args.speed;
+                tokio::time::sleep(Duration::from_secs_f64(delay)).await;
+            }
+        }
+        previous = Some(recorded.monotonic);
+
+        let mut record = EventRecord::new(
+            &recorded.event,
+            recorded.wall_clock,
+            recorded.monotonic,
+            recorded.target,
+        );
+        record.ancestry = recorded.ancestry;
+        record.application = recorded.application;
+
+        if !filters.is_empty() && !filters.iter().any(|f| f.matches(&record.event_type)) {
+            continue;
+        }
+
+        record.print();
+    }
+
+    Ok(())
+}
+
+/// The event type in the registry notation, e.g. `object:state-changed:focused`.
+///
+/// The last segment, the 'kind', is only present for events that carry one.
+pub(crate) fn event_type(event: &Event) -> String {
+    let kind = match event {
+        Event::Object(ObjectEvents::StateChanged(e)) => e.state.to_string(),
+        Event::Object(ObjectEvents::ChildrenChanged(e)) => e.operation.to_string(),
+        Event::Object(ObjectEvents::TextChanged(e)) => e.operation.to_string(),
+        Event::Object(ObjectEvents::PropertyChange(e)) => e.property.clone(),
+        Event::Window(WindowEvents::PropertyChange(e)) => e.property.clone(),
+        _ => String::new(),
+    };
+
+    let registry_string = event.registry_string();
+    match (kind.is_empty(), registry_string.ends_with(':')) {
+        (true, _) => registry_string.to_string(),
+        (false, true) => format!("{registry_string}{kind}"),
+        (false, false) => format!("{registry_string}:{kind}"),
+    }
+}
+
+/// Event specific details, such as the new value of a state or the inserted text.
+pub(crate) fn event_detail(event: &Event) -> String {
+    match event {
+        Event::Object(ObjectEvents::StateChanged(e)) => e.enabled.to_string(),
+        Event::Object(ObjectEvents::ChildrenChanged(e)) => {
+            format!("index {} ({})", e.index_in_parent, e.child.path.as_str())
+        }
+        Event::Object(ObjectEvents::TextChanged(e)) => {
+            format!("{}+{} {:?}", e.start_pos, e.length, e.text)
+        }
+        Event::Object(ObjectEvents::TextCaretMoved(e)) => format!("position {}", e.position),
+        Event::Object(ObjectEvents::ActiveDescendantChanged(e)) => e.child.path.to_string(),
+        Event::Object(ObjectEvents::Announcement(e)) => format!("{:?}", e.text),
+        Event::Object(ObjectEvents::PropertyChange(e)) => format!("{:?}", e.value),
+        Event::Mouse(MouseEvents::Abs(e)) => format!("({}, {})", e.x, e.y),
+        Event::Mouse(MouseEvents::Rel(e)) => format!("({}, {})", e.x, e.y),
+        Event::Mouse(MouseEvents::Button(e)) => {
+            format!("{} ({}, {})", e.detail, e.mouse_x, e.mouse_y)
+        }
+        Event::Keyboard(KeyboardEvents::Modifiers(e)) => {
+            format!("{} -> {}", e.previous_modifiers, e.current_modifiers)
+        }
+        _ => String::new(),
+    }
+}
+
+/// Role and name of the object an event was emitted for, e.g. `push button "Save"`.
+pub(crate) async fn describe_target(conn: &Connection, object: ObjectRef) -> String {
+    match accessible_from_object_ref(conn, object).await {
+        Ok(proxy) => describe_node(&proxy).await,
+        Err(e) => format!("Error: {e}"),
+    }
+}
+
+/// Role and name of an object, e.g. `push button "Save"`.
+pub(crate) async fn describe_node(proxy: &AccessibleProxy<'_>) -> String {
+    let role = match proxy.get_role().await {
+        Ok(role) => role.name().to_string(),
+        Err(_) => "unknown role".to_string(),
+    };
+
+    match proxy.name().await {
+        Ok(name) if !name.is_empty() => format!("{role} {name:?}"),
+        _ => role,
+    }
+}
+
+/// What we know about an object, as cached by [`NodeCache`].
+#[derive(Clone)]
+struct CachedNode {
+    role: Option<Role>,
+    /// Role and name, as given by `describe_node`.
+    description: String,
+    /// `None` for the application, which is the root of its own tree.
+    parent: Option<ObjectRef>,
+}
+
+/// Cache of the objects seen in events and their ancestors.
+///
+/// Resolving an object into its ancestor path takes a few calls per ancestor,
+/// while events tend to come from the same few parts of the tree.
+#[derive(Default)]
+struct NodeCache {
+    nodes: HashMap<ObjectRef, CachedNode>,
+}
+
+impl NodeCache {
+    async fn get(&mut self, conn: &Connection, object: &ObjectRef) -> CachedNode {
+        if let Some(node) = self.nodes.get(object) {
+            return node.clone();
+        }
+
+        let node = match accessible_from_object_ref(conn, object.clone()).await {
+            Ok(proxy) => {
+                let role = proxy.get_role().await.ok();
+                // The application's parent is the desktop, which is where we stop.
+                let parent = match role {
+                    Some(Role::Application) => None,
+                    _ => proxy
+                        .parent()
+                        .await
+                        .ok()
+                        .filter(|parent| parent.path.as_str() != NULL_PATH),
+                };
+                CachedNode {
+                    role,
+                    description: describe_node(&proxy).await,
+                    parent,
+                }
+            }
+            Err(e) => CachedNode {
+                role: None,
+                description: format!("Error: {e}"),
+                parent: None,
+            },
+        };
+
+        self.nodes.insert(object.clone(), node.clone());
+        node
+    }
+
+    /// The object followed by its ancestors, up to and including the application.
+    async fn ancestry(&mut self, conn: &Connection, object: ObjectRef) -> Vec<CachedNode> {
+        let mut nodes = Vec::new();
+        let mut current = Some(object);
+
+        while let Some(object) = current {
+            if nodes.len() >= MAX_ANCESTRY_DEPTH {
+                break;
+            }
+            let node = self.get(conn, &object).await;
+            current = node.parent.clone();
+            nodes.push(node);
+        }
+
+        nodes
+    }
+
+    /// Forget objects the event tells us have changed.
+    fn invalidate(&mut self, event: &Event) {
+        match event {
+            Event::Object(ObjectEvents::PropertyChange(e)) => {
+                self.nodes.remove(&e.item);
+            }
+            Event::Object(ObjectEvents::ChildrenChanged(e)) => {
+                self.nodes.remove(&e.child);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The path from the application down to the object,
+/// e.g. `application "gedit" > frame "Untitled" > push button "Save"`.
+fn describe_ancestry(ancestry: &[CachedNode]) -> String {
+    let crumbs: Vec<&str> = ancestry
+        .iter()
+        .rev()
+        .map(|node| node.description.as_str())
+        .collect();
+    crumbs.join(" > ")
+}
+
+/// The object in the context of the window it is part of,
+/// e.g. `push button "Save" in dialog "Preferences"`.
+fn describe_in_context(ancestry: &[CachedNode]) -> String {
+    let Some((node, ancestors)) = ancestry.split_first() else {
+        return String::new();
+    };
+
+    let context = ancestors
+        .iter()
+        .find(|ancestor| {
+            ancestor
+                .role
+                .is_some_and(|role| WINDOW_ROLES.contains(&role))
+        })
+        .or(ancestors.last());
+
+    match context {
+        Some(context) => format!("{} in {}", node.description, context.description),
+        None => node.description.clone(),
+    }
+}
+
+pub(crate) async fn application_from_object_ref(
+    conn: &Connection,
+    object: ObjectRef,
+) -> Result<ApplicationProxy<'static>> {
+    let proxy = ApplicationProxy::builder(conn)
+        .destination(object.name)?
+        .path(object.path)?
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await?;
+
+    Ok(proxy)
+}
+
+/// Run `command` through the shell, with the event in its environment.
+///
+/// The command runs in the background, so a slow command does not hold up the event stream.
+fn run_command(command: &str, record: &EventRecord, path: &str) {
+    let child = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("A11Y_EVENT_TYPE", &record.event_type)
+        .env("A11Y_EVENT_DETAIL", &record.detail)
+        .env("A11Y_EVENT_SENDER", &record.sender)
+        .env(
+            "A11Y_EVENT_APPLICATION",
+            record.application.as_deref().unwrap_or_default(),
+        )
+        .env("A11Y_EVENT_PATH", path)
+        .env("A11Y_EVENT_TARGET", &record.target)
+        .env(
+            "A11Y_EVENT_ANCESTRY",
+            record.ancestry.as_deref().unwrap_or_default(),
+        )
+        .env("A11Y_EVENT_TIME", record.wall_clock.to_string())
+        .spawn();
+
+    match child {
+        // Reap the child once it is done, so it does not linger as a zombie.
+        Ok(mut child) => {
+            tokio::task::spawn_blocking(move || child.wait());
+        }
+        Err(e) => warn!("could not run {command:?}: {e}"),
+    }
+}
+
+/// Write `value` as a single line of JSON.
+fn write_json_line(file: &mut File, value: &impl Serialize) -> Result<()> {
+    let mut line = serde_json::to_string(value)?;
+    line.push('\n');
+    file.write_all(line.as_bytes())?;
+    Ok(())
+}
+
+pub(crate) async fn accessible_from_object_ref(
+    conn: &Connection,
+    object: ObjectRef,
+) -> Result<AccessibleProxy<'static>> {
+    let proxy = AccessibleProxy::builder(conn)
+        .destination(object.name)?
+        .path(object.path)?
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await?;
+
+    Ok(proxy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(pattern: &str) -> EventFilter {
+        EventFilter::parse(pattern).unwrap()
+    }
+
+    #[test]
+    fn unknown_event_classes_are_refused() {
+        let error = EventFilter::parse("objects:state-changed").err().unwrap();
+        assert!(
+            error.to_string().contains("\"objects:state-changed\""),
+            "{error}"
+        );
+        assert!(EventFilter::parse("").is_err());
+        assert!(EventFilter::parse("window").is_ok());
+    }
+
+    #[test]
+    fn trailing_star_and_missing_segments_match_anything() {
+        for pattern in ["object:state-changed:*", "object:state-changed"] {
+            let filter = filter(pattern);
+            assert!(filter.matches("object:state-changed:focused"), "{pattern}");
+            assert!(filter.matches("object:state-changed"), "{pattern}");
+            assert!(!filter.matches("object:children-changed:add"), "{pattern}");
+            assert_eq!(filter.registry_event(), "object:state-changed");
+        }
+        assert!(filter("object:*").matches("object:text-changed:insert"));
+        assert!(!filter("object:*").matches("window:activate"));
+    }
+
+    #[test]
+    fn empty_segments_match_anything() {
+        let filter = filter("object::focused");
+        assert!(filter.matches("object:state-changed:focused"));
+        assert!(!filter.matches("object:state-changed:checked"));
+        // The registry only gets the segments before the first wildcard.
+        assert_eq!(filter.registry_event(), "object:");
+        assert_eq!(self::filter("window:").registry_event(), "window:");
+    }
+
+    #[test]
+    fn segments_are_matched_whole() {
+        let filter = filter("object:state-changed:focus");
+        assert!(!filter.matches("object:state-changed:focused"));
+        assert!(!filter.matches("object:state"));
+        assert_eq!(filter.registry_event(), "object:state-changed:focus");
+    }
+}